@@ -1,8 +1,11 @@
 //! Procedural macros for the toon-macro crate.
 //!
-//! This crate provides derive macros for TOON table serialization.
+//! This crate provides derive macros for TOON table serialization, the
+//! parsers behind the `toon!`, `toon_format!`, `toon_static!`, `toon_let!`
+//! and `toon_match!` macros and the compile-time validation behind
+//! `toon_str!`.
 //! It is not intended to be used directly; instead, use the `toon-macro` crate
-//! with the `derive` feature enabled.
+//! (with the `derive` feature enabled for `#[derive(ToonTable)]`).
 //!
 //! # Derive Macros
 //!
@@ -36,7 +39,14 @@
 
 extern crate proc_macro;
 
+#[cfg(feature = "table")]
 mod table_derive;
+mod toon_dsl;
+mod toon_format;
+mod toon_pattern;
+mod toon_static;
+mod toon_str;
+#[cfg(feature = "table")]
 mod utils;
 
 use proc_macro::TokenStream;
@@ -83,16 +93,80 @@ use proc_macro::TokenStream;
 ///
 /// # Supported Types
 ///
-/// The following types are supported for table fields:
+/// Any field type implementing both `toon_macro::ToToon` and
+/// `toon_macro::FromToon` is supported, including:
 ///
-/// - `String`, `&str`
-/// - `i32`, `i64`, `u32`, `u64`
-/// - `f64`
+/// - `String`, `char`
+/// - all integer types, `f32`, `f64`
 /// - `bool`
-/// - `Option<T>` where `T` is a supported type
+/// - `Option<T>`, `Vec<T>` and `Box<T>` where `T` is a supported type
 ///
-/// For other types, implement `FromToonValue` and `IntoToonValue` manually.
+/// For other types, implement `ToToon` and `FromToon` manually.
+#[cfg(feature = "table")]
 #[proc_macro_derive(ToonTable, attributes(toon))]
 pub fn derive_toon_table(input: TokenStream) -> TokenStream {
     table_derive::derive_toon_table(input)
 }
+
+/// Implementation of the `toon!` macro.
+///
+/// This expects the runtime crate path followed by `;` and the DSL input,
+/// and is only meant to be invoked through `toon_macro::toon!`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn toon_impl(input: TokenStream) -> TokenStream {
+    toon_dsl::expand_toon(input)
+}
+
+/// Implementation of the compile-time `toon_str!` macro.
+///
+/// This expects the runtime crate path followed by `;` and a string literal,
+/// and is only meant to be invoked through `toon_macro::toon_str!`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn toon_str_impl(input: TokenStream) -> TokenStream {
+    toon_str::expand_toon_str(input)
+}
+
+/// Implementation of the `toon_format!` macro.
+///
+/// This expects the runtime crate path followed by `;`, an optional
+/// `dst,` and the DSL input, and is only meant to be invoked through
+/// `toon_macro::toon_format!`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn toon_format_impl(input: TokenStream) -> TokenStream {
+    toon_format::expand_toon_format(input)
+}
+
+/// Implementation of the `toon_let!` macro.
+///
+/// This expects the runtime crate path followed by `;` and the
+/// `pattern = expr` input, and is only meant to be invoked through
+/// `toon_macro::toon_let!`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn toon_let_impl(input: TokenStream) -> TokenStream {
+    toon_pattern::expand_toon_let(input)
+}
+
+/// Implementation of the `toon_match!` macro.
+///
+/// This expects the runtime crate path followed by `;` and the
+/// `expr, pattern => body, ...` input, and is only meant to be invoked
+/// through `toon_macro::toon_match!`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn toon_match_impl(input: TokenStream) -> TokenStream {
+    toon_pattern::expand_toon_match(input)
+}
+
+/// Implementation of the `toon_static!` macro.
+///
+/// This expects the runtime crate path followed by `;` and `static` items,
+/// and is only meant to be invoked through `toon_macro::toon_static!`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn toon_static_impl(input: TokenStream) -> TokenStream {
+    toon_static::expand_toon_static(input)
+}
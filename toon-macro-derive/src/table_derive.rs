@@ -83,7 +83,7 @@ fn derive_toon_table_impl(input: &DeriveInput) -> Result<TokenStream2> {
         .map(|f| {
             let field_name = &f.name;
             quote! {
-                ::toon_macro::table::IntoToonValue::to_toon_value(&row.#field_name)
+                ::toon_macro::internal::cell_to_toon(&row.#field_name)
             }
         })
         .collect();
@@ -102,7 +102,7 @@ fn derive_toon_table_impl(input: &DeriveInput) -> Result<TokenStream2> {
                         match col_idx {
                             Some(idx) => {
                                 let cell = ::toon_macro::table::get_cell(row, idx)?;
-                                ::toon_macro::table::FromToonValue::from_toon_value(cell)?
+                                ::toon_macro::internal::cell_from_toon(cell)?
                             }
                             None => Default::default()
                         }
@@ -114,7 +114,7 @@ fn derive_toon_table_impl(input: &DeriveInput) -> Result<TokenStream2> {
                         let col_idx = column_map.get(#column_name).copied()
                             .ok_or_else(|| ::toon_macro::Error::MissingColumn(#column_name))?;
                         let cell = ::toon_macro::table::get_cell(row, col_idx)?;
-                        ::toon_macro::table::FromToonValue::from_toon_value(cell)?
+                        ::toon_macro::internal::cell_from_toon(cell)?
                     }
                 }
             }
@@ -0,0 +1,701 @@
+//! Implementation of the `toon!` DSL.
+//!
+//! The public `toon!` macro in `toon-macro` is a thin `macro_rules!` wrapper
+//! that forwards its input here prefixed with `$crate ;`, so that the generated
+//! code can refer to the runtime crate no matter how it was imported.
+
+use std::collections::HashSet;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2, TokenTree};
+use quote::{quote, quote_spanned};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{Expr, Ident, Lit, Pat, Result, Token, braced, bracketed, parse_macro_input};
+
+/// Main entry point for the `toon!` macro.
+pub fn expand_toon(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as ToonInput);
+    let krate = &input.krate;
+    let node = input.node.expand(krate);
+    // The serde fallback for value expressions is a trait method, so it must
+    // be in scope for every `Embed(..).into_toon()` call in the expansion.
+    quote! {{
+        #[allow(unused_imports)]
+        use #krate::internal::{EmbedLegacy as _, EmbedSerialize as _};
+        #node
+    }}
+    .into()
+}
+
+/// The full macro input: the runtime crate path followed by a single value.
+struct ToonInput {
+    krate: TokenTree,
+    node: Node,
+}
+
+impl Parse for ToonInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let krate: TokenTree = input.parse()?;
+        input.parse::<Token![;]>()?;
+
+        if input.is_empty() {
+            return Err(input.error("expected a TOON value"));
+        }
+
+        let node: Node = input.parse()?;
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens after TOON value"));
+        }
+
+        Ok(ToonInput { krate, node })
+    }
+}
+
+/// A single value in the DSL.
+pub(crate) enum Node {
+    /// The `null` keyword.
+    Null,
+    /// A `{ key: value, ... }` literal.
+    Object(Vec<Member>),
+    /// A `[value, ...]` literal.
+    Array(Vec<Element>),
+    /// Any other Rust expression, converted at runtime.
+    Expr(Box<Expr>),
+}
+
+/// An item inside an object literal.
+pub(crate) enum Member {
+    /// A `key: value` pair.
+    Entry(Entry),
+    /// A `..expr` spread copying all entries of another object.
+    Spread(Box<Expr>),
+    /// An `if cond => member` entry only inserted when `cond` holds.
+    Guarded {
+        cond: Box<Expr>,
+        member: Box<Member>,
+    },
+}
+
+/// A `key: value` pair inside an object literal.
+pub(crate) struct Entry {
+    pub(crate) key: Key,
+    pub(crate) value: Node,
+    /// Whether this is a `key?: expr` entry skipped when `expr` is `None`.
+    pub(crate) optional: bool,
+}
+
+/// An item inside an array literal.
+pub(crate) enum Element {
+    /// A single value.
+    Value(Node),
+    /// A `..expr` spread splicing in all elements of another array.
+    Spread(Box<Expr>),
+    /// An `if cond => element` only included when `cond` holds.
+    Guarded {
+        cond: Box<Expr>,
+        element: Box<Element>,
+    },
+    /// A `for pat in iter if filter => element` comprehension.
+    For(Box<Comprehension>),
+}
+
+/// The parts of a `for` comprehension inside an array literal.
+pub(crate) struct Comprehension {
+    pub(crate) pat: Pat,
+    pub(crate) iter: Expr,
+    pub(crate) filter: Option<Expr>,
+    pub(crate) body: Element,
+}
+
+/// An object key.
+pub(crate) enum Key {
+    /// An identifier or literal key, known at compile time.
+    Static { name: String, span: Span },
+    /// A `[expr]` key evaluated at runtime.
+    Computed(Box<Expr>),
+}
+
+impl Parse for Node {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(syn::token::Brace) {
+            let content;
+            braced!(content in input);
+            let mut members = Vec::new();
+            while !content.is_empty() {
+                members.push(content.parse()?);
+
+                if content.is_empty() {
+                    break;
+                }
+                content.parse::<Token![,]>()?;
+            }
+            check_duplicate_keys(&members)?;
+            Ok(Node::Object(members))
+        } else if input.peek(syn::token::Bracket) {
+            let content;
+            bracketed!(content in input);
+            let mut elements = Vec::new();
+            while !content.is_empty() {
+                elements.push(content.parse()?);
+
+                if content.is_empty() {
+                    break;
+                }
+                content.parse::<Token![,]>()?;
+            }
+            Ok(Node::Array(elements))
+        } else if is_null(input) {
+            input.parse::<Ident>()?;
+            Ok(Node::Null)
+        } else {
+            Ok(Node::Expr(Box::new(input.parse()?)))
+        }
+    }
+}
+
+impl Parse for Member {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if let Some(cond) = parse_guard(input)? {
+            let member = Box::new(input.parse()?);
+            return Ok(Member::Guarded { cond, member });
+        }
+        if let Some(expr) = parse_spread(input)? {
+            return Ok(Member::Spread(expr));
+        }
+        let key: Key = input.parse()?;
+        let optional = input.peek(Token![?]);
+        if optional {
+            input.parse::<Token![?]>()?;
+        }
+        input.parse::<Token![:]>()?;
+        let value = if optional {
+            // Optional entries always take an `Option` expression.
+            Node::Expr(Box::new(input.parse()?))
+        } else {
+            input.parse()?
+        };
+        Ok(Member::Entry(Entry {
+            key,
+            value,
+            optional,
+        }))
+    }
+}
+
+impl Parse for Element {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(Token![for]) {
+            return Ok(Element::For(Box::new(input.parse()?)));
+        }
+        if let Some(cond) = parse_guard(input)? {
+            let element = Box::new(input.parse()?);
+            return Ok(Element::Guarded { cond, element });
+        }
+        match parse_spread(input)? {
+            Some(expr) => Ok(Element::Spread(expr)),
+            None => Ok(Element::Value(input.parse()?)),
+        }
+    }
+}
+
+impl Parse for Comprehension {
+    fn parse(input: ParseStream) -> Result<Self> {
+        input.parse::<Token![for]>()?;
+        let pat = Pat::parse_multi_with_leading_vert(input)?;
+        input.parse::<Token![in]>()?;
+        let iter = Expr::parse_without_eager_brace(input)?;
+        let filter = if input.peek(Token![if]) {
+            input.parse::<Token![if]>()?;
+            Some(Expr::parse_without_eager_brace(input)?)
+        } else {
+            None
+        };
+        input.parse::<Token![=>]>()?;
+        let body = input.parse()?;
+        Ok(Comprehension {
+            pat,
+            iter,
+            filter,
+            body,
+        })
+    }
+}
+
+/// Parse an `if cond =>` guard if one starts at the cursor.
+///
+/// Anything else starting with `if` (such as an `if` expression used as a
+/// value) is left for the regular value parser.
+fn parse_guard(input: ParseStream) -> Result<Option<Box<Expr>>> {
+    if !input.peek(Token![if]) {
+        return Ok(None);
+    }
+    let fork = input.fork();
+    fork.parse::<Token![if]>()?;
+    if fork.call(Expr::parse_without_eager_brace).is_err() || !fork.peek(Token![=>]) {
+        return Ok(None);
+    }
+
+    input.parse::<Token![if]>()?;
+    let cond = input.call(Expr::parse_without_eager_brace)?;
+    input.parse::<Token![=>]>()?;
+    Ok(Some(Box::new(cond)))
+}
+
+/// Parse a `..expr` spread if one starts at the cursor.
+fn parse_spread(input: ParseStream) -> Result<Option<Box<Expr>>> {
+    if input.peek(Token![..]) && !input.peek(Token![..=]) {
+        input.parse::<Token![..]>()?;
+        Ok(Some(Box::new(input.parse()?)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Reject static keys that are unconditionally inserted more than once.
+///
+/// Keys under an `if` guard may legitimately repeat (for example in
+/// `if a => k: 1, if !a => k: 2`), so those are only checked at runtime.
+fn check_duplicate_keys(members: &[Member]) -> Result<()> {
+    let mut seen = HashSet::new();
+    for member in members {
+        if let Member::Entry(Entry {
+            key: Key::Static { name, span },
+            ..
+        }) = member
+            && !seen.insert(name.as_str())
+        {
+            return Err(syn::Error::new(
+                *span,
+                format!("duplicate key `{}` in toon! object", name),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether an object needs runtime duplicate key checks: it has a computed
+/// key, or a static key that appears more than once under guards.
+pub(crate) fn needs_key_tracker(members: &[Member]) -> bool {
+    fn collect<'a>(member: &'a Member, names: &mut Vec<&'a str>) -> bool {
+        match member {
+            Member::Entry(Entry {
+                key: Key::Static { name, .. },
+                ..
+            }) => {
+                names.push(name);
+                false
+            }
+            Member::Entry(Entry {
+                key: Key::Computed(_),
+                ..
+            }) => true,
+            Member::Spread(_) => false,
+            Member::Guarded { member, .. } => collect(member, names),
+        }
+    }
+
+    let mut names = Vec::new();
+    let mut computed = false;
+    for member in members {
+        computed |= collect(member, &mut names);
+    }
+    let unique: HashSet<_> = names.iter().collect();
+    computed || unique.len() != names.len()
+}
+
+/// `null` is only treated as a keyword when it forms the whole value.
+fn is_null(input: ParseStream) -> bool {
+    let fork = input.fork();
+    match fork.parse::<Ident>() {
+        Ok(ident) if ident == "null" => fork.is_empty() || fork.peek(Token![,]),
+        _ => false,
+    }
+}
+
+impl Parse for Key {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(syn::token::Bracket) {
+            let content;
+            bracketed!(content in input);
+            let expr: Expr = content.parse()?;
+            if !content.is_empty() {
+                return Err(content.error("expected a single key expression"));
+            }
+            Ok(Key::Computed(Box::new(expr)))
+        } else if input.peek(Lit) {
+            let lit: Lit = input.parse()?;
+            let name = match &lit {
+                Lit::Str(s) => s.value(),
+                Lit::Int(i) => i.base10_digits().to_string(),
+                Lit::Float(f) => f.base10_digits().to_string(),
+                Lit::Bool(b) => b.value.to_string(),
+                Lit::Char(c) => c.value().to_string(),
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        lit,
+                        "unsupported literal as object key",
+                    ));
+                }
+            };
+            Ok(Key::Static {
+                name,
+                span: lit.span(),
+            })
+        } else if input.peek(Ident::peek_any) {
+            let ident = Ident::parse_any(input)?;
+            Ok(Key::Static {
+                name: ident.unraw().to_string(),
+                span: ident.span(),
+            })
+        } else {
+            Err(input.error("expected an identifier, literal or `[expr]` object key"))
+        }
+    }
+}
+
+impl Node {
+    /// Generate the code constructing this value.
+    pub(crate) fn expand(&self, krate: &TokenTree) -> TokenStream2 {
+        match self {
+            Node::Null => quote! { #krate::Value::Null },
+            Node::Object(members) => {
+                // Locals use mixed-site hygiene so they cannot shadow user bindings
+                // referenced from value expressions.
+                let map = Ident::new("map", Span::mixed_site());
+                let keys =
+                    needs_key_tracker(members).then(|| Ident::new("keys", Span::mixed_site()));
+                let inserts = members
+                    .iter()
+                    .map(|member| member.expand(krate, &map, keys.as_ref()));
+                let tracker = keys.as_ref().map(|keys| {
+                    quote! { let mut #keys = #krate::internal::KeyTracker::new(); }
+                });
+                let spread_import = members.iter().any(Member::has_spread).then(|| {
+                    quote! {
+                        #[allow(unused_imports)]
+                        use #krate::internal::SpreadEntries as _;
+                    }
+                });
+                quote! {{
+                    #spread_import
+                    let mut #map = #krate::internal::new_map();
+                    #tracker
+                    #(#inserts)*
+                    #krate::Value::Object(#map)
+                }}
+            }
+            Node::Array(elements) => {
+                if elements.is_empty() {
+                    return quote! { #krate::Value::Array(::std::vec::Vec::new()) };
+                }
+                if elements.iter().all(|e| matches!(e, Element::Value(_))) {
+                    let values = elements.iter().map(|e| match e {
+                        Element::Value(node) => node.expand(krate),
+                        _ => unreachable!(),
+                    });
+                    return quote! {
+                        #krate::Value::Array(::std::vec![#(#values),*])
+                    };
+                }
+
+                let vec = Ident::new("vec", Span::mixed_site());
+                let pushes = elements.iter().map(|element| element.expand(krate, &vec));
+                let spread_import = elements.iter().any(Element::has_spread).then(|| {
+                    quote! {
+                        #[allow(unused_imports)]
+                        use #krate::internal::SpreadElements as _;
+                    }
+                });
+                quote! {{
+                    #spread_import
+                    let mut #vec = ::std::vec::Vec::new();
+                    #(#pushes)*
+                    #krate::Value::Array(#vec)
+                }}
+            }
+            Node::Expr(expr) => {
+                quote_spanned! {expr.span()=>
+                    #krate::internal::Embed(#expr).into_toon()
+                }
+            }
+        }
+    }
+}
+
+impl Member {
+    /// Generate the code inserting this member into `map`.
+    ///
+    /// When `keys` is set, explicit entries are inserted through that
+    /// `KeyTracker` so colliding keys are caught in debug builds.
+    fn expand(&self, krate: &TokenTree, map: &Ident, keys: Option<&Ident>) -> TokenStream2 {
+        let insert = |key: TokenStream2, value: TokenStream2| match keys {
+            Some(keys) => quote! { #keys.insert(&mut #map, #key, #value) },
+            None => quote! { #krate::internal::map_insert(&mut #map, #key, #value) },
+        };
+        match self {
+            Member::Entry(entry) => {
+                let key = entry.key.expand();
+                if entry.optional {
+                    let Node::Expr(expr) = &entry.value else {
+                        unreachable!("optional entries always hold an expression");
+                    };
+                    let value = Ident::new("value", Span::mixed_site());
+                    let insert =
+                        insert(key, quote! { #krate::internal::Embed(#value).into_toon() });
+                    quote_spanned! {expr.span()=>
+                        if let ::std::option::Option::Some(#value) = #expr {
+                            #insert;
+                        }
+                    }
+                } else {
+                    let insert = insert(key, entry.value.expand(krate));
+                    quote_spanned! {entry.key.span()=>
+                        #insert;
+                    }
+                }
+            }
+            Member::Spread(expr) => quote_spanned! {expr.span()=>
+                #krate::internal::Spread(#expr).extend_object(&mut #map);
+            },
+            Member::Guarded { cond, member } => {
+                let inner = member.expand(krate, map, keys);
+                quote! {
+                    if #cond {
+                        #inner
+                    }
+                }
+            }
+        }
+    }
+
+    fn has_spread(&self) -> bool {
+        match self {
+            Member::Entry(_) => false,
+            Member::Spread(_) => true,
+            Member::Guarded { member, .. } => member.has_spread(),
+        }
+    }
+}
+
+impl Element {
+    /// Generate the code appending this element to `vec`.
+    fn expand(&self, krate: &TokenTree, vec: &Ident) -> TokenStream2 {
+        match self {
+            Element::Value(node) => {
+                let value = node.expand(krate);
+                quote! { #vec.push(#value); }
+            }
+            Element::Spread(expr) => quote_spanned! {expr.span()=>
+                #krate::internal::Spread(#expr).extend_array(&mut #vec);
+            },
+            Element::Guarded { cond, element } => {
+                let inner = element.expand(krate, vec);
+                quote! {
+                    if #cond {
+                        #inner
+                    }
+                }
+            }
+            Element::For(comprehension) => {
+                let Comprehension {
+                    pat,
+                    iter,
+                    filter,
+                    body,
+                } = &**comprehension;
+                let mut inner = body.expand(krate, vec);
+                if let Some(filter) = filter {
+                    inner = quote! {
+                        if #filter {
+                            #inner
+                        }
+                    };
+                }
+                quote! {
+                    for #pat in #iter {
+                        #inner
+                    }
+                }
+            }
+        }
+    }
+
+    fn has_spread(&self) -> bool {
+        match self {
+            Element::Value(_) => false,
+            Element::Spread(_) => true,
+            Element::Guarded { element, .. } => element.has_spread(),
+            Element::For(comprehension) => comprehension.body.has_spread(),
+        }
+    }
+}
+
+impl Key {
+    fn span(&self) -> Span {
+        match self {
+            Key::Static { span, .. } => *span,
+            Key::Computed(expr) => expr.span(),
+        }
+    }
+
+    /// Generate the code producing this key as a `String`.
+    fn expand(&self) -> TokenStream2 {
+        match self {
+            Key::Static { name, .. } => quote! { ::std::string::String::from(#name) },
+            Key::Computed(expr) => quote_spanned! {expr.span()=>
+                ::std::convert::Into::<::std::string::String>::into(#expr)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_object_keys() {
+        let node: Node = syn::parse_str(r#"{ name: 1, "kebab-key": 2, r#type: 3, 4: 5 }"#).unwrap();
+        let Node::Object(members) = node else {
+            panic!("expected object");
+        };
+        let keys: Vec<_> = members
+            .iter()
+            .map(|m| match m {
+                Member::Entry(Entry {
+                    key: Key::Static { name, .. },
+                    ..
+                }) => name.as_str(),
+                _ => panic!("expected static key"),
+            })
+            .collect();
+        assert_eq!(keys, ["name", "kebab-key", "type", "4"]);
+    }
+
+    #[test]
+    fn test_parse_computed_key() {
+        let node: Node = syn::parse_str(r#"{ [format!("k{}", i)]: 1, [PREFIX]: 2 }"#).unwrap();
+        let Node::Object(members) = node else {
+            panic!("expected object");
+        };
+        assert!(members.iter().all(|m| matches!(
+            m,
+            Member::Entry(Entry {
+                key: Key::Computed(_),
+                ..
+            })
+        )));
+        assert!(syn::parse_str::<Node>("{ [a b]: 1 }").is_err());
+    }
+
+    #[test]
+    fn test_parse_expressions() {
+        let node: Node = syn::parse_str("[-5, user.name, items.len(), x as u8, null]").unwrap();
+        let Node::Array(elements) = node else {
+            panic!("expected array");
+        };
+        assert_eq!(elements.len(), 5);
+        assert!(
+            elements[..4]
+                .iter()
+                .all(|e| matches!(e, Element::Value(Node::Expr(_))))
+        );
+        assert!(matches!(elements[4], Element::Value(Node::Null)));
+    }
+
+    #[test]
+    fn test_parse_optional_and_guarded() {
+        let node: Node = syn::parse_str(
+            r#"{ nick?: user.nick, if admin => role: "admin", if a > b => ..extra }"#,
+        )
+        .unwrap();
+        let Node::Object(members) = node else {
+            panic!("expected object");
+        };
+        assert!(matches!(
+            members[0],
+            Member::Entry(Entry { optional: true, .. })
+        ));
+        assert!(matches!(members[1], Member::Guarded { .. }));
+        assert!(members[2].has_spread());
+
+        let node: Node = syn::parse_str("[if flag => 1, if x { 2 } else { 3 }]").unwrap();
+        let Node::Array(elements) = node else {
+            panic!("expected array");
+        };
+        assert!(matches!(elements[0], Element::Guarded { .. }));
+        assert!(matches!(elements[1], Element::Value(Node::Expr(_))));
+    }
+
+    #[test]
+    fn test_parse_comprehension() {
+        let node: Node =
+            syn::parse_str("[for (i, u) in users.iter().enumerate() if u.active => { id: i }]")
+                .unwrap();
+        let Node::Array(elements) = node else {
+            panic!("expected array");
+        };
+        let Element::For(comprehension) = &elements[0] else {
+            panic!("expected comprehension");
+        };
+        assert!(comprehension.filter.is_some());
+        assert!(matches!(
+            comprehension.body,
+            Element::Value(Node::Object(_))
+        ));
+
+        assert!(syn::parse_str::<Node>("[for u in users { u }]").is_err());
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let err = syn::parse_str::<Node>(r#"{ name: 1, age: 2, "name": 3 }"#)
+            .err()
+            .expect("duplicate key should be rejected");
+        assert_eq!(err.to_string(), "duplicate key `name` in toon! object");
+
+        // Nested objects have their own key space.
+        assert!(syn::parse_str::<Node>("{ a: { a: 1 }, b: { a: 2 } }").is_ok());
+        // Optional entries are still unconditional keys.
+        assert!(syn::parse_str::<Node>("{ a?: x, a: 1 }").is_err());
+    }
+
+    #[test]
+    fn test_needs_key_tracker() {
+        let tracked = |src: &str| {
+            let Node::Object(members) = syn::parse_str::<Node>(src).unwrap() else {
+                panic!("expected object");
+            };
+            needs_key_tracker(&members)
+        };
+        assert!(!tracked("{ a: 1, b: 2, ..base }"));
+        assert!(!tracked("{ a: 1, if c => b: 2 }"));
+        assert!(tracked("{ a: 1, [k]: 2 }"));
+        assert!(tracked("{ if c => a: 1, if !c => a: 2 }"));
+    }
+
+    #[test]
+    fn test_parse_spread() {
+        let node: Node = syn::parse_str("{ ..base, extra: 1, ..other.clone() }").unwrap();
+        let Node::Object(members) = node else {
+            panic!("expected object");
+        };
+        assert!(matches!(members[0], Member::Spread(_)));
+        assert!(matches!(members[1], Member::Entry(_)));
+        assert!(matches!(members[2], Member::Spread(_)));
+
+        let node: Node = syn::parse_str("[..items, 4]").unwrap();
+        let Node::Array(elements) = node else {
+            panic!("expected array");
+        };
+        assert!(matches!(elements[0], Element::Spread(_)));
+        assert!(matches!(elements[1], Element::Value(_)));
+    }
+
+    #[test]
+    fn test_parse_missing_colon() {
+        assert!(syn::parse_str::<Node>("{ name 1 }").is_err());
+    }
+}
@@ -0,0 +1,213 @@
+//! Implementation of `toon_format!`.
+//!
+//! The input uses the `toon!` DSL. Objects made only of static keys are
+//! written entry by entry through `internal::FormatWriter`; any other value
+//! (arrays, expressions, objects with spreads or computed keys) is built
+//! with the regular `toon!` expansion and then encoded in one go.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2, TokenTree};
+use quote::{quote, quote_spanned};
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{Expr, Ident, Result, Token, parse_macro_input};
+
+use crate::toon_dsl::{Entry, Key, Member, Node, needs_key_tracker};
+
+/// Main entry point for the `toon_format!` macro.
+pub fn expand_toon_format(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as ToonFormatInput);
+    let krate = &input.krate;
+    let writer = Ident::new("writer", Span::mixed_site());
+    let body = write_document(krate, &writer, &input.node);
+
+    let expansion = match &input.dst {
+        Some(dst) => {
+            let init = quote_spanned! {dst.span()=>
+                (#dst).toon_writer()
+            };
+            quote! {
+                use #krate::internal::FormatTarget as _;
+                let mut #writer = #init;
+                #body
+                #writer.finish()
+            }
+        }
+        None => {
+            let out = Ident::new("out", Span::mixed_site());
+            quote! {
+                let mut #out = ::std::string::String::new();
+                let mut #writer = #krate::internal::FormatWriter::new(&mut #out);
+                #body
+                // Writing to a `String` only fails on layouts TOON cannot
+                // represent, which this form has no way to report.
+                if #writer.finish().is_err() {
+                    #krate::internal::format_failed();
+                }
+                #out
+            }
+        }
+    };
+
+    quote! {{
+        #[allow(unused_imports)]
+        use #krate::internal::{EmbedLegacy as _, EmbedSerialize as _};
+        #expansion
+    }}
+    .into()
+}
+
+/// The macro input: the runtime crate path, an optional destination and a
+/// single DSL value.
+struct ToonFormatInput {
+    krate: TokenTree,
+    dst: Option<Expr>,
+    node: Node,
+}
+
+impl Parse for ToonFormatInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let krate: TokenTree = input.parse()?;
+        input.parse::<Token![;]>()?;
+
+        // `dst, value` — a DSL value never contains a top-level comma.
+        let fork = input.fork();
+        let dst = match fork.parse::<Expr>() {
+            Ok(dst) if fork.peek(Token![,]) && !fork.peek2(syn::parse::End) => {
+                input.parse::<Expr>()?;
+                input.parse::<Token![,]>()?;
+                Some(dst)
+            }
+            _ => None,
+        };
+
+        if input.is_empty() {
+            return Err(input.error("expected a TOON value"));
+        }
+
+        let node: Node = input.parse()?;
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens after TOON value"));
+        }
+
+        Ok(ToonFormatInput { krate, dst, node })
+    }
+}
+
+/// Whether the members of an object can be written one by one: every key
+/// is static and unique, and nothing is spread in.
+fn is_streamable(members: &[Member]) -> bool {
+    fn static_entry(member: &Member) -> bool {
+        match member {
+            Member::Entry(entry) => matches!(entry.key, Key::Static { .. }),
+            Member::Spread(_) => false,
+            Member::Guarded { member, .. } => static_entry(member),
+        }
+    }
+    members.iter().all(static_entry) && !needs_key_tracker(members)
+}
+
+/// Generate the code writing `node` as a whole document.
+fn write_document(krate: &TokenTree, writer: &Ident, node: &Node) -> TokenStream2 {
+    match node {
+        Node::Object(members) if is_streamable(members) => write_members(krate, writer, members),
+        _ => {
+            let value = node.expand(krate);
+            quote! { #writer.value(&#value); }
+        }
+    }
+}
+
+fn write_members(krate: &TokenTree, writer: &Ident, members: &[Member]) -> TokenStream2 {
+    members
+        .iter()
+        .map(|member| write_member(krate, writer, member))
+        .collect()
+}
+
+fn write_member(krate: &TokenTree, writer: &Ident, member: &Member) -> TokenStream2 {
+    match member {
+        Member::Entry(entry) => write_entry(krate, writer, entry),
+        Member::Guarded { cond, member } => {
+            let inner = write_member(krate, writer, member);
+            quote! {
+                if #cond {
+                    #inner
+                }
+            }
+        }
+        Member::Spread(_) => unreachable!("objects with spreads are not streamed"),
+    }
+}
+
+fn write_entry(krate: &TokenTree, writer: &Ident, entry: &Entry) -> TokenStream2 {
+    let Key::Static { name, span } = &entry.key else {
+        unreachable!("objects with computed keys are not streamed");
+    };
+
+    if entry.optional {
+        let Node::Expr(expr) = &entry.value else {
+            unreachable!("optional entries always hold an expression");
+        };
+        let value = Ident::new("value", Span::mixed_site());
+        return quote_spanned! {expr.span()=>
+            if let ::std::option::Option::Some(#value) = #expr {
+                #writer.entry(#name, &#krate::internal::Embed(#value).into_toon());
+            }
+        };
+    }
+
+    match &entry.value {
+        Node::Object(members) if is_streamable(members) => {
+            let inner = write_members(krate, writer, members);
+            quote_spanned! {*span=>
+                #writer.begin_object(#name);
+                #inner
+                #writer.end_object();
+            }
+        }
+        value => {
+            let value = value.expand(krate);
+            quote_spanned! {*span=>
+                #writer.entry(#name, &#value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(input: &str) -> Vec<Member> {
+        match syn::parse_str::<Node>(input).unwrap() {
+            Node::Object(members) => members,
+            _ => panic!("expected an object"),
+        }
+    }
+
+    #[test]
+    fn test_is_streamable() {
+        assert!(is_streamable(&members("{}")));
+        assert!(is_streamable(&members(
+            "{ a: 1, b?: x, if c => d: { e: 2 } }"
+        )));
+        assert!(!is_streamable(&members("{ ..base, a: 1 }")));
+        assert!(!is_streamable(&members("{ [key]: 1 }")));
+        assert!(!is_streamable(&members("{ if c => a: 1, if !c => a: 2 }")));
+    }
+
+    #[test]
+    fn test_parse_destination() {
+        let input: ToonFormatInput = syn::parse_str("crate; out, { a: 1 }").unwrap();
+        assert!(input.dst.is_some());
+        assert!(matches!(input.node, Node::Object(_)));
+
+        let input: ToonFormatInput = syn::parse_str("crate; [1, 2]").unwrap();
+        assert!(input.dst.is_none());
+
+        let input: ToonFormatInput = syn::parse_str("crate; name").unwrap();
+        assert!(input.dst.is_none());
+        assert!(matches!(input.node, Node::Expr(_)));
+    }
+}
@@ -0,0 +1,608 @@
+//! Implementation of `toon_let!` and `toon_match!`.
+//!
+//! A pattern describes the shape of a `Value`. Each pattern expands to a
+//! labeled block evaluating to `Result<(bindings, ...)>`: the value is
+//! checked step by step with the `internal::match_*` helpers and the block
+//! breaks out with the first error, which names the path of the mismatch.
+
+use std::collections::HashSet;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, Span, TokenStream as TokenStream2, TokenTree};
+use quote::{ToTokens, quote};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::{
+    Block, Expr, ExprBlock, Ident, Lifetime, Lit, LitStr, Result, Token, Type, braced, bracketed,
+    parse_macro_input,
+};
+
+/// Main entry point for the `toon_let!` macro.
+pub fn expand_toon_let(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as ToonLetInput);
+    let krate = &input.krate;
+    let expr = &input.expr;
+    let scrutinee = Ident::new("scrutinee", Span::mixed_site());
+    let result = Ident::new("result", Span::mixed_site());
+    let (matcher, binds) = match expand_pattern(krate, &input.pattern, &scrutinee) {
+        Ok(expanded) => expanded,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let binds = bind_tuple(&binds);
+    let bind = match &input.else_block {
+        Some(else_block) => quote! {
+            let #binds = match #result {
+                ::std::result::Result::Ok(#result) => #result,
+                ::std::result::Result::Err(_) => #else_block,
+            };
+        },
+        None => quote! {
+            let #binds = #result?;
+        },
+    };
+    quote! {
+        let #scrutinee: &#krate::Value = &#expr;
+        let #result = #matcher;
+        #bind
+    }
+    .into()
+}
+
+/// Main entry point for the `toon_match!` macro.
+pub fn expand_toon_match(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as ToonMatchInput);
+    let krate = &input.krate;
+    let expr = &input.expr;
+    let scrutinee = Ident::new("scrutinee", Span::mixed_site());
+    let err = Ident::new("err", Span::mixed_site());
+
+    // Arms are tried in order; each one falls through to the next on error,
+    // and the error of the last arm is returned.
+    let mut expansion = quote! { ::std::result::Result::Err(#err) };
+    for (i, arm) in input.arms.iter().enumerate().rev() {
+        let (matcher, binds) = match expand_pattern(krate, &arm.pattern, &scrutinee) {
+            Ok(expanded) => expanded,
+            Err(err) => return err.to_compile_error().into(),
+        };
+        let binds = bind_tuple(&binds);
+        let body = &arm.body;
+        let err_pat = if i + 1 == input.arms.len() {
+            quote! { #err }
+        } else {
+            quote! { _ }
+        };
+        expansion = quote! {
+            match #matcher {
+                ::std::result::Result::Ok(#binds) => {
+                    ::std::result::Result::<_, #krate::Error>::Ok(#body)
+                }
+                ::std::result::Result::Err(#err_pat) => #expansion,
+            }
+        };
+    }
+
+    quote! {{
+        let #scrutinee: &#krate::Value = &#expr;
+        #expansion
+    }}
+    .into()
+}
+
+/// `toon_let!` input: `$crate ; pattern = expr` with an optional
+/// `else { ... }` block.
+struct ToonLetInput {
+    krate: TokenTree,
+    pattern: Pattern,
+    expr: Expr,
+    else_block: Option<Block>,
+}
+
+impl Parse for ToonLetInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let krate: TokenTree = input.parse()?;
+        input.parse::<Token![;]>()?;
+        let pattern: Pattern = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let expr: Expr = input.parse()?;
+        let else_block = if input.peek(Token![else]) {
+            input.parse::<Token![else]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens after toon_let! pattern"));
+        }
+        Ok(ToonLetInput {
+            krate,
+            pattern,
+            expr,
+            else_block,
+        })
+    }
+}
+
+/// `toon_match!` input: `$crate ; expr, pattern => body, ...`.
+struct ToonMatchInput {
+    krate: TokenTree,
+    expr: Expr,
+    arms: Vec<Arm>,
+}
+
+struct Arm {
+    pattern: Pattern,
+    body: Expr,
+}
+
+impl Parse for ToonMatchInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let krate: TokenTree = input.parse()?;
+        input.parse::<Token![;]>()?;
+        let expr: Expr = input.parse()?;
+        input.parse::<Token![,]>()?;
+
+        let mut arms = Vec::new();
+        while !input.is_empty() {
+            let pattern: Pattern = input.parse()?;
+            input.parse::<Token![=>]>()?;
+            // Like `match`, a block body does not need a trailing comma.
+            let (body, is_block) = if input.peek(syn::token::Brace) {
+                let block: Block = input.parse()?;
+                let body = Expr::Block(ExprBlock {
+                    attrs: Vec::new(),
+                    label: None,
+                    block,
+                });
+                (body, true)
+            } else {
+                (input.parse()?, false)
+            };
+            arms.push(Arm { pattern, body });
+
+            if input.is_empty() {
+                break;
+            }
+            if is_block {
+                input.parse::<Option<Token![,]>>()?;
+            } else {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        if arms.is_empty() {
+            return Err(input.error("expected at least one `pattern => expr` arm"));
+        }
+
+        Ok(ToonMatchInput { krate, expr, arms })
+    }
+}
+
+/// The shape of a value.
+pub(crate) enum Pattern {
+    /// `_`, matching anything.
+    Wildcard,
+    /// `null`.
+    Null,
+    /// A string, number or bool literal, optionally negated.
+    Literal { negative: bool, lit: Lit },
+    /// `name` binding a `&Value`, or `name @ Type` converting with `FromToon`.
+    Bind { binding: Binding, ty: Option<Type> },
+    /// `{ key: pattern, ... }`; keys not listed are ignored.
+    Object(Vec<(String, Pattern)>),
+    /// `[pattern, ..., rest @ .., pattern]`.
+    Array {
+        before: Vec<Pattern>,
+        rest: Option<Option<Binding>>,
+        after: Vec<Pattern>,
+    },
+}
+
+/// A bound name, with an optional `mut`.
+pub(crate) struct Binding {
+    mutability: Option<Token![mut]>,
+    name: Ident,
+}
+
+impl Parse for Binding {
+    fn parse(input: ParseStream) -> Result<Self> {
+        Ok(Binding {
+            mutability: input.parse()?,
+            name: input.parse()?,
+        })
+    }
+}
+
+impl Parse for Pattern {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(syn::token::Brace) {
+            let content;
+            braced!(content in input);
+            let mut fields = Vec::new();
+            while !content.is_empty() {
+                fields.push(parse_field(&content)?);
+                if content.is_empty() {
+                    break;
+                }
+                content.parse::<Token![,]>()?;
+            }
+            Ok(Pattern::Object(fields))
+        } else if input.peek(syn::token::Bracket) {
+            let content;
+            bracketed!(content in input);
+            let mut before = Vec::new();
+            let mut rest = None;
+            let mut after = Vec::new();
+            while !content.is_empty() {
+                if let Some(binding) = parse_rest(&content)? {
+                    if rest.is_some() {
+                        return Err(content.error("only one `..` is allowed in an array pattern"));
+                    }
+                    rest = Some(binding);
+                } else {
+                    let pattern: Pattern = content.parse()?;
+                    match rest {
+                        Some(_) => after.push(pattern),
+                        None => before.push(pattern),
+                    }
+                }
+                if content.is_empty() {
+                    break;
+                }
+                content.parse::<Token![,]>()?;
+            }
+            Ok(Pattern::Array {
+                before,
+                rest,
+                after,
+            })
+        } else if input.peek(Token![_]) {
+            input.parse::<Token![_]>()?;
+            Ok(Pattern::Wildcard)
+        } else if input.peek(Token![-]) || input.peek(Lit) {
+            let negative = input.parse::<Option<Token![-]>>()?.is_some();
+            let lit: Lit = input.parse()?;
+            match &lit {
+                Lit::Str(_) | Lit::Bool(_) if !negative => {}
+                Lit::Int(_) | Lit::Float(_) => {}
+                _ => return Err(syn::Error::new(lit.span(), "unsupported literal pattern")),
+            }
+            Ok(Pattern::Literal { negative, lit })
+        } else if input.peek(Ident) && input.fork().parse::<Ident>()? == "null" {
+            input.parse::<Ident>()?;
+            Ok(Pattern::Null)
+        } else if input.peek(Token![mut]) || input.peek(Ident) {
+            let binding: Binding = input.parse()?;
+            let ty = if input.peek(Token![@]) {
+                input.parse::<Token![@]>()?;
+                Some(input.parse()?)
+            } else {
+                None
+            };
+            Ok(Pattern::Bind { binding, ty })
+        } else {
+            Err(input.error("expected a TOON pattern"))
+        }
+    }
+}
+
+/// An object field: `key: pattern`, or the shorthand `name` / `mut name`
+/// binding the value of key `name`.
+fn parse_field(input: ParseStream) -> Result<(String, Pattern)> {
+    if input.peek(LitStr) {
+        let key: LitStr = input.parse()?;
+        input.parse::<Token![:]>()?;
+        return Ok((key.value(), input.parse()?));
+    }
+
+    if input.peek(Token![mut]) {
+        let binding: Binding = input.parse()?;
+        let key = binding.name.to_string();
+        return Ok((key, Pattern::Bind { binding, ty: None }));
+    }
+
+    let ident = Ident::parse_any(input)?;
+    let key = ident.unraw().to_string();
+    if input.peek(Token![:]) {
+        input.parse::<Token![:]>()?;
+        Ok((key, input.parse()?))
+    } else {
+        let binding = Binding {
+            mutability: None,
+            name: ident,
+        };
+        Ok((key, Pattern::Bind { binding, ty: None }))
+    }
+}
+
+/// Parse `..` or `name @ ..` if that is what follows.
+fn parse_rest(input: ParseStream) -> Result<Option<Option<Binding>>> {
+    if input.peek(Token![..]) {
+        input.parse::<Token![..]>()?;
+        return Ok(Some(None));
+    }
+    let fork = input.fork();
+    if fork.parse::<Binding>().is_ok() && fork.peek(Token![@]) && fork.peek2(Token![..]) {
+        let binding: Binding = input.parse()?;
+        input.parse::<Token![@]>()?;
+        input.parse::<Token![..]>()?;
+        return Ok(Some(Some(binding)));
+    }
+    Ok(None)
+}
+
+/// Expand `pattern` against the `&Value` in `scrutinee`, returning the
+/// labeled block and the names it binds, in order.
+fn expand_pattern(
+    krate: &TokenTree,
+    pattern: &Pattern,
+    scrutinee: &Ident,
+) -> Result<(TokenStream2, Vec<Binding>)> {
+    let mut expander = Expander {
+        krate,
+        label: Lifetime::new("'pattern", Span::mixed_site()),
+        binds: Vec::new(),
+        names: HashSet::new(),
+        counter: 0,
+    };
+    let code = expander.pattern(pattern, scrutinee, "$")?;
+    let label = &expander.label;
+    let names = expander.binds.iter().map(|binding| &binding.name);
+    let matcher = quote! {
+        #label: {
+            #code
+            ::std::result::Result::<_, #krate::Error>::Ok((#(#names,)*))
+        }
+    };
+    Ok((matcher, expander.binds))
+}
+
+/// The tuple pattern destructuring the bindings of a matched pattern.
+fn bind_tuple(binds: &[Binding]) -> TokenStream2 {
+    let binds = binds.iter().map(|Binding { mutability, name }| {
+        quote! { #mutability #name }
+    });
+    quote! { (#(#binds,)*) }
+}
+
+struct Expander<'a> {
+    krate: &'a TokenTree,
+    label: Lifetime,
+    binds: Vec<Binding>,
+    names: HashSet<String>,
+    counter: usize,
+}
+
+impl Expander<'_> {
+    /// A fresh local that cannot clash with user bindings.
+    fn local(&mut self, name: &str) -> Ident {
+        self.counter += 1;
+        Ident::new(&format!("{}{}", name, self.counter), Span::mixed_site())
+    }
+
+    /// Unwrap `result`, breaking out of the pattern block on error.
+    fn check(&self, result: TokenStream2) -> TokenStream2 {
+        let label = &self.label;
+        let value = Ident::new("value", Span::mixed_site());
+        let err = Ident::new("err", Span::mixed_site());
+        quote! {
+            match #result {
+                ::std::result::Result::Ok(#value) => #value,
+                ::std::result::Result::Err(#err) => break #label ::std::result::Result::Err(#err),
+            }
+        }
+    }
+
+    fn bind(&mut self, binding: &Binding) -> Result<()> {
+        let name = &binding.name;
+        if !self.names.insert(name.unraw().to_string()) {
+            return Err(syn::Error::new(
+                name.span(),
+                format!(
+                    "identifier `{}` is bound more than once in the same pattern",
+                    name
+                ),
+            ));
+        }
+        self.binds.push(Binding {
+            mutability: binding.mutability,
+            name: name.clone(),
+        });
+        Ok(())
+    }
+
+    /// Generate the checks and bindings for `pattern` against `value`.
+    fn pattern(&mut self, pattern: &Pattern, value: &Ident, path: &str) -> Result<TokenStream2> {
+        let krate = self.krate;
+        match pattern {
+            Pattern::Wildcard => Ok(TokenStream2::new()),
+            Pattern::Null => {
+                let check = self.check(quote! {
+                    #krate::internal::match_literal(#value, &#krate::Value::Null, "`null`", #path)
+                });
+                Ok(quote! { #check; })
+            }
+            Pattern::Literal { negative, lit } => {
+                let minus = negative.then(|| quote! { - });
+                let number = widen_integer(lit, *negative);
+                let expected = format!(
+                    "`{}{}`",
+                    if *negative { "-" } else { "" },
+                    lit.to_token_stream()
+                );
+                let check = self.check(quote! {
+                    #krate::internal::match_literal(
+                        #value,
+                        &#krate::ToToon::to_toon(&(#minus #number)),
+                        #expected,
+                        #path,
+                    )
+                });
+                Ok(quote! { #check; })
+            }
+            Pattern::Bind { binding, ty } => {
+                self.bind(binding)?;
+                let name = &binding.name;
+                Ok(match ty {
+                    Some(ty) => {
+                        let convert = self.check(quote! {
+                            #krate::internal::match_bind::<#ty>(#value, #path)
+                        });
+                        quote! { let #name: #ty = #convert; }
+                    }
+                    None => quote! { let #name: &#krate::Value = #value; },
+                })
+            }
+            Pattern::Object(fields) => {
+                let map = self.local("map");
+                let object = self.check(quote! {
+                    #krate::internal::match_object(#value, #path)
+                });
+                let mut code = quote! { let #map = #object; };
+                for (key, pattern) in fields {
+                    let field = self.local("field");
+                    let expected = format!("object with key `{}`", key);
+                    let lookup = self.check(quote! {
+                        #krate::internal::match_key(#map, #key, #expected, #path)
+                    });
+                    let inner = self.pattern(pattern, &field, &key_path(path, key))?;
+                    code.extend(quote! {
+                        let #field = #lookup;
+                        #inner
+                    });
+                }
+                Ok(code)
+            }
+            Pattern::Array {
+                before,
+                rest,
+                after,
+            } => {
+                let items = self.local("items");
+                let len = before.len() + after.len();
+                let plural = if len == 1 { "" } else { "s" };
+                let expected = match rest {
+                    Some(_) => format!("array of at least {} element{}", len, plural),
+                    None => format!("array of {} element{}", len, plural),
+                };
+                let exact = rest.is_none();
+                let array = self.check(quote! {
+                    #krate::internal::match_array(#value, #len, #exact, #expected, #path)
+                });
+                let mut code = quote! { let #items = #array; };
+
+                for (i, pattern) in before.iter().enumerate() {
+                    let element = self.local("element");
+                    let inner = self.pattern(pattern, &element, &format!("{}[{}]", path, i))?;
+                    code.extend(quote! {
+                        let #element = &#items[#i];
+                        #inner
+                    });
+                }
+                if let Some(Some(binding)) = rest {
+                    self.bind(binding)?;
+                    let name = &binding.name;
+                    let (start, end) = (before.len(), after.len());
+                    code.extend(quote! {
+                        let #name: &[#krate::Value] = &#items[#start..#items.len() - #end];
+                    });
+                }
+                for (i, pattern) in after.iter().enumerate() {
+                    let element = self.local("element");
+                    let from_end = after.len() - i;
+                    let inner =
+                        self.pattern(pattern, &element, &format!("{}[-{}]", path, from_end))?;
+                    code.extend(quote! {
+                        let #element = &#items[#items.len() - #from_end];
+                        #inner
+                    });
+                }
+                Ok(code)
+            }
+        }
+    }
+}
+
+/// An unsuffixed integer literal with the narrowest of `i64`, `u64` and
+/// `i128` that holds it, so that `{ size: 3000000000 }` is not read as an
+/// overflowing `i32`. Other literals are returned unchanged.
+fn widen_integer(lit: &Lit, negative: bool) -> TokenStream2 {
+    let Lit::Int(int) = lit else {
+        return lit.to_token_stream();
+    };
+    // The minus sign is emitted separately, so `n` is the magnitude.
+    let suffix = match int.base10_parse::<u128>() {
+        _ if !int.suffix().is_empty() => return lit.to_token_stream(),
+        Ok(n) if n <= i64::MAX as u128 + negative as u128 => "i64",
+        Ok(n) if !negative && n <= u64::MAX as u128 => "u64",
+        Ok(n) if n <= i128::MAX as u128 + negative as u128 => "i128",
+        _ => return lit.to_token_stream(),
+    };
+    let mut widened: Literal = format!("{}{}", int.base10_digits(), suffix)
+        .parse()
+        .expect("integer literal");
+    widened.set_span(int.span());
+    widened.into_token_stream()
+}
+
+/// The path of `key` inside the object at `path`, JSONPath style.
+fn key_path(path: &str, key: &str) -> String {
+    let mut chars = key.chars();
+    let simple = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if simple {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}[{:?}]", path, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pattern() {
+        let pattern: Pattern = syn::parse_str(
+            "{ user: { name: n @ String, age }, tags: [first, rest @ .., -1], _x: _ }",
+        )
+        .unwrap();
+        let Pattern::Object(fields) = pattern else {
+            panic!("expected an object pattern");
+        };
+        assert_eq!(fields.len(), 3);
+        assert!(matches!(fields[0].1, Pattern::Object(_)));
+        assert!(matches!(
+            &fields[1].1,
+            Pattern::Array { before, rest: Some(Some(_)), after } if before.len() == 1 && after.len() == 1
+        ));
+        assert!(matches!(fields[2].1, Pattern::Wildcard));
+    }
+
+    #[test]
+    fn test_parse_pattern_errors() {
+        assert!(syn::parse_str::<Pattern>("[.., ..]").is_err());
+        assert!(syn::parse_str::<Pattern>("-\"x\"").is_err());
+        assert!(syn::parse_str::<Pattern>("{ \"key\" }").is_err());
+    }
+
+    #[test]
+    fn test_duplicate_bindings() {
+        let krate: TokenTree = syn::parse_str("crate").unwrap();
+        let scrutinee = Ident::new("scrutinee", Span::call_site());
+        let pattern: Pattern = syn::parse_str("{ a: x, b: [x] }").unwrap();
+        let err = expand_pattern(&krate, &pattern, &scrutinee).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "identifier `x` is bound more than once in the same pattern"
+        );
+    }
+
+    #[test]
+    fn test_key_path() {
+        assert_eq!(key_path("$", "user"), "$.user");
+        assert_eq!(key_path("$.user", "first name"), "$.user[\"first name\"]");
+        assert_eq!(key_path("$", "1st"), "$[\"1st\"]");
+    }
+}
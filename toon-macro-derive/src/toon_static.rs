@@ -0,0 +1,345 @@
+//! Implementation of `toon_static!`.
+//!
+//! Each `static NAME = value;` item becomes a `LazyLock<Value>` built with
+//! the `toon!` expansion. When the value is made only of literals it is
+//! also evaluated here, so the static is built without runtime conversions
+//! and a `NAME_TOON` constant holds its encoded text.
+
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote};
+use serde_toon2::{Map, Number, Value};
+use syn::parse::{Parse, ParseStream};
+use syn::{Attribute, Expr, ExprLit, ExprUnary, Ident, Lit, Result, Token, UnOp, Visibility};
+
+use crate::toon_dsl::{Element, Entry, Key, Member, Node};
+use crate::toon_str::value_tokens;
+
+/// Main entry point for the `toon_static!` macro.
+pub fn expand_toon_static(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as ToonStaticInput);
+    let krate = &input.krate;
+    input
+        .items
+        .iter()
+        .map(|item| item.expand(krate))
+        .collect::<Result<TokenStream2>>()
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// The macro input: the runtime crate path followed by `static` items.
+struct ToonStaticInput {
+    krate: TokenTree,
+    items: Vec<StaticItem>,
+}
+
+impl Parse for ToonStaticInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let krate: TokenTree = input.parse()?;
+        input.parse::<Token![;]>()?;
+        let mut items = Vec::new();
+        while !input.is_empty() {
+            items.push(input.parse()?);
+        }
+        Ok(ToonStaticInput { krate, items })
+    }
+}
+
+/// `#[attrs] vis static NAME = value;`
+struct StaticItem {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    name: Ident,
+    node: Node,
+}
+
+impl Parse for StaticItem {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis: Visibility = input.parse()?;
+        input.parse::<Token![static]>()?;
+        let name: Ident = input.parse()?;
+        if input.peek(Token![:]) {
+            return Err(input.error(
+                "toon_static! items have no type annotation; they are always `LazyLock<Value>`",
+            ));
+        }
+        input.parse::<Token![=]>()?;
+        let node: Node = input.parse()?;
+        input.parse::<Token![;]>()?;
+        Ok(StaticItem {
+            attrs,
+            vis,
+            name,
+            node,
+        })
+    }
+}
+
+impl StaticItem {
+    fn expand(&self, krate: &TokenTree) -> Result<TokenStream2> {
+        let StaticItem {
+            attrs,
+            vis,
+            name,
+            node,
+        } = self;
+        let literal = literal_value(node);
+
+        let init = match &literal {
+            Some(value) => value_tokens(krate, value),
+            None => {
+                let node = node.expand(krate);
+                quote! {{
+                    #[allow(unused_imports)]
+                    use #krate::internal::{EmbedLegacy as _, EmbedSerialize as _};
+                    #node
+                }}
+            }
+        };
+        let mut expansion = quote! {
+            #(#attrs)*
+            #vis static #name: ::std::sync::LazyLock<#krate::Value> =
+                ::std::sync::LazyLock::new(|| #init);
+        };
+
+        if let Some(value) = literal {
+            if !is_encodable(&value) {
+                return Err(syn::Error::new(
+                    name.span(),
+                    "value cannot be encoded as TOON: an array inside an array holds arrays or objects",
+                ));
+            }
+            let text = serde_toon2::to_string(&value)
+                .map_err(|err| syn::Error::new(name.span(), err.to_string()))?;
+            let text_name = format_ident!("{}_TOON", name);
+            let doc = format!("Pre-encoded TOON text of [`{}`].", name);
+            let other_attrs = attrs.iter().filter(|attr| !attr.path().is_ident("doc"));
+            expansion.extend(quote! {
+                #[doc = #doc]
+                #(#other_attrs)*
+                #vis const #text_name: &str = #text;
+            });
+        }
+        Ok(expansion)
+    }
+}
+
+/// The value of `node` if it is made only of literals, computed exactly as
+/// the `toon!` expansion would at runtime.
+fn literal_value(node: &Node) -> Option<Value> {
+    match node {
+        Node::Null => Some(Value::Null),
+        Node::Expr(expr) => literal_expr(expr),
+        Node::Object(members) => {
+            let mut map = Map::new();
+            for member in members {
+                let Member::Entry(Entry {
+                    key: Key::Static { name, .. },
+                    value,
+                    optional: false,
+                }) = member
+                else {
+                    return None;
+                };
+                map.insert(name.clone(), literal_value(value)?);
+            }
+            Some(Value::Object(map))
+        }
+        Node::Array(elements) => elements
+            .iter()
+            .map(|element| match element {
+                Element::Value(node) => literal_value(node),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Array),
+    }
+}
+
+fn literal_expr(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Lit(ExprLit { lit, .. }) => literal(lit, false),
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => match &**expr {
+            Expr::Lit(ExprLit { lit, .. }) => literal(lit, true),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Convert a literal with the `ToToon` impl of its Rust type: integers
+/// that fit are `I64`, then `U64`, and only 128-bit integers beyond both a
+/// lossy `F64`; floats are `F64`. Integers out of range for their type
+/// (unsuffixed ones are `i32`) are left to the runtime expansion, which
+/// rejects them as `toon!` does.
+fn literal(lit: &Lit, negative: bool) -> Option<Value> {
+    let number = match lit {
+        Lit::Str(s) if !negative => return Some(Value::String(s.value())),
+        Lit::Char(c) if !negative => return Some(Value::String(c.value().to_string())),
+        Lit::Bool(b) if !negative => return Some(Value::Bool(b.value)),
+        Lit::Int(int) => match int.suffix() {
+            suffix @ ("" | "i8" | "i16" | "i32" | "i64" | "isize" | "i128") => {
+                let (min, max) = match suffix {
+                    "i8" => (i8::MIN as i128, i8::MAX as i128),
+                    "i16" => (i16::MIN as i128, i16::MAX as i128),
+                    "" | "i32" => (i32::MIN as i128, i32::MAX as i128),
+                    "i64" | "isize" => (i64::MIN as i128, i64::MAX as i128),
+                    _ => (i128::MIN, i128::MAX),
+                };
+                let n = if negative {
+                    // `-170141183460469231731687303715884105728i128` is `i128::MIN`.
+                    let n = int.base10_parse::<u128>().ok()?;
+                    0i128.checked_sub_unsigned(n)?
+                } else {
+                    int.base10_parse::<i128>().ok()?
+                };
+                if n < min || n > max {
+                    return None;
+                }
+                integer(n)
+            }
+            suffix @ ("u8" | "u16" | "u32" | "u64" | "usize" | "u128") if !negative => {
+                let max = match suffix {
+                    "u8" => u8::MAX as u128,
+                    "u16" => u16::MAX as u128,
+                    "u32" => u32::MAX as u128,
+                    "u64" | "usize" => u64::MAX as u128,
+                    _ => u128::MAX,
+                };
+                let n = int.base10_parse::<u128>().ok()?;
+                if n > max {
+                    return None;
+                }
+                match u64::try_from(n) {
+                    Ok(n) => Number::U64(n),
+                    Err(_) => Number::F64(n as f64),
+                }
+            }
+            "f32" => float(int.base10_parse::<f32>().ok()? as f64, negative),
+            "f64" => float(int.base10_parse::<f64>().ok()?, negative),
+            _ => return None,
+        },
+        Lit::Float(lit) => match lit.suffix() {
+            "" | "f64" => float(lit.base10_parse::<f64>().ok()?, negative),
+            "f32" => float(lit.base10_parse::<f32>().ok()? as f64, negative),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(Value::Number(number))
+}
+
+/// The number `ToToon for i128` gives, which agrees with the narrower
+/// signed types wherever they fit.
+fn integer(n: i128) -> Number {
+    if let Ok(n) = i64::try_from(n) {
+        Number::I64(n)
+    } else if let Ok(n) = u64::try_from(n) {
+        Number::U64(n)
+    } else {
+        Number::F64(n as f64)
+    }
+}
+
+fn float(n: f64, negative: bool) -> Number {
+    Number::F64(if negative { -n } else { n })
+}
+
+/// Whether `value` can be encoded as TOON.
+///
+/// An array inside another array is written inline, so it can only hold
+/// primitives: `[[1, 2], [3]]` can be encoded but `[[1, [2]]]` cannot, and
+/// `serde_toon2` panics on it.
+fn is_encodable(value: &Value) -> bool {
+    match value {
+        Value::Array(items) => items.iter().all(|item| match item {
+            Value::Array(inner) => inner
+                .iter()
+                .all(|v| !matches!(v, Value::Array(_) | Value::Object(_))),
+            other => is_encodable(other),
+        }),
+        Value::Object(map) => map.values().all(is_encodable),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal_of(input: &str) -> Option<Value> {
+        literal_value(&syn::parse_str::<Node>(input).unwrap())
+    }
+
+    #[test]
+    fn test_literal_values() {
+        assert_eq!(literal_of("null"), Some(Value::Null));
+        assert_eq!(literal_of("-5"), Some(Value::Number(Number::I64(-5))));
+        assert_eq!(literal_of("5u8"), Some(Value::Number(Number::U64(5))));
+        assert_eq!(literal_of("-0.0"), Some(Value::Number(Number::F64(-0.0))));
+        assert_eq!(
+            literal_of("0.1f32"),
+            Some(Value::Number(Number::F64(0.1f32 as f64)))
+        );
+        assert_eq!(literal_of("'x'"), Some(Value::String("x".into())));
+        assert_eq!(
+            literal_of("18446744073709551615i128"),
+            Some(Value::Number(Number::U64(u64::MAX)))
+        );
+        assert_eq!(
+            literal_of("-170141183460469231731687303715884105728i128"),
+            Some(Value::Number(Number::F64(i128::MIN as f64)))
+        );
+
+        let value = literal_of(r#"{ a: [1, "two", true], "b c": {} }"#).unwrap();
+        assert_eq!(
+            serde_toon2::to_string(&value).unwrap(),
+            "a[3]: 1,two,true\n\"b c\":"
+        );
+    }
+
+    #[test]
+    fn test_non_literal_values() {
+        assert_eq!(literal_of("x"), None);
+        assert_eq!(literal_of("-x"), None);
+        assert_eq!(literal_of("-1u8"), None);
+        // Out of range for their type, left to the compiler to reject.
+        assert_eq!(literal_of("3000000000"), None);
+        assert_eq!(literal_of("256u8"), None);
+        assert_eq!(literal_of("-129i8"), None);
+        assert_eq!(literal_of("9223372036854775808i64"), None);
+        assert_eq!(literal_of("{ a: 1, b?: x }"), None);
+        assert_eq!(literal_of("{ a: 1, ..base }"), None);
+        assert_eq!(literal_of("{ [key]: 1 }"), None);
+        assert_eq!(literal_of("[1, if c => 2]"), None);
+        assert_eq!(literal_of("[for x in xs => x]"), None);
+    }
+
+    #[test]
+    fn test_unencodable_literal_is_an_error() {
+        let item: StaticItem = syn::parse_str("static GRID = [[1, [2]]];").unwrap();
+        let krate: TokenTree = syn::parse_str("toon_macro").unwrap();
+        let err = item.expand(&krate).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("value cannot be encoded as TOON")
+        );
+    }
+
+    #[test]
+    fn test_is_encodable() {
+        let parse = |s: &str| serde_toon2::from_str::<Value>(s).unwrap();
+        assert!(is_encodable(&parse("a[2]:\n  - [2]: 1,2\n  - [0]:")));
+        assert!(is_encodable(&parse("a[1]:\n  - b[1]:\n    - [1]: 1")));
+
+        let nested = Value::Array(vec![Value::Array(vec![Value::Array(vec![])])]);
+        assert!(!is_encodable(&nested));
+        assert!(!is_encodable(&literal_of("{ a: [[[]]] }").unwrap()));
+    }
+}
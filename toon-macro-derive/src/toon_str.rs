@@ -0,0 +1,517 @@
+//! Implementation of the compile-time `toon_str!` macro.
+//!
+//! The string literal is parsed with the same `serde_toon2` parser used at
+//! runtime by `from_toon_str`, so both produce identical values. Parse errors
+//! are reported as compile errors, and a successful parse expands to code
+//! that builds the resulting `Value` directly.
+//!
+//! `#{expr}` placeholders are replaced by unique unquoted-string markers
+//! before parsing. Every string value equal to a marker is then generated as
+//! a conversion of its expression, so injected values are never spliced into
+//! the TOON text itself.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, Span, TokenStream as TokenStream2, TokenTree};
+use quote::quote;
+use serde_toon2::{Number, Value};
+use syn::parse::Parser;
+use syn::parse::{Parse, ParseStream};
+use syn::{Expr, Ident, LitStr, Result, Token, parse_macro_input};
+
+/// Entry point for `toon_str!` with a string literal argument.
+pub fn expand_toon_str(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as ToonStrInput);
+    match input.expand() {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// The macro input: the runtime crate path followed by a string literal.
+struct ToonStrInput {
+    krate: TokenTree,
+    lit: LitStr,
+}
+
+impl Parse for ToonStrInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let krate: TokenTree = input.parse()?;
+        input.parse::<Token![;]>()?;
+        let lit: LitStr = input.parse()?;
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens after TOON string literal"));
+        }
+        Ok(ToonStrInput { krate, lit })
+    }
+}
+
+impl ToonStrInput {
+    fn expand(&self) -> Result<TokenStream2> {
+        let text = self.lit.value();
+        let template = Template::parse(&self.lit, &text)?;
+        match serde_toon2::from_str::<Value>(&template.text) {
+            Ok(value) => {
+                let krate = &self.krate;
+                let value = template.value_tokens(krate, &value)?;
+                if template.exprs.is_empty() {
+                    Ok(value)
+                } else {
+                    Ok(quote! {{
+                        #[allow(unused_imports)]
+                        use #krate::internal::{EmbedLegacy as _, EmbedSerialize as _};
+                        #value
+                    }})
+                }
+            }
+            Err(err) => {
+                let mut err = ParseError::new(&err);
+                err.line = err.line.map(|line| template.original_line(line));
+                err.snippet = err
+                    .line
+                    .and_then(|line| text.lines().nth(line - 1))
+                    .map(str::to_string);
+                let span = err
+                    .line
+                    .and_then(|line| line_span(&self.lit, &text, line))
+                    .unwrap_or_else(|| self.lit.span());
+                Err(syn::Error::new(span, err))
+            }
+        }
+    }
+}
+
+/// TOON text with its `#{expr}` placeholders replaced by markers.
+struct Template {
+    /// The rewritten text passed to the parser.
+    text: String,
+    /// Prefix of every marker; marker `i` is `{prefix}{i}_`.
+    prefix: String,
+    /// Placeholder expressions, indexed by marker number.
+    exprs: Vec<Expr>,
+    /// `(rewritten line, newlines removed)` for multi-line placeholders.
+    removed_lines: Vec<(usize, usize)>,
+}
+
+impl Template {
+    fn parse(lit: &LitStr, text: &str) -> Result<Self> {
+        let mut prefix = String::from("__toon_interp_");
+        while text.contains(&prefix) {
+            prefix.push('_');
+        }
+
+        let mut template = Template {
+            text: String::with_capacity(text.len()),
+            prefix,
+            exprs: Vec::new(),
+            removed_lines: Vec::new(),
+        };
+        let mut rest = text;
+        while let Some(pos) = rest.find("#{") {
+            // `##{` is an escaped, literal `#{`
+            if rest[..pos].ends_with('#') {
+                template.text.push_str(&rest[..pos - 1]);
+                template.text.push_str("#{");
+                rest = &rest[pos + 2..];
+                continue;
+            }
+
+            template.text.push_str(&rest[..pos]);
+            let body = &rest[pos + 2..];
+            let len = placeholder_len(body).ok_or_else(|| {
+                syn::Error::new(lit.span(), "unterminated `#{` placeholder in toon_str!")
+            })?;
+            let source = &body[..len];
+            if source.trim().is_empty() {
+                return Err(syn::Error::new(
+                    lit.span(),
+                    "empty `#{}` placeholder in toon_str!",
+                ));
+            }
+            template.push_expr(lit, source)?;
+            rest = &body[len + 1..];
+        }
+        template.text.push_str(rest);
+        Ok(template)
+    }
+
+    fn push_expr(&mut self, lit: &LitStr, source: &str) -> Result<()> {
+        let tokens: TokenStream2 = source.parse().map_err(|_| {
+            syn::Error::new(
+                lit.span(),
+                format!(
+                    "invalid expression in toon_str! placeholder `#{{{}}}`",
+                    source
+                ),
+            )
+        })?;
+        // Point diagnostics for the expression at the literal it came from.
+        let tokens = respan(tokens, lit.span());
+        let expr = Expr::parse.parse2(tokens)?;
+
+        let newlines = source.matches('\n').count();
+        if newlines > 0 {
+            let line = self.text.matches('\n').count() + 1;
+            self.removed_lines.push((line, newlines));
+        }
+        self.text.push_str(&self.prefix);
+        self.text.push_str(&self.exprs.len().to_string());
+        self.text.push('_');
+        self.exprs.push(expr);
+        Ok(())
+    }
+
+    /// Map a line of the rewritten text back to the literal.
+    fn original_line(&self, line: usize) -> usize {
+        line + self
+            .removed_lines
+            .iter()
+            .filter(|(at, _)| *at < line)
+            .map(|(_, n)| n)
+            .sum::<usize>()
+    }
+
+    /// The placeholder expression a parsed string stands for, if any.
+    fn placeholder(&self, s: &str) -> Result<Option<&Expr>> {
+        if !s.contains(&self.prefix) {
+            return Ok(None);
+        }
+        let expr = s
+            .strip_prefix(&self.prefix)
+            .and_then(|index| index.strip_suffix('_'))
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| self.exprs.get(index));
+        match expr {
+            Some(expr) => Ok(Some(expr)),
+            None => Err(syn::Error::new(
+                self.span(),
+                "a `#{}` placeholder in toon_str! must be a whole value, not part of a string",
+            )),
+        }
+    }
+
+    fn span(&self) -> Span {
+        self.exprs
+            .first()
+            .map(syn::spanned::Spanned::span)
+            .unwrap_or_else(Span::call_site)
+    }
+
+    /// Generate code constructing `value`, substituting placeholders.
+    fn value_tokens(&self, krate: &TokenTree, value: &Value) -> Result<TokenStream2> {
+        Ok(match value {
+            Value::String(s) => match self.placeholder(s)? {
+                Some(expr) => quote! { #krate::internal::Embed(#expr).into_toon() },
+                None => value_tokens(krate, value),
+            },
+            Value::Array(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| self.value_tokens(krate, item))
+                    .collect::<Result<Vec<_>>>()?;
+                quote! { #krate::Value::Array(::std::vec![#(#items),*]) }
+            }
+            Value::Object(entries) => {
+                let map = Ident::new("map", Span::mixed_site());
+                let mut inserts = Vec::with_capacity(entries.len());
+                for (key, value) in entries {
+                    if key.contains(&self.prefix) {
+                        return Err(syn::Error::new(
+                            self.span(),
+                            "`#{}` placeholders in toon_str! are only supported in value position",
+                        ));
+                    }
+                    let value = self.value_tokens(krate, value)?;
+                    inserts.push(quote! {
+                        #krate::internal::map_insert(
+                            &mut #map,
+                            ::std::string::String::from(#key),
+                            #value,
+                        );
+                    });
+                }
+                quote! {{
+                    let mut #map = #krate::internal::new_map();
+                    #(#inserts)*
+                    #krate::Value::Object(#map)
+                }}
+            }
+            _ => value_tokens(krate, value),
+        })
+    }
+}
+
+/// Length of a placeholder body up to (not including) its closing `}`,
+/// skipping over nested braces, string and raw string literals, and char
+/// literals (but not lifetimes such as `'a`).
+fn placeholder_len(body: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut i = 0;
+    while let Some(c) = body[i..].chars().next() {
+        let rest = &body[i + c.len_utf8()..];
+        // Where scanning resumes when `c` starts a literal.
+        let skip_to = match c {
+            '{' => {
+                depth += 1;
+                None
+            }
+            '}' if depth == 0 => return Some(i),
+            '}' => {
+                depth -= 1;
+                None
+            }
+            '"' => Some(after_quote(rest, '"')?),
+            '\'' => {
+                let mut lit = rest.chars();
+                match (lit.next(), lit.next()) {
+                    (Some('\\'), _) | (_, Some('\'')) => Some(after_quote(rest, '\'')?),
+                    _ => None,
+                }
+            }
+            'r' if matches!(identifier_before(&body[..i]), "" | "b") => {
+                let hashes = rest.len() - rest.trim_start_matches('#').len();
+                match rest[hashes..].strip_prefix('"') {
+                    Some(raw) => {
+                        let end = format!("\"{}", "#".repeat(hashes));
+                        Some(&raw[raw.find(&end)? + end.len()..])
+                    }
+                    None => None,
+                }
+            }
+            _ => None,
+        };
+        i = match skip_to {
+            Some(after) => body.len() - after.len(),
+            None => i + c.len_utf8(),
+        };
+    }
+    None
+}
+
+/// The text after the `quote` that closes a literal starting at `rest`.
+fn after_quote(rest: &str, quote: char) -> Option<&str> {
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == quote {
+            return Some(&rest[i + c.len_utf8()..]);
+        }
+    }
+    None
+}
+
+/// The identifier characters at the end of `text`.
+fn identifier_before(text: &str) -> &str {
+    let start = text
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map_or(0, |i| {
+            i + text[i..].chars().next().map_or(0, char::len_utf8)
+        });
+    &text[start..]
+}
+
+fn respan(tokens: TokenStream2, span: Span) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .map(|mut token| {
+            if let TokenTree::Group(group) = &token {
+                let mut inner =
+                    proc_macro2::Group::new(group.delimiter(), respan(group.stream(), span));
+                inner.set_span(span);
+                token = TokenTree::Group(inner);
+            } else {
+                token.set_span(span);
+            }
+            token
+        })
+        .collect()
+}
+
+/// A `serde_toon2` parse error, split into its message and location.
+struct ParseError {
+    message: String,
+    line: Option<usize>,
+    snippet: Option<String>,
+}
+
+impl ParseError {
+    fn new(err: &serde_toon2::Error) -> Self {
+        // serde_toon2 only exposes the location of an error through its
+        // `Display` output, as "<message> at line N, column M". The runtime
+        // crate reads it back the same way.
+        let message = err.to_string();
+        let located = message.rsplit_once(" at line ").and_then(|(text, loc)| {
+            let (line, _column) = loc.split_once(", column ")?;
+            Some((text.to_string(), line.parse::<usize>().ok()?))
+        });
+        match located {
+            Some((message, line)) => ParseError {
+                message,
+                line: Some(line),
+                snippet: None,
+            },
+            None => ParseError {
+                message,
+                line: None,
+                snippet: None,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid TOON in toon_str!: {}", self.message)?;
+        if let Some(line) = self.line {
+            write!(f, " (line {} of the literal)", line)?;
+            if let Some(snippet) = &self.snippet {
+                write!(f, "\n{} | {}", line, snippet)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Span of a line inside the literal, when the compiler supports subspans
+/// and the literal's source text matches its value (no escapes).
+fn line_span(lit: &LitStr, text: &str, line: usize) -> Option<Span> {
+    let source = lit.token().to_string();
+    let body_start = if source.starts_with('r') {
+        source.find('"')? + 1
+    } else if source.contains('\\') {
+        return None;
+    } else {
+        1
+    };
+
+    let offset: usize = text.lines().take(line - 1).map(|l| l.len() + 1).sum();
+    let len = text.lines().nth(line - 1)?.len();
+    let start = body_start + offset;
+    lit.token().subspan(start..start + len)
+}
+
+/// Generate code constructing `value` through the runtime crate `krate`.
+pub(crate) fn value_tokens(krate: &TokenTree, value: &Value) -> TokenStream2 {
+    match value {
+        Value::Null => quote! { #krate::Value::Null },
+        Value::Bool(b) => quote! { #krate::Value::Bool(#b) },
+        Value::Number(n) => {
+            let number = match n {
+                Number::I64(n) => {
+                    let lit = Literal::i64_suffixed(*n);
+                    quote! { #krate::Number::I64(#lit) }
+                }
+                Number::U64(n) => {
+                    let lit = Literal::u64_suffixed(*n);
+                    quote! { #krate::Number::U64(#lit) }
+                }
+                Number::F64(n) if n.is_finite() => {
+                    let lit = Literal::f64_suffixed(*n);
+                    quote! { #krate::Number::F64(#lit) }
+                }
+                Number::F64(n) => {
+                    let bits = n.to_bits();
+                    quote! { #krate::Number::F64(f64::from_bits(#bits)) }
+                }
+            };
+            quote! { #krate::Value::Number(#number) }
+        }
+        Value::String(s) => quote! { #krate::Value::String(::std::string::String::from(#s)) },
+        Value::Array(items) => {
+            let items = items.iter().map(|item| value_tokens(krate, item));
+            quote! { #krate::Value::Array(::std::vec![#(#items),*]) }
+        }
+        Value::Object(entries) => {
+            let map = Ident::new("map", Span::mixed_site());
+            let inserts = entries.iter().map(|(key, value)| {
+                let value = value_tokens(krate, value);
+                quote! {
+                    #krate::internal::map_insert(
+                        &mut #map,
+                        ::std::string::String::from(#key),
+                        #value,
+                    );
+                }
+            });
+            quote! {{
+                let mut #map = #krate::internal::new_map();
+                #(#inserts)*
+                #krate::Value::Object(#map)
+            }}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_error_location() {
+        let err = serde_toon2::from_str::<Value>("a: 1\nb").unwrap_err();
+        // `ParseError::new` depends on this exact wording.
+        assert_eq!(
+            err.to_string(),
+            "Missing colon after key at line 2, column 1"
+        );
+        let mut err = ParseError::new(&err);
+        assert_eq!(err.line, Some(2));
+        err.snippet = Some("b".into());
+        assert_eq!(
+            err.to_string(),
+            "invalid TOON in toon_str!: Missing colon after key (line 2 of the literal)\n2 | b"
+        );
+    }
+
+    #[test]
+    fn test_parse_error_without_location() {
+        let err = ParseError::new(&serde_toon2::Error::custom("something went wrong"));
+        assert_eq!(err.line, None);
+        assert_eq!(
+            err.to_string(),
+            "invalid TOON in toon_str!: something went wrong"
+        );
+    }
+
+    #[test]
+    fn test_placeholder_len() {
+        assert_eq!(placeholder_len("x} rest"), Some(1));
+        assert_eq!(placeholder_len("f({ 1 })}"), Some(8));
+        assert_eq!(placeholder_len(r#"format!("{}}", x)}"#), Some(17));
+        assert_eq!(placeholder_len("x"), None);
+        assert_eq!(placeholder_len("c == '}'} rest"), Some(8));
+        assert_eq!(placeholder_len("c != '{'}"), Some(8));
+        assert_eq!(placeholder_len(r"c == '\''}"), Some(9));
+        assert_eq!(placeholder_len(r#"r"}" == s}"#), Some(9));
+        assert_eq!(placeholder_len(r###"br#"}"#"###), None);
+        assert_eq!(placeholder_len(r###"r#"a"}"#.len()}"###), Some(14));
+        assert_eq!(placeholder_len("f::<'a>(x)}"), Some(10));
+        assert_eq!(placeholder_len("bar(\"}\")}"), Some(8));
+    }
+
+    #[test]
+    fn test_template() {
+        let lit: LitStr = syn::parse_str(r#""a: #{x}\nb: ##{y}\nc: #{v\n.len()}\nd""#).unwrap();
+        let template = Template::parse(&lit, &lit.value()).unwrap();
+        assert_eq!(
+            template.text,
+            "a: __toon_interp_0_\nb: #{y}\nc: __toon_interp_1_\nd"
+        );
+        assert_eq!(template.exprs.len(), 2);
+        assert_eq!(template.original_line(3), 3);
+        assert_eq!(template.original_line(4), 5);
+
+        let lit: LitStr = syn::parse_str(r#""a: #{x""#).unwrap();
+        assert!(Template::parse(&lit, &lit.value()).is_err());
+    }
+
+    #[test]
+    fn test_value_tokens() {
+        let krate: TokenTree = syn::parse_str::<Ident>("krate").unwrap().into();
+        let tokens = value_tokens(&krate, &Value::Number(Number::I64(-3)));
+        assert_eq!(
+            tokens.to_string(),
+            "krate :: Value :: Number (krate :: Number :: I64 (- 3i64))"
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! Compares the native `to_value`/`from_value` with a round trip through
+//! TOON text, which is how they used to be implemented, and the owned and
+//! borrowed parsers.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use serde::{Deserialize, Serialize};
+use toon_macro::value::{from_value, to_value};
+use toon_macro::{Value, from_toon_str, from_toon_str_borrowed, to_toon_string};
+
+// Kept to shapes the text round trip can parse back: it fails on some
+// nested layouts (e.g. a table inside a list item) and on `Option` fields,
+// both of which the native conversions handle.
+#[derive(Serialize, Deserialize, Clone)]
+struct Order {
+    id: u64,
+    customer: String,
+    total: f64,
+    paid: bool,
+    skus: Vec<String>,
+    quantities: Vec<u32>,
+    note: String,
+}
+
+fn orders(n: u64) -> Vec<Order> {
+    (0..n)
+        .map(|id| Order {
+            id,
+            customer: format!("customer-{}", id % 97),
+            total: id as f64 * 1.25,
+            paid: id % 3 == 0,
+            skus: (0..5).map(|i| format!("SKU-{:05}", id * 10 + i)).collect(),
+            quantities: (1..=5).collect(),
+            note: if id % 4 == 0 {
+                "leave at the door".to_string()
+            } else {
+                String::new()
+            },
+        })
+        .collect()
+}
+
+fn text_to_value<T: Serialize>(value: &T) -> Value {
+    serde_toon2::from_str(&serde_toon2::to_string(value).unwrap()).unwrap()
+}
+
+fn text_from_value<T: serde::de::DeserializeOwned>(value: &Value) -> T {
+    serde_toon2::from_str(&serde_toon2::to_string(value).unwrap()).unwrap()
+}
+
+fn bench_to_value(c: &mut Criterion) {
+    let data = orders(1_000);
+    let mut group = c.benchmark_group("to_value");
+    group.bench_function("native", |b| b.iter(|| to_value(black_box(&data)).unwrap()));
+    group.bench_function("text_round_trip", |b| {
+        b.iter(|| text_to_value(black_box(&data)))
+    });
+    group.finish();
+}
+
+fn bench_from_value(c: &mut Criterion) {
+    let value = to_value(&orders(1_000)).unwrap();
+    let mut group = c.benchmark_group("from_value");
+    group.bench_function("native", |b| {
+        b.iter(|| from_value::<Vec<Order>>(black_box(&value)).unwrap())
+    });
+    group.bench_function("text_round_trip", |b| {
+        b.iter(|| text_from_value::<Vec<Order>>(black_box(&value)))
+    });
+    group.finish();
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let text = to_toon_string(&to_value(&orders(1_000)).unwrap()).unwrap();
+    let mut group = c.benchmark_group("parse");
+    group.bench_function("owned", |b| {
+        b.iter(|| from_toon_str(black_box(&text)).unwrap())
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| from_toon_str_borrowed(black_box(&text)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_to_value, bench_from_value, bench_parse);
+criterion_main!(benches);
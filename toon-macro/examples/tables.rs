@@ -123,19 +123,19 @@ fn main() {
     println!("   Columns: {:?}", Product::COLUMNS);
     let table = Product::to_toon_table(&products);
 
-    if let Value::Object(map) = &table {
-        if let Some(Value::Array(cols)) = map.get("columns") {
-            print!("   Column names in table: ");
-            for (i, col) in cols.iter().enumerate() {
-                if i > 0 {
-                    print!(", ");
-                }
-                if let Value::String(s) = col {
-                    print!("{}", s);
-                }
+    if let Value::Object(map) = &table
+        && let Some(Value::Array(cols)) = map.get("columns")
+    {
+        print!("   Column names in table: ");
+        for (i, col) in cols.iter().enumerate() {
+            if i > 0 {
+                print!(", ");
+            }
+            if let Value::String(s) = col {
+                print!("{}", s);
             }
-            println!();
         }
+        println!();
     }
     println!();
 
@@ -0,0 +1,135 @@
+//! Deterministic pseudo-random values for property tests.
+//!
+//! The generator favours the shapes the encoder lays out differently:
+//! strings that need quoting, uniform object arrays (tables), arrays of
+//! arrays, list items and awkward keys.
+
+use crate::{Map, Number, Value};
+
+/// Strings covering the quoting rules, plus some plain ones.
+const STRINGS: &[&str] = &[
+    "",
+    "a",
+    "hello world",
+    "true",
+    "false",
+    "null",
+    "42",
+    "-1.5",
+    "1e3",
+    "007",
+    "-",
+    "-x",
+    " pad",
+    "pad ",
+    "a,b",
+    "a:b",
+    "a|b",
+    "a\tb",
+    "x\ny",
+    "q\"q",
+    "back\\slash",
+    "[1]",
+    "{a}",
+    "#hash",
+    "ünïcödé",
+    "日本",
+];
+
+/// Keys covering identifier and quoted forms.
+const KEYS: &[&str] = &[
+    "a", "b", "id", "name", "x_y", "a.b", "", "k y", "1a", "-k", "a:b", "\"q\"", "ü",
+];
+
+/// A xorshift generator; enough for varied but reproducible test data.
+pub(crate) struct Gen(u64);
+
+impl Gen {
+    pub(crate) fn new(seed: u64) -> Self {
+        Gen(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn number(&mut self) -> Number {
+        match self.below(8) {
+            0 => Number::I64(self.below(10) as i64),
+            1 => Number::I64(-(self.next() as i64).abs() / 3),
+            2 => Number::U64(self.next()),
+            3 => Number::F64(self.below(1000) as f64 / 8.0),
+            4 => Number::F64(f64::from_bits(self.next() >> 2)),
+            5 => Number::F64([-0.0, 1e-20, 1e21, 0.1, f64::MAX][self.below(5)]),
+            6 => Number::F64([f64::NAN, f64::INFINITY, f64::NEG_INFINITY][self.below(3)]),
+            _ => Number::I64(self.next() as i64),
+        }
+    }
+
+    fn primitive(&mut self) -> Value {
+        match self.below(6) {
+            0 => Value::Null,
+            1 => Value::Bool(self.below(2) == 0),
+            2 | 3 => Value::Number(self.number()),
+            _ => Value::String(STRINGS[self.below(STRINGS.len())].to_string()),
+        }
+    }
+
+    fn key(&mut self) -> String {
+        KEYS[self.below(KEYS.len())].to_string()
+    }
+
+    fn object(&mut self, depth: usize) -> Map<String, Value> {
+        (0..self.below(4))
+            .map(|_| (self.key(), self.value(depth + 1)))
+            .collect()
+    }
+
+    /// A value nested at most a few levels below `depth`.
+    pub(crate) fn value(&mut self, depth: usize) -> Value {
+        if depth >= 4 {
+            return self.primitive();
+        }
+        match self.below(10) {
+            0..=3 => self.primitive(),
+            4 => Value::Array((0..self.below(4)).map(|_| self.primitive()).collect()),
+            5 => {
+                // Rows of a table: the same keys with primitive values.
+                let keys: Vec<String> = (0..1 + self.below(3)).map(|_| self.key()).collect();
+                Value::Array(
+                    (0..1 + self.below(3))
+                        .map(|_| {
+                            Value::Object(
+                                keys.iter().map(|k| (k.clone(), self.primitive())).collect(),
+                            )
+                        })
+                        .collect(),
+                )
+            }
+            6 => Value::Array(
+                (0..1 + self.below(3))
+                    .map(|_| self.value(depth + 2))
+                    .map(|v| match v {
+                        Value::Array(_) => v,
+                        other => Value::Array(vec![other]),
+                    })
+                    .collect(),
+            ),
+            7 => Value::Array((0..self.below(4)).map(|_| self.value(depth + 1)).collect()),
+            _ => Value::Object(self.object(depth)),
+        }
+    }
+}
+
+/// `count` generated values, the same on every run.
+pub(crate) fn values(count: usize) -> impl Iterator<Item = Value> {
+    let mut g = Gen::new(0x5eed);
+    (0..count).map(move |_| g.value(0))
+}
@@ -0,0 +1,618 @@
+//! Conversions between Rust types and TOON values.
+//!
+//! This module provides the [`ToToon`] and [`FromToon`] traits, which are the
+//! single conversion mechanism used throughout the crate:
+//!
+//! - value expressions and spreads inside [`toon!`](crate::toon)
+//! - cells of `#[derive(ToonTable)]` structs
+//! - the older [`IntoValue`](crate::value::IntoValue),
+//!   [`IntoToonValue`](crate::table::IntoToonValue) and
+//!   [`FromToonValue`](crate::table::FromToonValue) traits, which are now
+//!   deprecated and implemented for every `ToToon`/`FromToon` type
+//!
+//! Implement these traits once for your own types and they can be used
+//! everywhere.
+//!
+//! # Example
+//!
+//! ```
+//! use toon_macro::{toon, FromToon, Result, ToToon, Value};
+//!
+//! #[derive(Debug, PartialEq)]
+//! struct Celsius(f64);
+//!
+//! impl ToToon for Celsius {
+//!     fn to_toon(&self) -> Value {
+//!         self.0.to_toon()
+//!     }
+//! }
+//!
+//! impl FromToon for Celsius {
+//!     fn from_toon(value: &Value) -> Result<Self> {
+//!         f64::from_toon(value).map(Celsius)
+//!     }
+//! }
+//!
+//! let reading = toon!({ temp: Celsius(21.5) });
+//! let Value::Object(map) = &reading else { unreachable!() };
+//! assert_eq!(Celsius::from_toon(&map["temp"]).unwrap(), Celsius(21.5));
+//! ```
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::{Error, Map, Number, Result, Value};
+
+/// A type that can be converted into a TOON [`Value`].
+///
+/// Only [`to_toon`](ToToon::to_toon) needs to be implemented.
+/// [`into_toon`](ToToon::into_toon) can be overridden to avoid cloning when
+/// the value is consumed, as `toon!` does with owned expressions.
+///
+/// Integers are stored as `Number::I64` (signed) or `Number::U64`
+/// (unsigned), floats as `Number::F64`. 128-bit integers that do not fit in
+/// 64 bits fall back to a (lossy) float, since `Number` has no wider variant.
+pub trait ToToon {
+    /// Convert a reference to this value into a TOON [`Value`].
+    fn to_toon(&self) -> Value;
+
+    /// Convert this value into a TOON [`Value`], consuming it.
+    #[inline]
+    fn into_toon(self) -> Value
+    where
+        Self: Sized,
+    {
+        self.to_toon()
+    }
+}
+
+/// A type that can be extracted from a TOON [`Value`].
+pub trait FromToon: Sized {
+    /// Convert a TOON value to this type.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value has the wrong type or is out of range
+    /// for this type.
+    fn from_toon(value: &Value) -> Result<Self>;
+}
+
+impl ToToon for Value {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        self.clone()
+    }
+
+    #[inline]
+    fn into_toon(self) -> Value {
+        self
+    }
+}
+
+impl ToToon for Map<String, Value> {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        Value::Object(self.clone())
+    }
+
+    #[inline]
+    fn into_toon(self) -> Value {
+        Value::Object(self)
+    }
+}
+
+impl ToToon for bool {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        Value::Bool(*self)
+    }
+}
+
+impl ToToon for char {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl ToToon for str {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl ToToon for String {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        Value::String(self.clone())
+    }
+
+    #[inline]
+    fn into_toon(self) -> Value {
+        Value::String(self)
+    }
+}
+
+macro_rules! impl_to_toon_for_int {
+    ($variant:ident as $wide:ty: $($ty:ty),*) => {
+        $(
+            impl ToToon for $ty {
+                #[inline]
+                fn to_toon(&self) -> Value {
+                    Value::Number(Number::$variant(*self as $wide))
+                }
+            }
+        )*
+    };
+}
+
+impl_to_toon_for_int!(I64 as i64: i8, i16, i32, i64, isize);
+impl_to_toon_for_int!(U64 as u64: u8, u16, u32, u64, usize);
+impl_to_toon_for_int!(F64 as f64: f32, f64);
+
+impl ToToon for i128 {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        if let Ok(n) = i64::try_from(*self) {
+            Value::Number(Number::I64(n))
+        } else if let Ok(n) = u64::try_from(*self) {
+            Value::Number(Number::U64(n))
+        } else {
+            Value::Number(Number::F64(*self as f64))
+        }
+    }
+}
+
+impl ToToon for u128 {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        match u64::try_from(*self) {
+            Ok(n) => Value::Number(Number::U64(n)),
+            Err(_) => Value::Number(Number::F64(*self as f64)),
+        }
+    }
+}
+
+// `None` becomes null
+impl<T: ToToon> ToToon for Option<T> {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        match self {
+            Some(v) => v.to_toon(),
+            None => Value::Null,
+        }
+    }
+
+    #[inline]
+    fn into_toon(self) -> Value {
+        match self {
+            Some(v) => v.into_toon(),
+            None => Value::Null,
+        }
+    }
+}
+
+// Sequences become arrays
+impl<T: ToToon> ToToon for [T] {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        Value::Array(self.iter().map(T::to_toon).collect())
+    }
+}
+
+impl<T: ToToon, const N: usize> ToToon for [T; N] {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        self.as_slice().to_toon()
+    }
+
+    #[inline]
+    fn into_toon(self) -> Value {
+        Value::Array(self.into_iter().map(T::into_toon).collect())
+    }
+}
+
+impl<T: ToToon> ToToon for Vec<T> {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        self.as_slice().to_toon()
+    }
+
+    #[inline]
+    fn into_toon(self) -> Value {
+        Value::Array(self.into_iter().map(T::into_toon).collect())
+    }
+}
+
+// Maps with string keys become objects. `HashMap` entries keep the map's
+// (unspecified) iteration order; use a `BTreeMap` for sorted keys.
+impl<K, V, S> ToToon for HashMap<K, V, S>
+where
+    K: AsRef<str> + Into<String>,
+    V: ToToon,
+    S: BuildHasher,
+{
+    #[inline]
+    fn to_toon(&self) -> Value {
+        Value::Object(
+            self.iter()
+                .map(|(k, v)| (k.as_ref().to_owned(), v.to_toon()))
+                .collect(),
+        )
+    }
+
+    #[inline]
+    fn into_toon(self) -> Value {
+        Value::Object(
+            self.into_iter()
+                .map(|(k, v)| (k.into(), v.into_toon()))
+                .collect(),
+        )
+    }
+}
+
+impl<K, V> ToToon for BTreeMap<K, V>
+where
+    K: AsRef<str> + Into<String>,
+    V: ToToon,
+{
+    #[inline]
+    fn to_toon(&self) -> Value {
+        Value::Object(
+            self.iter()
+                .map(|(k, v)| (k.as_ref().to_owned(), v.to_toon()))
+                .collect(),
+        )
+    }
+
+    #[inline]
+    fn into_toon(self) -> Value {
+        Value::Object(
+            self.into_iter()
+                .map(|(k, v)| (k.into(), v.into_toon()))
+                .collect(),
+        )
+    }
+}
+
+// Tuples become fixed-length arrays
+macro_rules! impl_to_toon_for_tuple {
+    ($(($($name:ident),+)),* $(,)?) => {
+        $(
+            impl<$($name: ToToon),+> ToToon for ($($name,)+) {
+                #[inline]
+                #[allow(non_snake_case)]
+                fn to_toon(&self) -> Value {
+                    let ($($name,)+) = self;
+                    Value::Array(vec![$($name.to_toon()),+])
+                }
+
+                #[inline]
+                #[allow(non_snake_case)]
+                fn into_toon(self) -> Value {
+                    let ($($name,)+) = self;
+                    Value::Array(vec![$($name.into_toon()),+])
+                }
+            }
+        )*
+    };
+}
+
+impl_to_toon_for_tuple!(
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F),
+    (A, B, C, D, E, F, G),
+    (A, B, C, D, E, F, G, H),
+);
+
+// References and smart pointers convert their contents
+impl<T: ToToon + ?Sized> ToToon for &T {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        (**self).to_toon()
+    }
+}
+
+impl<T: ToToon + ?Sized> ToToon for &mut T {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        (**self).to_toon()
+    }
+}
+
+impl<T: ToToon + ?Sized> ToToon for Box<T> {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        (**self).to_toon()
+    }
+}
+
+impl<T: ToToon + ?Sized> ToToon for Rc<T> {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        (**self).to_toon()
+    }
+}
+
+impl<T: ToToon + ?Sized> ToToon for Arc<T> {
+    #[inline]
+    fn to_toon(&self) -> Value {
+        (**self).to_toon()
+    }
+}
+
+impl<T> ToToon for Cow<'_, T>
+where
+    T: ToToon + ToOwned + ?Sized,
+    T::Owned: ToToon,
+{
+    #[inline]
+    fn to_toon(&self) -> Value {
+        (**self).to_toon()
+    }
+
+    #[inline]
+    fn into_toon(self) -> Value {
+        match self {
+            Cow::Borrowed(v) => v.to_toon(),
+            Cow::Owned(v) => v.into_toon(),
+        }
+    }
+}
+
+impl FromToon for Value {
+    #[inline]
+    fn from_toon(value: &Value) -> Result<Self> {
+        Ok(value.clone())
+    }
+}
+
+impl FromToon for String {
+    fn from_toon(value: &Value) -> Result<Self> {
+        match value {
+            Value::String(s) => Ok(s.clone()),
+            Value::Null => Ok(String::new()),
+            _ => Err(Error::invalid_value("string", value)),
+        }
+    }
+}
+
+impl FromToon for char {
+    fn from_toon(value: &Value) -> Result<Self> {
+        match value {
+            Value::String(s) => {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(c),
+                    _ => Err(Error::ConversionError(
+                        "string is not a single character".into(),
+                    )),
+                }
+            }
+            _ => Err(Error::invalid_value("char", value)),
+        }
+    }
+}
+
+impl FromToon for bool {
+    fn from_toon(value: &Value) -> Result<Self> {
+        match value {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(Error::invalid_value("bool", value)),
+        }
+    }
+}
+
+impl FromToon for i64 {
+    fn from_toon(value: &Value) -> Result<Self> {
+        match value {
+            Value::Number(n) => n
+                .as_i64()
+                .ok_or_else(|| Error::ConversionError("number is not an i64".into())),
+            _ => Err(Error::invalid_value("i64", value)),
+        }
+    }
+}
+
+impl FromToon for u64 {
+    fn from_toon(value: &Value) -> Result<Self> {
+        match value {
+            Value::Number(n) => n
+                .as_u64()
+                .ok_or_else(|| Error::ConversionError("number is not a u64".into())),
+            _ => Err(Error::invalid_value("u64", value)),
+        }
+    }
+}
+
+// Reads back every integer `ToToon for i128` writes, including the `U64`
+// values between `i64::MAX` and `u64::MAX`.
+impl FromToon for i128 {
+    fn from_toon(value: &Value) -> Result<Self> {
+        match value {
+            Value::Number(Number::U64(n)) => Ok(i128::from(*n)),
+            Value::Number(n) => n
+                .as_i64()
+                .map(i128::from)
+                .ok_or_else(|| Error::ConversionError("number is not an i128".into())),
+            _ => Err(Error::invalid_value("i128", value)),
+        }
+    }
+}
+
+macro_rules! impl_from_toon_for_int {
+    ($via:ty: $($ty:ident),*) => {
+        $(
+            impl FromToon for $ty {
+                fn from_toon(value: &Value) -> Result<Self> {
+                    match value {
+                        Value::Number(_) => <$ty>::try_from(<$via>::from_toon(value)?).map_err(
+                            |_| Error::ConversionError(
+                                concat!("number is out of range for ", stringify!($ty)).into(),
+                            ),
+                        ),
+                        _ => Err(Error::invalid_value(stringify!($ty), value)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_toon_for_int!(i64: i8, i16, i32, isize);
+impl_from_toon_for_int!(u64: u8, u16, u32, usize, u128);
+
+impl FromToon for f64 {
+    fn from_toon(value: &Value) -> Result<Self> {
+        match value {
+            Value::Number(n) => Ok(n.as_f64()),
+            _ => Err(Error::invalid_value("f64", value)),
+        }
+    }
+}
+
+impl FromToon for f32 {
+    fn from_toon(value: &Value) -> Result<Self> {
+        f64::from_toon(value).map(|n| n as f32)
+    }
+}
+
+impl<T: FromToon> FromToon for Option<T> {
+    fn from_toon(value: &Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            _ => T::from_toon(value).map(Some),
+        }
+    }
+}
+
+impl<T: FromToon> FromToon for Vec<T> {
+    fn from_toon(value: &Value) -> Result<Self> {
+        match value {
+            Value::Array(items) => items.iter().map(T::from_toon).collect(),
+            _ => Err(Error::invalid_value("array", value)),
+        }
+    }
+}
+
+impl<T: FromToon, S: BuildHasher + Default> FromToon for HashMap<String, T, S> {
+    fn from_toon(value: &Value) -> Result<Self> {
+        match value {
+            Value::Object(map) => map
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), T::from_toon(v)?)))
+                .collect(),
+            _ => Err(Error::invalid_value("object", value)),
+        }
+    }
+}
+
+impl<T: FromToon> FromToon for BTreeMap<String, T> {
+    fn from_toon(value: &Value) -> Result<Self> {
+        match value {
+            Value::Object(map) => map
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), T::from_toon(v)?)))
+                .collect(),
+            _ => Err(Error::invalid_value("object", value)),
+        }
+    }
+}
+
+impl<T: FromToon> FromToon for Box<T> {
+    fn from_toon(value: &Value) -> Result<Self> {
+        T::from_toon(value).map(Box::new)
+    }
+}
+
+impl<T: FromToon> FromToon for Rc<T> {
+    fn from_toon(value: &Value) -> Result<Self> {
+        T::from_toon(value).map(Rc::new)
+    }
+}
+
+impl<T: FromToon> FromToon for Arc<T> {
+    fn from_toon(value: &Value) -> Result<Self> {
+        T::from_toon(value).map(Arc::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_toon_scalars() {
+        assert_eq!(42i32.to_toon(), Value::Number(Number::I64(42)));
+        assert_eq!(42u8.to_toon(), Value::Number(Number::U64(42)));
+        assert_eq!((-1i128).to_toon(), Value::Number(Number::I64(-1)));
+        assert_eq!('x'.to_toon(), Value::String("x".into()));
+        assert_eq!("hi".to_toon(), Value::String("hi".into()));
+        assert_eq!(Option::<bool>::None.to_toon(), Value::Null);
+    }
+
+    #[test]
+    fn test_to_toon_collections() {
+        let v = vec![(1u32, "a"), (2, "b")];
+        assert_eq!(v.to_toon(), v.clone().into_toon());
+        assert_eq!(v.to_toon(), toon!([[1u32, "a"], [2u32, "b"]]));
+
+        let mut m = BTreeMap::new();
+        m.insert("k", Rc::new(1.5));
+        assert_eq!(m.to_toon(), toon!({ k: 1.5 }));
+    }
+
+    #[test]
+    fn test_from_toon_roundtrip() {
+        let v = toon!({ a: [1u8, 2u8], b: null });
+        let map = BTreeMap::<String, Option<Vec<u8>>>::from_toon(&v).unwrap();
+        assert_eq!(map["a"], Some(vec![1, 2]));
+        assert_eq!(map["b"], None);
+        assert_eq!(map.to_toon(), v);
+    }
+
+    #[test]
+    fn test_from_toon_errors() {
+        assert!(matches!(
+            u8::from_toon(&toon!(300)),
+            Err(Error::ConversionError(_))
+        ));
+        assert!(matches!(
+            i32::from_toon(&toon!("1")),
+            Err(Error::InvalidType {
+                expected: "i32",
+                ..
+            })
+        ));
+        assert!(char::from_toon(&toon!("ab")).is_err());
+    }
+
+    #[test]
+    fn test_wide_integers_roundtrip() {
+        for n in [
+            i64::MIN as i128,
+            -1,
+            i64::MAX as i128,
+            i64::MAX as i128 + 1,
+            u64::MAX as i128,
+        ] {
+            assert_eq!(i128::from_toon(&n.to_toon()).unwrap(), n);
+        }
+        assert_eq!(
+            u128::from_toon(&(u64::MAX as u128).to_toon()).unwrap(),
+            u64::MAX as u128
+        );
+        assert!(matches!(
+            i128::from_toon(&toon!(1.5)),
+            Err(Error::ConversionError(_))
+        ));
+    }
+}
@@ -0,0 +1,449 @@
+//! Borrowing TOON decoder.
+//!
+//! Parses TOON (two space indentation, any of the `,`, `|` and tab
+//! delimiters) into a [`ValueRef`], slicing keys and strings out of the
+//! input wherever they contain no escape sequences. Where it reads input
+//! differently from [`from_toon_str`](crate::from_toon_str) is listed on
+//! [`from_toon_str_borrowed`](crate::from_toon_str_borrowed).
+//!
+//! Nested layouts follow the TOON specification: array bodies and the
+//! fields of nested objects sit one level below their header, except that
+//! the fields of an object in a list sit one level below its `- ` line and
+//! the fields of an object nested in the first of those two levels below.
+
+use std::borrow::Cow;
+
+use crate::value::ValueRef;
+use crate::{Error, Map, Number, Result};
+
+const INDENT: usize = 2;
+
+/// Parse a complete document.
+pub(crate) fn parse(input: &str) -> Result<ValueRef<'_>> {
+    let lines = input
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let content = line.trim_start_matches(' ');
+            (!content.is_empty()).then(|| Line {
+                number: index + 1,
+                depth: (line.len() - content.len()) / INDENT,
+                content,
+            })
+        })
+        .collect();
+    Decoder { lines, next: 0 }.document()
+}
+
+#[derive(Clone, Copy)]
+struct Line<'a> {
+    number: usize,
+    depth: usize,
+    /// The line without its indentation; never empty.
+    content: &'a str,
+}
+
+/// The `[N]` or `[N|]{a|b}` part of an array header.
+struct Header<'a> {
+    len: usize,
+    delimiter: u8,
+    fields: Option<Vec<Cow<'a, str>>>,
+}
+
+struct Decoder<'a> {
+    lines: Vec<Line<'a>>,
+    next: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn document(mut self) -> Result<ValueRef<'a>> {
+        let Some(&first) = self.lines.first() else {
+            return Ok(ValueRef::Object(Map::new()));
+        };
+        if first.depth != 0 {
+            return Err(error("First line must be at depth 0", first.number));
+        }
+
+        let value = if is_header(first.content) {
+            self.next += 1;
+            let (header, inline) = header(first.content, first.number)?;
+            self.array(header, inline, 1, first.number)?
+        } else if self.lines.len() == 1 && find_unquoted(first.content, b":").is_none() {
+            self.next += 1;
+            primitive(first.content, first.number)?
+        } else {
+            let mut map = Map::new();
+            self.entries(0, &mut map)?;
+            ValueRef::Object(map)
+        };
+
+        match self.lines.get(self.next) {
+            Some(line) => Err(error("Unexpected indentation", line.number)),
+            None => Ok(value),
+        }
+    }
+
+    /// The next line, if it is at `depth`.
+    fn next_at(&mut self, depth: usize) -> Option<Line<'a>> {
+        let line = *self.lines.get(self.next)?;
+        (line.depth == depth).then(|| {
+            self.next += 1;
+            line
+        })
+    }
+
+    /// Object entries at `depth`, added to `map`.
+    fn entries(&mut self, depth: usize, map: &mut Map<Cow<'a, str>, ValueRef<'a>>) -> Result<()> {
+        while let Some(line) = self.lines.get(self.next) {
+            if line.depth > depth {
+                return Err(error("Unexpected indentation", line.number));
+            }
+            let Some(line) = self.next_at(depth) else {
+                break;
+            };
+            self.entry(line.content, line.number, depth + 1, depth + 1, map)?;
+        }
+        Ok(())
+    }
+
+    /// One `key: value`, `key:` or `key[N]...:` entry. The fields of a
+    /// nested object are expected at `object_depth` and array items at
+    /// `items_depth`.
+    fn entry(
+        &mut self,
+        content: &'a str,
+        number: usize,
+        object_depth: usize,
+        items_depth: usize,
+        map: &mut Map<Cow<'a, str>, ValueRef<'a>>,
+    ) -> Result<()> {
+        let (key, rest) = key(content, number)?;
+        let value = if rest.starts_with('[') {
+            let (header, inline) = header(rest, number)?;
+            self.array(header, inline, items_depth, number)?
+        } else {
+            let value = rest[1..].trim();
+            if !value.is_empty() {
+                primitive(value, number)?
+            } else {
+                let mut nested = Map::new();
+                if self
+                    .lines
+                    .get(self.next)
+                    .is_some_and(|line| line.depth == object_depth)
+                {
+                    self.entries(object_depth, &mut nested)?;
+                }
+                ValueRef::Object(nested)
+            }
+        };
+        map.insert(key, value);
+        Ok(())
+    }
+
+    /// The array introduced by `header`, with `inline` the text after its
+    /// colon and any items at `depth`.
+    fn array(
+        &mut self,
+        header: Header<'a>,
+        inline: &'a str,
+        depth: usize,
+        number: usize,
+    ) -> Result<ValueRef<'a>> {
+        let inline = inline.trim();
+        let items = if !inline.is_empty() {
+            let items = split(inline, header.delimiter)
+                .into_iter()
+                .map(|item| primitive(item, number))
+                .collect::<Result<Vec<_>>>()?;
+            if items.len() != header.len {
+                let message = format!("Expected {} values, got {}", header.len, items.len());
+                return Err(error(&message, number));
+            }
+            items
+        } else if let Some(fields) = header.fields {
+            self.rows(header.len, header.delimiter, &fields, depth, number)?
+        } else {
+            self.list(header.len, depth, number)?
+        };
+        Ok(ValueRef::Array(items))
+    }
+
+    fn rows(
+        &mut self,
+        len: usize,
+        delimiter: u8,
+        fields: &[Cow<'a, str>],
+        depth: usize,
+        number: usize,
+    ) -> Result<Vec<ValueRef<'a>>> {
+        let mut rows = Vec::new();
+        while rows.len() < len {
+            let Some(line) = self
+                .lines
+                .get(self.next)
+                .filter(|line| line.depth == depth && is_row(line.content, delimiter))
+                .copied()
+            else {
+                let message = format!("Expected {} rows, got {}", len, rows.len());
+                return Err(error(&message, number));
+            };
+            self.next += 1;
+
+            let values = split(line.content, delimiter);
+            if values.len() != fields.len() {
+                let message = format!("Expected {} values, got {}", fields.len(), values.len());
+                return Err(error(&message, line.number));
+            }
+            let mut row = Map::with_capacity(fields.len());
+            for (field, value) in fields.iter().zip(values) {
+                row.insert(field.clone(), primitive(value, line.number)?);
+            }
+            rows.push(ValueRef::Object(row));
+        }
+        Ok(rows)
+    }
+
+    fn list(&mut self, len: usize, depth: usize, number: usize) -> Result<Vec<ValueRef<'a>>> {
+        let mut items = Vec::new();
+        while items.len() < len {
+            let item = self
+                .lines
+                .get(self.next)
+                .filter(|line| line.depth == depth)
+                .and_then(|line| match line.content {
+                    "-" => Some(""),
+                    content => content.strip_prefix("- "),
+                });
+            let Some(item) = item else {
+                let message = format!("Expected {} items, got {}", len, items.len());
+                return Err(error(&message, number));
+            };
+            let number = self.lines[self.next].number;
+            self.next += 1;
+            items.push(self.item(item.trim_start(), number, depth)?);
+        }
+        Ok(items)
+    }
+
+    /// The content of a `- ` line at `depth`.
+    fn item(&mut self, content: &'a str, number: usize, depth: usize) -> Result<ValueRef<'a>> {
+        if content.is_empty() {
+            Ok(ValueRef::Object(Map::new()))
+        } else if is_header(content) {
+            let (header, inline) = header(content, number)?;
+            self.array(header, inline, depth + 1, number)
+        } else if find_unquoted(content, b":").is_some() {
+            // The first field shares the `- ` line; the others follow one
+            // level down.
+            let mut map = Map::new();
+            self.entry(content, number, depth + 2, depth + 1, &mut map)?;
+            self.entries(depth + 1, &mut map)?;
+            Ok(ValueRef::Object(map))
+        } else {
+            primitive(content, number)
+        }
+    }
+}
+
+fn error(message: &str, number: usize) -> Error {
+    Error::Deserialize(format!("{} at line {}", message, number))
+}
+
+/// Split the key off an entry, returning the rest from its `[` or `:`.
+fn key(content: &str, number: usize) -> Result<(Cow<'_, str>, &str)> {
+    if content.starts_with('"') {
+        let end = closing_quote(content).ok_or_else(|| error("Unterminated quoted key", number))?;
+        let key = unescape(&content[1..end], number)?;
+        let rest = content[end + 1..].trim_start();
+        if rest.starts_with([':', '[']) {
+            Ok((key, rest))
+        } else {
+            Err(error("Missing colon after key", number))
+        }
+    } else {
+        let end = find_unquoted(content, b":[")
+            .ok_or_else(|| error("Missing colon after key", number))?;
+        Ok((Cow::Borrowed(content[..end].trim()), &content[end..]))
+    }
+}
+
+/// Parse an array header starting at its `[`, returning the text after its
+/// colon.
+fn header(content: &str, number: usize) -> Result<(Header<'_>, &str)> {
+    let invalid = || error("Invalid array header", number);
+
+    let close = content.find(']').ok_or_else(invalid)?;
+    let inner = &content[1..close];
+    let (len, delimiter) = match inner.as_bytes().last() {
+        Some(&delimiter @ (b'|' | b'\t')) => (&inner[..inner.len() - 1], delimiter),
+        _ => (inner, b','),
+    };
+    if len.is_empty() || !len.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(error(&format!("Invalid array length: {}", len), number));
+    }
+    let len = len.parse().map_err(|_| invalid())?;
+
+    let mut rest = content[close + 1..].trim_start();
+    let mut fields = None;
+    if let Some(body) = rest.strip_prefix('{') {
+        let close = find_unquoted(body, b"}").ok_or_else(invalid)?;
+        fields = Some(
+            split(&body[..close], delimiter)
+                .into_iter()
+                .map(|field| match field.strip_prefix('"') {
+                    Some(quoted) => match quoted.strip_suffix('"') {
+                        Some(inner) => unescape(inner, number),
+                        None => Err(invalid()),
+                    },
+                    None => Ok(Cow::Borrowed(field)),
+                })
+                .collect::<Result<_>>()?,
+        );
+        rest = body[close + 1..].trim_start();
+    }
+
+    let inline = rest.strip_prefix(':').ok_or_else(invalid)?;
+    Ok((
+        Header {
+            len,
+            delimiter,
+            fields,
+        },
+        inline,
+    ))
+}
+
+/// Whether `content` starts with a keyless array header, as opposed to a
+/// string such as `[draft]`.
+fn is_header(content: &str) -> bool {
+    content.starts_with('[') && find_unquoted(content, b":").is_some()
+}
+
+/// Whether `content` is a table row rather than a `key: value` line: it
+/// has no colon outside quotes, or a delimiter before the first one.
+fn is_row(content: &str, delimiter: u8) -> bool {
+    match find_unquoted(content, b":") {
+        None => true,
+        Some(colon) => find_unquoted(content, &[delimiter]).is_some_and(|d| d < colon),
+    }
+}
+
+/// The byte offset of the first of `targets` outside a quoted string.
+fn find_unquoted(content: &str, targets: &[u8]) -> Option<usize> {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, &b) in content.as_bytes().iter().enumerate() {
+        if escaped {
+            escaped = false;
+        } else if in_quotes && b == b'\\' {
+            escaped = true;
+        } else if b == b'"' {
+            in_quotes = !in_quotes;
+        } else if !in_quotes && targets.contains(&b) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// The byte offset of the quote closing the string that `content` starts
+/// with.
+fn closing_quote(content: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, &b) in content.as_bytes().iter().enumerate().skip(1) {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split delimited values outside quoted strings, trimming each one.
+fn split(content: &str, delimiter: u8) -> Vec<&str> {
+    let mut values = Vec::new();
+    let mut rest = content;
+    while let Some(i) = find_unquoted(rest, &[delimiter]) {
+        values.push(rest[..i].trim());
+        rest = &rest[i + 1..];
+    }
+    values.push(rest.trim());
+    values
+}
+
+fn primitive(content: &str, number: usize) -> Result<ValueRef<'_>> {
+    let content = content.trim();
+    if let Some(quoted) = content.strip_prefix('"') {
+        return match quoted.strip_suffix('"') {
+            Some(inner) => Ok(ValueRef::String(unescape(inner, number)?)),
+            None => Err(error(
+                "String starting with quote must end with quote",
+                number,
+            )),
+        };
+    }
+    Ok(match content {
+        "true" => ValueRef::Bool(true),
+        "false" => ValueRef::Bool(false),
+        "null" => ValueRef::Null,
+        _ => match parse_number(content) {
+            Some(n) => ValueRef::Number(n),
+            None => ValueRef::String(Cow::Borrowed(content)),
+        },
+    })
+}
+
+/// Numbers as [`from_toon_str`](crate::from_toon_str) reads them: no
+/// leading zeros, and integers where the value is integral, even when
+/// written with a fraction or exponent.
+fn parse_number(s: &str) -> Option<Number> {
+    if s.len() > 1 && s.starts_with('0') && s.as_bytes()[1].is_ascii_digit() {
+        return None;
+    }
+    let n = if s.contains(['.', 'e', 'E']) {
+        Number::F64(s.parse().ok()?)
+    } else if s.starts_with('-') {
+        Number::I64(s.parse().ok()?)
+    } else {
+        s.parse()
+            .map(Number::U64)
+            .or_else(|_| s.parse().map(Number::I64))
+            .ok()?
+    };
+    Some(match (n.as_i64(), n.as_u64()) {
+        (Some(i), _) => Number::I64(i),
+        (None, Some(u)) => Number::U64(u),
+        (None, None) => n,
+    })
+}
+
+/// The text of a quoted string, borrowed unless it has escapes.
+fn unescape(s: &str, number: usize) -> Result<Cow<'_, str>> {
+    if !s.contains('\\') {
+        return Ok(Cow::Borrowed(s));
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next() {
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some(other) => {
+                let message = format!("Invalid escape sequence: \\{}", other);
+                return Err(error(&message, number));
+            }
+            None => return Err(error("Backslash at end of string", number)),
+        });
+    }
+    Ok(Cow::Owned(out))
+}
@@ -0,0 +1,594 @@
+//! Structural differences between two values.
+//!
+//! [`diff`] compares two values and lists the [`Change`]s that turn the
+//! first into the second, each addressed by a JSON pointer. Objects are
+//! compared key by key. Arrays are aligned on their longest common
+//! subsequence, so an insertion does not show up as a change to every later
+//! element; equal elements that changed position are reported as moves.
+//!
+//! The changes are listed in the order they apply, which makes a [`Diff`]
+//! convertible to a [`Patch`] with [`Diff::to_patch`]. It also renders as a
+//! line-based diff with TOON-encoded values, in plain text through
+//! [`Display`](fmt::Display) or with ANSI colours through
+//! [`Diff::to_colored_string`]. Values that TOON has no layout for, such as
+//! `[[1, [2]]]`, are written on one line in an inline form instead.
+
+use std::fmt::{self, Write};
+
+use crate::Value;
+use crate::encode::Encoder;
+use crate::value::{Patch, PatchOperation, to_pointer, values_equal};
+
+/// Beyond this many element pairs, the middle parts of two arrays that
+/// differ are compared position by position rather than aligned.
+const ALIGN_LIMIT: usize = 1 << 20;
+
+/// One change between two values. Paths are JSON pointers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// `value` was added at `path`.
+    Added {
+        /// Where the value was added.
+        path: String,
+        /// The added value.
+        value: Value,
+    },
+    /// `value` was removed from `path`.
+    Removed {
+        /// Where the value was.
+        path: String,
+        /// The removed value.
+        value: Value,
+    },
+    /// The value at `path` changed from `old` to `new`.
+    Replaced {
+        /// The changed location.
+        path: String,
+        /// The previous value.
+        old: Value,
+        /// The new value.
+        new: Value,
+    },
+    /// An array element moved from `from` to `path`, as in an RFC 6902
+    /// `move` operation.
+    Moved {
+        /// The element's position before the move.
+        from: String,
+        /// The element's position after the move.
+        path: String,
+        /// The moved element.
+        value: Value,
+    },
+}
+
+/// The changes between two values, as returned by [`diff`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff(pub Vec<Change>);
+
+/// Compare two values.
+///
+/// Numbers compare by value and the order of object keys is ignored, as in
+/// patch `test` operations.
+///
+/// # Example
+///
+/// ```
+/// use toon_macro::{diff, toon, ValueExt};
+///
+/// let old = toon!({ title: "Draft", tags: ["a", "b", "c"] });
+/// let new = toon!({ title: "Final", tags: ["c", "a", "b"], done: true });
+///
+/// let changes = diff(&old, &new);
+/// assert_eq!(
+///     changes.to_string(),
+///     "- /title: Draft\n\
+///      + /title: Final\n\
+///      ~ /tags/2 -> /tags/0: c\n\
+///      + /done: true\n"
+/// );
+///
+/// let mut patched = old.clone();
+/// patched.apply_patch(&changes.to_patch()).unwrap();
+/// assert_eq!(patched, new);
+/// ```
+pub fn diff(old: &Value, new: &Value) -> Diff {
+    let mut changes = Vec::new();
+    diff_values(old, new, "", &mut changes);
+    Diff(changes)
+}
+
+impl Diff {
+    /// Whether the two values were equal.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The RFC 6902 patch performing these changes.
+    pub fn to_patch(&self) -> Patch {
+        self.0
+            .iter()
+            .map(|change| match change {
+                Change::Added { path, value } => PatchOperation::Add {
+                    path: path.clone(),
+                    value: value.clone(),
+                },
+                Change::Removed { path, .. } => PatchOperation::Remove { path: path.clone() },
+                Change::Replaced { path, new, .. } => PatchOperation::Replace {
+                    path: path.clone(),
+                    value: new.clone(),
+                },
+                Change::Moved { from, path, .. } => PatchOperation::Move {
+                    from: from.clone(),
+                    path: path.clone(),
+                },
+            })
+            .collect()
+    }
+
+    /// Render the diff with ANSI colours: removals in red, additions in
+    /// green and moves in cyan.
+    pub fn to_colored_string(&self) -> String {
+        let mut out = String::new();
+        // Writing to a `String` cannot fail, and every value renders.
+        let _ = self.render(&mut out, true);
+        out
+    }
+
+    fn render<W: Write + ?Sized>(&self, out: &mut W, color: bool) -> fmt::Result {
+        for change in &self.0 {
+            match change {
+                Change::Added { path, value } => render_line(out, color, '+', path, value)?,
+                Change::Removed { path, value } => render_line(out, color, '-', path, value)?,
+                Change::Replaced { path, old, new } => {
+                    render_line(out, color, '-', path, old)?;
+                    render_line(out, color, '+', path, new)?;
+                }
+                Change::Moved { from, path, value } => {
+                    let target = format!("{} -> {}", from, path);
+                    render_line(out, color, '~', &target, value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(f, false)
+    }
+}
+
+impl IntoIterator for Diff {
+    type Item = Change;
+    type IntoIter = std::vec::IntoIter<Change>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Write `value` as a TOON entry keyed by `target`, every line prefixed
+/// with `sign`. Values that TOON has no layout for are written on one line
+/// in an inline form instead.
+fn render_line<W: Write + ?Sized>(
+    out: &mut W,
+    color: bool,
+    sign: char,
+    target: &str,
+    value: &Value,
+) -> fmt::Result {
+    let (start, end) = match (color, sign) {
+        (false, _) => ("", ""),
+        (true, '+') => ("\x1b[32m", "\x1b[0m"),
+        (true, '-') => ("\x1b[31m", "\x1b[0m"),
+        (true, _) => ("\x1b[36m", "\x1b[0m"),
+    };
+
+    let mut text = String::new();
+    if Encoder::new(&mut text).value(value).is_err() {
+        text.clear();
+        Encoder::new(&mut text).inline_value(value)?;
+        return writeln!(out, "{}{} {}: {}{}", start, sign, target, text, end);
+    }
+    let mut lines = text.lines();
+    let head = match value {
+        Value::Object(_) => ":".to_string(),
+        // Root arrays are encoded as `[N]: ...`, which reads as `target[N]: ...`.
+        Value::Array(_) => lines.next().unwrap_or_default().to_string(),
+        _ => format!(": {}", text),
+    };
+
+    writeln!(out, "{}{} {}{}{}", start, sign, target, head, end)?;
+    if matches!(value, Value::Object(_) | Value::Array(_)) {
+        for line in lines {
+            writeln!(out, "{}{}   {}{}", start, sign, line, end)?;
+        }
+    }
+    Ok(())
+}
+
+fn diff_values(old: &Value, new: &Value, path: &str, changes: &mut Vec<Change>) {
+    if values_equal(old, new) {
+        return;
+    }
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, value) in old {
+                if !new.contains_key(key) {
+                    changes.push(Change::Removed {
+                        path: child(path, key),
+                        value: value.clone(),
+                    });
+                }
+            }
+            for (key, value) in new {
+                match old.get(key) {
+                    Some(previous) => diff_values(previous, value, &child(path, key), changes),
+                    None => changes.push(Change::Added {
+                        path: child(path, key),
+                        value: value.clone(),
+                    }),
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => diff_arrays(old, new, path, changes),
+        _ => changes.push(Change::Replaced {
+            path: path.to_string(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+    }
+}
+
+fn child(path: &str, segment: &str) -> String {
+    format!("{}{}", path, to_pointer(&[segment]))
+}
+
+/// Where an element of the new array comes from.
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    /// An equal element that keeps its relative order.
+    Kept(usize),
+    /// An equal element that changed position.
+    Moved(usize),
+    /// A different element at the same relative position, diffed in place.
+    Changed(usize),
+    /// A new element.
+    Inserted,
+}
+
+impl Source {
+    fn old_index(self) -> Option<usize> {
+        match self {
+            Source::Kept(i) | Source::Moved(i) | Source::Changed(i) => Some(i),
+            Source::Inserted => None,
+        }
+    }
+}
+
+/// Diff two arrays. The changes are emitted so that they apply in order:
+/// removals (from the back), then moves, then insertions, then changes
+/// inside elements at their final positions.
+fn diff_arrays(old: &[Value], new: &[Value], path: &str, changes: &mut Vec<Change>) {
+    let sources = align(old, new);
+    let mut used = vec![false; old.len()];
+    for source in &sources {
+        if let Some(i) = source.old_index() {
+            used[i] = true;
+        }
+    }
+    let index = |i: usize| child(path, &i.to_string());
+
+    // Positions refer to the array as modified by the changes so far; it is
+    // tracked as the old index of each element, `None` for inserted ones.
+    let mut current: Vec<Option<usize>> = (0..old.len()).map(Some).collect();
+    let position = |current: &[Option<usize>], i: usize| {
+        current
+            .iter()
+            .position(|&element| element == Some(i))
+            .expect("element is in the array")
+    };
+
+    for i in (0..old.len()).rev().filter(|&i| !used[i]) {
+        current.remove(i);
+        changes.push(Change::Removed {
+            path: index(i),
+            value: old[i].clone(),
+        });
+    }
+
+    // Each moved element goes right after the element that precedes it in
+    // the new array; handled in new-array order, this yields that order.
+    for (j, source) in sources.iter().enumerate() {
+        let Source::Moved(i) = *source else { continue };
+        let from = position(&current, i);
+        current.remove(from);
+        let predecessor = sources[..j].iter().rev().find_map(|s| s.old_index());
+        let to = predecessor.map_or(0, |p| position(&current, p) + 1);
+        current.insert(to, Some(i));
+        if from != to {
+            changes.push(Change::Moved {
+                from: index(from),
+                path: index(to),
+                value: old[i].clone(),
+            });
+        }
+    }
+
+    for (j, source) in sources.iter().enumerate() {
+        if *source == Source::Inserted {
+            current.insert(j, None);
+            changes.push(Change::Added {
+                path: index(j),
+                value: new[j].clone(),
+            });
+        }
+    }
+
+    for (j, source) in sources.iter().enumerate() {
+        if let Source::Changed(i) = *source {
+            diff_values(&old[i], &new[j], &index(j), changes);
+        }
+    }
+}
+
+/// Decide where each element of `new` comes from in `old`.
+fn align(old: &[Value], new: &[Value]) -> Vec<Source> {
+    let mut sources = vec![Source::Inserted; new.len()];
+    let mut matched = vec![false; old.len()];
+    let anchors = common_subsequence(old, new);
+    for &(i, j) in &anchors {
+        sources[j] = Source::Kept(i);
+        matched[i] = true;
+    }
+
+    let unmatched_old = matched.iter().filter(|&&m| !m).count();
+    let unmatched_new = sources.len() - anchors.len();
+    if unmatched_old * unmatched_new <= ALIGN_LIMIT {
+        for j in 0..new.len() {
+            if sources[j] != Source::Inserted {
+                continue;
+            }
+            let moved = (0..old.len()).find(|&i| !matched[i] && values_equal(&old[i], &new[j]));
+            if let Some(i) = moved {
+                sources[j] = Source::Moved(i);
+                matched[i] = true;
+            }
+        }
+    }
+
+    // Between consecutive anchors, pair the remaining elements in order.
+    let mut bounds = vec![(0, 0)];
+    bounds.extend(anchors.iter().map(|&(i, j)| (i + 1, j + 1)));
+    let mut ends: Vec<_> = anchors.clone();
+    ends.push((old.len(), new.len()));
+    for (&(old_start, new_start), &(old_end, new_end)) in bounds.iter().zip(&ends) {
+        let old_free = (old_start..old_end).filter(|&i| !matched[i]);
+        let new_free = (new_start..new_end).filter(|&j| sources[j] == Source::Inserted);
+        let pairs: Vec<_> = old_free.zip(new_free).collect();
+        for (i, j) in pairs {
+            sources[j] = Source::Changed(i);
+            matched[i] = true;
+        }
+    }
+    sources
+}
+
+/// Index pairs of a longest common subsequence of equal elements.
+fn common_subsequence(old: &[Value], new: &[Value]) -> Vec<(usize, usize)> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(a, b)| values_equal(a, b))
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| values_equal(a, b))
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut pairs: Vec<_> = (0..prefix).map(|k| (k, k)).collect();
+    if a.len() * b.len() <= ALIGN_LIMIT {
+        // lengths[i][j] is the length of a common subsequence of a[i..] and b[j..].
+        let width = b.len() + 1;
+        let mut lengths = vec![0u32; (a.len() + 1) * width];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lengths[i * width + j] = if values_equal(&a[i], &b[j]) {
+                    lengths[(i + 1) * width + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            if values_equal(&a[i], &b[j]) {
+                pairs.push((prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
+    pairs.extend((0..suffix).map(|k| (old.len() - suffix + k, new.len() - suffix + k)));
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ValueExt;
+
+    /// Diff, then check that the patch turns `old` into `new`.
+    fn check(old: Value, new: Value) -> Diff {
+        let changes = diff(&old, &new);
+        let mut patched = old;
+        patched.apply_patch(&changes.to_patch()).unwrap();
+        assert!(values_equal(&patched, &new), "{:?} != {:?}", patched, new);
+        changes
+    }
+
+    #[test]
+    fn test_objects() {
+        let changes = check(
+            toon!({ a: 1, b: { c: 2, d: 3 }, e: "x" }),
+            toon!({ a: 1.0, b: { c: 2, d: 4 }, f: [1] }),
+        );
+        assert_eq!(
+            changes.0,
+            [
+                Change::Removed {
+                    path: "/e".into(),
+                    value: toon!("x")
+                },
+                Change::Replaced {
+                    path: "/b/d".into(),
+                    old: toon!(3),
+                    new: toon!(4)
+                },
+                Change::Added {
+                    path: "/f".into(),
+                    value: toon!([1])
+                },
+            ]
+        );
+        assert!(diff(&toon!({ a: 1, b: 2 }), &toon!({ b: 2, a: 1 })).is_empty());
+    }
+
+    #[test]
+    fn test_arrays() {
+        let changes = check(toon!([1, 2, 3, 4]), toon!([1, 9, 2, 4]));
+        assert_eq!(
+            changes.0,
+            [
+                Change::Removed {
+                    path: "/2".into(),
+                    value: toon!(3)
+                },
+                Change::Added {
+                    path: "/1".into(),
+                    value: toon!(9)
+                },
+            ]
+        );
+
+        let changes = check(
+            toon!([{ id: 1, n: "a" }, { id: 2, n: "b" }]),
+            toon!([{ id: 1, n: "a" }, { id: 2, n: "B" }]),
+        );
+        assert_eq!(
+            changes.0,
+            [Change::Replaced {
+                path: "/1/n".into(),
+                old: toon!("b"),
+                new: toon!("B")
+            }]
+        );
+    }
+
+    #[test]
+    fn test_moves() {
+        let changes = check(toon!(["a", "b", "c", "d"]), toon!(["d", "a", "b", "c"]));
+        assert_eq!(
+            changes.0,
+            [Change::Moved {
+                from: "/3".into(),
+                path: "/0".into(),
+                value: toon!("d")
+            }]
+        );
+
+        check(toon!(["m2", "m1", "a"]), toon!(["a", "m1", "m2"]));
+        check(toon!([1, 2, 3, 4, 5, 6]), toon!([6, 5, 4, 3, 2, 1, 7]));
+        check(toon!(["x", "y", "x"]), toon!(["y", "x", "x", "z"]));
+    }
+
+    #[test]
+    fn test_mixed_changes_apply_in_order() {
+        check(
+            toon!({ list: [{ k: 1 }, "drop", 3, [4], { k: 5 }], meta: null }),
+            toon!({ list: [[4], { k: 1, extra: true }, 3, "new", { k: 6 }], meta: { v: 2 } }),
+        );
+        check(toon!([1, [2, 3]]), toon!({ replaced: "root" }));
+        check(toon!([]), toon!([1, 2]));
+        check(toon!([1, 2]), toon!([]));
+    }
+
+    #[test]
+    fn test_every_small_array_pair() {
+        let mut arrays = vec![Vec::new()];
+        for len in 1..=3 {
+            for n in 0..3usize.pow(len) {
+                let letter = |k| ["a", "b", "c"][n / 3usize.pow(k) % 3];
+                arrays.push((0..len).map(|k| toon!(letter(k))).collect::<Vec<_>>());
+            }
+        }
+        for old in &arrays {
+            for new in &arrays {
+                check(Value::Array(old.clone()), Value::Array(new.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let changes = diff(
+            &toon!({ user: { name: "Al" }, tags: ["a"], gone: { x: 1 } }),
+            &toon!({ user: { name: "Al", role: { id: 2 } }, tags: ["a", "b"] }),
+        );
+        assert_eq!(
+            changes.to_string(),
+            "- /gone:\n\
+             -   x: 1\n\
+             + /user/role:\n\
+             +   id: 2\n\
+             + /tags/1: b\n"
+        );
+        assert!(
+            changes
+                .to_colored_string()
+                .starts_with("\x1b[31m- /gone:\x1b[0m\n\x1b[31m-   x: 1\x1b[0m\n")
+        );
+
+        let changes = diff(&toon!({ a: [1] }), &toon!({ a: { b: [1, 2] } }));
+        assert_eq!(changes.to_string(), "- /a[1]: 1\n+ /a:\n+   b[2]: 1,2\n");
+    }
+
+    #[test]
+    fn test_render_nested_arrays() {
+        let changes = diff(&toon!(null), &toon!([[1, [2]]]));
+        assert_eq!(changes.to_string(), "- : null\n+ : [[1, [2]]]\n");
+
+        let changes = diff(
+            &toon!({ a: [[1], [2]] }),
+            &toon!({ a: [[1], [2, [3]]], b: { c: [[{ d: 1 }]] } }),
+        );
+        assert_eq!(
+            changes.to_string(),
+            "+ /a/1/1[1]: 3\n+ /b: {c: [[{d: 1}]]}\n"
+        );
+        assert!(
+            changes
+                .to_colored_string()
+                .contains("\x1b[32m+ /b: {c: [[{d: 1}]]}\x1b[0m")
+        );
+    }
+
+    #[test]
+    fn test_render_generated_diffs() {
+        let values: Vec<Value> = crate::arbitrary::values(300).collect();
+        for pair in values.windows(2) {
+            let changes = diff(&pair[0], &pair[1]);
+            assert!(changes.to_string().lines().count() >= changes.0.len());
+            assert!(changes.to_colored_string().lines().count() >= changes.0.len());
+        }
+    }
+}
@@ -0,0 +1,712 @@
+//! Streaming TOON encoder.
+//!
+//! This is a port of the `serde_toon2` encoder (with its default options:
+//! two-space indent, comma delimiter, no key folding) that writes to any
+//! [`fmt::Write`]. It produces output byte-for-byte identical to
+//! [`to_toon_string`](crate::to_toon_string), and additionally allows
+//! object entries to be written one at a time, which is what
+//! [`toon_format!`](crate::toon_format) builds on.
+//!
+//! An array inside another array is written inline, so it can only hold
+//! primitives: `[[1, 2], [3]]` has a TOON layout but `[[1, [2]]]` does not.
+//! Where `serde_toon2` panics on such values, the encoder returns
+//! [`fmt::Error`], so callers must not assume that writing to a `String`
+//! cannot fail.
+//!
+//! Matching that output depends on `serde_toon2` internals, so the workspace
+//! pins it to an exact version, and the tests below compare the two encoders
+//! on values from `arbitrary.rs`. Re-run them before bumping the pin.
+
+use std::fmt::{self, Write};
+
+use crate::{Map, Number, Value};
+
+const INDENT: usize = 2;
+const DELIMITER: char = ',';
+
+/// Encodes [`Value`]s as TOON text into a [`fmt::Write`].
+pub(crate) struct Encoder<'w, W: ?Sized> {
+    out: &'w mut W,
+    depth: usize,
+    /// Whether the next entry must start on a new line.
+    needs_newline: bool,
+    /// Write non-integral floats in full rather than as `serde_toon2` does.
+    exact_floats: bool,
+    /// Quote the keys of array headers like other keys.
+    quote_header_keys: bool,
+}
+
+impl<'w, W: Write + ?Sized> Encoder<'w, W> {
+    pub(crate) fn new(out: &'w mut W) -> Self {
+        Encoder {
+            out,
+            depth: 0,
+            needs_newline: false,
+            exact_floats: false,
+            quote_header_keys: false,
+        }
+    }
+
+    /// Write finite non-integral floats with their shortest exact decimal
+    /// form. `serde_toon2` switches to 17 fixed decimals for floats whose
+    /// shortest form has an exponent, so `1e-20` comes out as `0`.
+    pub(crate) fn exact_floats(mut self) -> Self {
+        self.exact_floats = true;
+        self
+    }
+
+    /// Quote the keys of array headers where other keys would be quoted.
+    /// `serde_toon2` writes them as they are, so `"a b": 1` but `a b[1]: 1`.
+    pub(crate) fn quote_header_keys(mut self) -> Self {
+        self.quote_header_keys = true;
+        self
+    }
+
+    /// Write a complete document.
+    pub(crate) fn value(&mut self, value: &Value) -> fmt::Result {
+        self.value_with_key(value, None)
+    }
+
+    /// Write one `key: value` entry of the object currently being written.
+    pub(crate) fn entry(&mut self, key: &str, value: &Value) -> fmt::Result {
+        self.entry_separator()?;
+        self.value_with_key(value, Some(key))
+    }
+
+    /// Start a nested object under `key`; its entries follow until
+    /// [`end_object`](Self::end_object).
+    pub(crate) fn begin_object(&mut self, key: &str) -> fmt::Result {
+        self.entry_separator()?;
+        self.indent()?;
+        self.key(key)?;
+        self.out.write_char(':')?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub(crate) fn end_object(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn entry_separator(&mut self) -> fmt::Result {
+        if self.needs_newline {
+            self.out.write_char('\n')?;
+        }
+        self.needs_newline = true;
+        Ok(())
+    }
+
+    fn indent(&mut self) -> fmt::Result {
+        write!(self.out, "{:1$}", "", self.depth * INDENT)
+    }
+
+    fn value_with_key(&mut self, value: &Value, key: Option<&str>) -> fmt::Result {
+        match value {
+            Value::Array(items) => self.array(items, key),
+            Value::Object(map) => self.object(map, key),
+            primitive => {
+                if let Some(key) = key {
+                    self.indent()?;
+                    self.key(key)?;
+                    self.out.write_str(": ")?;
+                }
+                self.primitive(primitive)
+            }
+        }
+    }
+
+    fn object(&mut self, map: &Map<String, Value>, key: Option<&str>) -> fmt::Result {
+        match key {
+            Some(key) => {
+                self.indent()?;
+                self.key(key)?;
+                self.out.write_char(':')?;
+                self.depth += 1;
+                for (key, value) in map {
+                    self.out.write_char('\n')?;
+                    self.value_with_key(value, Some(key))?;
+                }
+                self.depth -= 1;
+            }
+            None => {
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        self.out.write_char('\n')?;
+                    }
+                    self.value_with_key(value, Some(key))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Array header. Note that `serde_toon2` does not quote keys here,
+    /// unless [`quote_header_keys`](Self::quote_header_keys) is set.
+    fn array_header(&mut self, key: Option<&str>, len: usize) -> fmt::Result {
+        if let Some(key) = key {
+            self.indent()?;
+            if self.quote_header_keys {
+                self.key(key)?;
+            } else {
+                self.out.write_str(key)?;
+            }
+        }
+        write!(self.out, "[{}]", len)
+    }
+
+    fn array(&mut self, items: &[Value], key: Option<&str>) -> fmt::Result {
+        if items.is_empty() {
+            self.array_header(key, 0)?;
+            return self.out.write_char(':');
+        }
+
+        if is_primitive_array(items) {
+            self.array_header(key, items.len())?;
+            self.out.write_str(": ")?;
+            self.primitive_items(items)
+        } else if is_array_of_arrays(items) {
+            self.array_header(key, items.len())?;
+            self.out.write_char(':')?;
+            self.nested_arrays(items)
+        } else if let Some(fields) = tabular_fields(items) {
+            self.array_header(key, items.len())?;
+            self.tabular(items, &fields)
+        } else {
+            self.array_header(key, items.len())?;
+            self.out.write_char(':')?;
+            self.list_items(items)
+        }
+    }
+
+    /// An inline primitive array, e.g. the `[2]: a,b` after a `- `.
+    fn inline_array(&mut self, items: &[Value]) -> fmt::Result {
+        if items.is_empty() {
+            return self.out.write_str("[0]:");
+        }
+        write!(self.out, "[{}]: ", items.len())?;
+        self.primitive_items(items)
+    }
+
+    fn primitive_items(&mut self, items: &[Value]) -> fmt::Result {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.out.write_char(DELIMITER)?;
+            }
+            self.primitive(item)?;
+        }
+        Ok(())
+    }
+
+    fn nested_arrays(&mut self, items: &[Value]) -> fmt::Result {
+        self.depth += 1;
+        for item in items {
+            if let Value::Array(inner) = item {
+                self.out.write_char('\n')?;
+                self.indent()?;
+                self.out.write_str("- ")?;
+                self.inline_array(inner)?;
+            }
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    /// Tabular body, starting at the `{fields}:` part of the header.
+    fn tabular(&mut self, items: &[Value], fields: &[&String]) -> fmt::Result {
+        self.out.write_char('{')?;
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                self.out.write_char(DELIMITER)?;
+            }
+            self.key(field)?;
+        }
+        self.out.write_str("}:")?;
+
+        self.depth += 1;
+        for item in items {
+            if let Value::Object(map) = item {
+                self.out.write_char('\n')?;
+                self.indent()?;
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.out.write_char(DELIMITER)?;
+                    }
+                    if let Some(value) = map.get(field.as_str()) {
+                        self.primitive(value)?;
+                    }
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn list_items(&mut self, items: &[Value]) -> fmt::Result {
+        self.depth += 1;
+        for item in items {
+            self.out.write_char('\n')?;
+            self.indent()?;
+            self.out.write_str("- ")?;
+            match item {
+                Value::Array(inner) => self.inline_array(inner)?,
+                Value::Object(map) => self.list_item_object(map)?,
+                primitive => self.primitive(primitive)?,
+            }
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    /// An object in a list: its first entry shares the `- ` line.
+    fn list_item_object(&mut self, map: &Map<String, Value>) -> fmt::Result {
+        let mut entries = map.iter();
+        let Some((key, value)) = entries.next() else {
+            return Ok(());
+        };
+
+        match value {
+            Value::Array(items) => {
+                // Unlike other array headers, this one quotes its key.
+                self.key(key)?;
+                if is_primitive_array(items) {
+                    if items.is_empty() {
+                        self.out.write_str("[0]:")?;
+                    } else {
+                        write!(self.out, "[{}]: ", items.len())?;
+                        self.primitive_items(items)?;
+                    }
+                } else if is_array_of_arrays(items) {
+                    write!(self.out, "[{}]:", items.len())?;
+                    self.nested_arrays(items)?;
+                } else if let Some(fields) = tabular_fields(items) {
+                    write!(self.out, "[{}]", items.len())?;
+                    self.tabular(items, &fields)?;
+                } else {
+                    write!(self.out, "[{}]:", items.len())?;
+                    self.list_items(items)?;
+                }
+            }
+            Value::Object(nested) => {
+                self.key(key)?;
+                self.out.write_char(':')?;
+                self.depth += 1;
+                for (key, value) in nested {
+                    self.out.write_char('\n')?;
+                    self.value_with_key(value, Some(key))?;
+                }
+                self.depth -= 1;
+            }
+            primitive => {
+                self.key(key)?;
+                self.out.write_str(": ")?;
+                self.primitive(primitive)?;
+            }
+        }
+
+        for (key, value) in entries {
+            match value {
+                Value::Array(items) => {
+                    self.out.write_char('\n')?;
+                    self.depth += 1;
+                    self.array(items, Some(key))?;
+                    self.depth -= 1;
+                }
+                Value::Object(nested) => {
+                    self.out.write_char('\n')?;
+                    self.depth += 1;
+                    self.indent()?;
+                    self.key(key)?;
+                    self.out.write_char(':')?;
+                    self.depth += 1;
+                    for (key, value) in nested {
+                        self.out.write_char('\n')?;
+                        self.value_with_key(value, Some(key))?;
+                    }
+                    self.depth -= 2;
+                }
+                primitive => {
+                    self.out.write_char('\n')?;
+                    self.depth += 1;
+                    self.indent()?;
+                    self.depth -= 1;
+                    self.key(key)?;
+                    self.out.write_str(": ")?;
+                    self.primitive(primitive)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn primitive(&mut self, value: &Value) -> fmt::Result {
+        match value {
+            Value::Null => self.out.write_str("null"),
+            Value::Bool(b) => write!(self.out, "{}", b),
+            Value::Number(Number::F64(f))
+                if self.exact_floats && f.is_finite() && f.fract() != 0.0 =>
+            {
+                write!(self.out, "{}", f)
+            }
+            Value::Number(n) => write_number(self.out, n),
+            Value::String(s) => self.string(s),
+            // An array or object inside an array inside an array, which TOON
+            // has no layout for; `serde_toon2` hits `unreachable!()` here.
+            Value::Array(_) | Value::Object(_) => Err(fmt::Error),
+        }
+    }
+
+    /// Write `value` on one line, arrays in brackets and objects in braces
+    /// with primitives and keys as in TOON, e.g. `{grid: [[1, [2]]]}`. For
+    /// values that have no TOON layout, where [`value`](Self::value) fails.
+    pub(crate) fn inline_value(&mut self, value: &Value) -> fmt::Result {
+        match value {
+            Value::Array(items) => {
+                self.out.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.out.write_str(", ")?;
+                    }
+                    self.inline_value(item)?;
+                }
+                self.out.write_char(']')
+            }
+            Value::Object(map) => {
+                self.out.write_char('{')?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        self.out.write_str(", ")?;
+                    }
+                    self.key(key)?;
+                    self.out.write_str(": ")?;
+                    self.inline_value(value)?;
+                }
+                self.out.write_char('}')
+            }
+            primitive => self.primitive(primitive),
+        }
+    }
+
+    fn string(&mut self, s: &str) -> fmt::Result {
+        if needs_quoting(s) {
+            write_quoted(self.out, s)
+        } else {
+            self.out.write_str(s)
+        }
+    }
+
+    fn key(&mut self, key: &str) -> fmt::Result {
+        write_key(self.out, key)
+    }
+}
+
+/// Write an object key, quoted if it is not a plain identifier.
+pub(crate) fn write_key<W: Write + ?Sized>(out: &mut W, key: &str) -> fmt::Result {
+    if key_needs_quoting(key) {
+        write_quoted(out, key)
+    } else {
+        out.write_str(key)
+    }
+}
+
+fn write_quoted<W: Write + ?Sized>(out: &mut W, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for ch in s.chars() {
+        match ch {
+            '\\' => out.write_str("\\\\")?,
+            '"' => out.write_str("\\\"")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            _ => out.write_char(ch)?,
+        }
+    }
+    out.write_char('"')
+}
+
+pub(crate) fn write_number<W: Write + ?Sized>(out: &mut W, n: &Number) -> fmt::Result {
+    match n {
+        Number::I64(n) => write!(out, "{}", n),
+        Number::U64(n) => write!(out, "{}", n),
+        Number::F64(f) if !f.is_finite() => out.write_str("null"),
+        Number::F64(f) if *f == 0.0 => out.write_str("0"),
+        Number::F64(f) if f.fract() == 0.0 => write!(out, "{:.0}", f),
+        Number::F64(f) => {
+            let s = f.to_string();
+            if s.contains('e') || s.contains('E') {
+                let s = format!("{:.17}", f);
+                out.write_str(s.trim_end_matches('0').trim_end_matches('.'))
+            } else {
+                out.write_str(&s)
+            }
+        }
+    }
+}
+
+fn is_primitive(value: &Value) -> bool {
+    !matches!(value, Value::Array(_) | Value::Object(_))
+}
+
+fn is_primitive_array(items: &[Value]) -> bool {
+    items.iter().all(is_primitive)
+}
+
+fn is_array_of_arrays(items: &[Value]) -> bool {
+    !items.is_empty()
+        && items
+            .iter()
+            .all(|item| matches!(item, Value::Array(inner) if is_primitive_array(inner)))
+}
+
+/// The header fields if `items` can be written as a table: every item is an
+/// object of primitives, all with the same set of keys.
+fn tabular_fields(items: &[Value]) -> Option<Vec<&String>> {
+    let mut fields: Option<Vec<&String>> = None;
+    for item in items {
+        let Value::Object(map) = item else {
+            return None;
+        };
+        if !map.values().all(is_primitive) {
+            return None;
+        }
+        match &fields {
+            Some(fields) => {
+                if map.len() != fields.len() || !fields.iter().all(|f| map.contains_key(*f)) {
+                    return None;
+                }
+            }
+            None => fields = Some(map.keys().collect()),
+        }
+    }
+    fields
+}
+
+fn needs_quoting(s: &str) -> bool {
+    s.is_empty()
+        || s.starts_with(char::is_whitespace)
+        || s.ends_with(char::is_whitespace)
+        || matches!(s, "true" | "false" | "null")
+        || s.starts_with('-')
+        || s.contains([
+            DELIMITER, ':', '"', '\\', '[', ']', '{', '}', '\n', '\r', '\t',
+        ])
+        || looks_like_number(s)
+        || has_leading_zeros(s)
+}
+
+fn looks_like_number(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while *i < bytes.len() && bytes[*i].is_ascii_digit() {
+            *i += 1;
+        }
+        *i > start
+    };
+
+    if bytes.first() == Some(&b'-') {
+        i += 1;
+    }
+    if !digits(&mut i) {
+        return false;
+    }
+    if bytes.get(i) == Some(&b'.') {
+        i += 1;
+        if !digits(&mut i) {
+            return false;
+        }
+    }
+    if matches!(bytes.get(i), Some(b'e' | b'E')) {
+        i += 1;
+        if matches!(bytes.get(i), Some(b'+' | b'-')) {
+            i += 1;
+        }
+        if !digits(&mut i) {
+            return false;
+        }
+    }
+    i == bytes.len()
+}
+
+fn has_leading_zeros(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    bytes.len() >= 2 && bytes[0] == b'0' && bytes[1].is_ascii_digit()
+}
+
+fn key_needs_quoting(key: &str) -> bool {
+    let mut bytes = key.bytes();
+    let valid_first = bytes
+        .next()
+        .is_some_and(|b| b.is_ascii_alphabetic() || b == b'_');
+    !valid_first || !bytes.all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_string(value: &Value) -> String {
+        let mut out = String::new();
+        Encoder::new(&mut out).value(value).unwrap();
+        out
+    }
+
+    fn assert_matches_serde_toon2(value: &Value) {
+        let expected = serde_toon2::to_string(value).unwrap();
+        assert_eq!(to_string(value), expected, "for {:?}", value);
+    }
+
+    #[test]
+    fn test_encode_scalars() {
+        for value in [
+            toon!(null),
+            toon!(true),
+            toon!(-42),
+            toon!(42u64),
+            toon!(1.5),
+            toon!(2.0),
+            toon!(-0.0),
+            toon!(1e21),
+            toon!(1.25e-7),
+            toon!(f64::NAN),
+            toon!(""),
+            toon!("plain"),
+            toon!(" padded"),
+            toon!("true"),
+            toon!("-dash"),
+            toon!("a,b"),
+            toon!("key: value"),
+            toon!("quote \" and \\ slash"),
+            toon!("line\nbreak\ttab"),
+            toon!("123"),
+            toon!("1.5e3"),
+            toon!("007"),
+            toon!("1."),
+            toon!("ünïcödé"),
+        ] {
+            assert_matches_serde_toon2(&value);
+        }
+    }
+
+    #[test]
+    fn test_encode_objects() {
+        assert_matches_serde_toon2(&toon!({}));
+        assert_matches_serde_toon2(&toon!({
+            name: "Alice",
+            "needs quoting": 1,
+            "": null,
+            "9lives": true,
+            "dotted.key": "x",
+            nested: { deeper: { value: 1 }, empty: {} },
+            after: 2
+        }));
+    }
+
+    #[test]
+    fn test_encode_arrays() {
+        for value in [
+            toon!([]),
+            toon!([1, "two", null, true]),
+            toon!([[1, 2], [], ["a"]]),
+            toon!([{ id: 1, name: "A" }, { name: "B", id: 2 }]),
+            toon!([{}, {}]),
+            toon!([{ id: 1 }, { id: 2, extra: 3 }]),
+            toon!([1, [2, 3], { a: 1 }, {}]),
+            toon!({
+                "odd key": [1, 2],
+                empty: [],
+                grid: [[1], [2]],
+                rows: [{ "a b": 1, c: "x,y" }],
+                mixed: [1, { a: 1 }]
+            }),
+        ] {
+            assert_matches_serde_toon2(&value);
+        }
+    }
+
+    #[test]
+    fn test_encode_list_item_objects() {
+        let value = toon!([
+            { first: null, second: [1, 2], third: { x: 1 }, fourth: "s" },
+            { "k y": [1], next: [[1], [2]] },
+            { grid: [[1], [2]], rows: [{ a: 1 }, { a: 2 }] },
+            { table: [{ a: 1 }], list: [1, { b: 2 }] },
+            { list: [{ a: [1] }, 2], nested: { deep: { x: 1 } } },
+            { obj: { inner: [1, 2] }, tail: {} },
+            { "": 1 }
+        ]);
+        assert_matches_serde_toon2(&value);
+    }
+
+    #[test]
+    fn test_encode_matches_serde_toon2_on_generated_values() {
+        let mut checked = 0;
+        for value in crate::arbitrary::values(5_000) {
+            let mut out = String::new();
+            // `serde_toon2` panics where the encoder returns an error.
+            if Encoder::new(&mut out).value(&value).is_ok() {
+                assert_matches_serde_toon2(&value);
+                checked += 1;
+            }
+        }
+        assert!(checked > 4_000, "only {} values were encodable", checked);
+    }
+
+    #[test]
+    fn test_encode_unrepresentable_layouts() {
+        for value in [
+            toon!([[1, [2]]]),
+            toon!({ grid: [[{ a: 1 }]] }),
+            toon!([1, [2, [3]]]),
+        ] {
+            let mut out = String::new();
+            assert_eq!(Encoder::new(&mut out).value(&value), Err(fmt::Error));
+        }
+    }
+
+    #[test]
+    fn test_encode_inline_value() {
+        let value = toon!({ grid: [[1, [2, "a b"]]], "k y": {}, e: [], s: "x,y" });
+        let mut out = String::new();
+        Encoder::new(&mut out).inline_value(&value).unwrap();
+        assert_eq!(
+            out,
+            r#"{grid: [[1, [2, a b]]], "k y": {}, e: [], s: "x,y"}"#
+        );
+    }
+
+    #[test]
+    fn test_encode_quoted_header_keys() {
+        let value = toon!({ "k y": [1], "t?": [{ a: 1 }], plain: [[1]] });
+        assert_eq!(
+            to_string(&value),
+            "k y[1]: 1\nt?[1]{a}:\n  1\nplain[1]:\n  - [1]: 1"
+        );
+
+        let mut out = String::new();
+        Encoder::new(&mut out)
+            .quote_header_keys()
+            .value(&value)
+            .unwrap();
+        assert_eq!(
+            out,
+            "\"k y\"[1]: 1\n\"t?\"[1]{a}:\n  1\nplain[1]:\n  - [1]: 1"
+        );
+    }
+
+    #[test]
+    fn test_encode_entries() {
+        let mut out = String::new();
+        let mut encoder = Encoder::new(&mut out);
+        encoder.entry("a", &toon!(1)).unwrap();
+        encoder.begin_object("b").unwrap();
+        encoder.entry("c", &toon!([1, 2])).unwrap();
+        encoder.begin_object("d").unwrap();
+        encoder.end_object();
+        encoder.end_object();
+        encoder.entry("e", &toon!("x")).unwrap();
+
+        let expected = toon!({ a: 1, b: { c: [1, 2], d: {} }, e: "x" });
+        assert_eq!(out, to_string(&expected));
+    }
+}
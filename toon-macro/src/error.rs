@@ -5,8 +5,15 @@
 
 use thiserror::Error;
 
+use crate::Value;
+use crate::value::describe;
+
 /// A unified error type for all toon-macro operations.
+///
+/// New variants may be added in minor releases, so matches on it need a
+/// wildcard arm.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     /// Error during TOON serialization.
     #[error("TOON serialization error: {0}")]
@@ -54,6 +61,61 @@ pub enum Error {
         /// The number of columns in the table.
         len: usize,
     },
+
+    /// A path segment does not exist in the value.
+    #[error("Path not found: {} has no `{segment}`", location(.path))]
+    PathNotFound {
+        /// JSON pointer to the value that was searched.
+        path: String,
+        /// The missing key or index.
+        segment: String,
+    },
+
+    /// A path segment reaches a value that cannot be indexed by it.
+    #[error("Path type mismatch: cannot index {got} at {} with `{segment}`", location(.path))]
+    PathTypeMismatch {
+        /// JSON pointer to the value that was indexed.
+        path: String,
+        /// The key or index that was applied.
+        segment: String,
+        /// The type name of the indexed value.
+        got: &'static str,
+    },
+
+    /// A path that cannot be used for the requested operation.
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
+
+    /// A query that could not be parsed.
+    #[error("Invalid query: {message} at position {position} in `{query}`")]
+    InvalidQuery {
+        /// The query text.
+        query: String,
+        /// Byte offset of the problem in `query`.
+        position: usize,
+        /// What was wrong.
+        message: String,
+    },
+
+    /// A patch document that is not a valid list of operations.
+    #[error("Invalid patch: {0}")]
+    InvalidPatch(String),
+
+    /// A `test` operation of a patch did not match.
+    #[error("Patch test failed: value at `{path}` differs from the expected value")]
+    PatchTestFailed {
+        /// JSON pointer of the tested value.
+        path: String,
+    },
+}
+
+/// Describe the value at a JSON pointer in a message.
+fn location(path: &str) -> String {
+    if path.is_empty() {
+        "the root".to_string()
+    } else {
+        format!("`{}`", path)
+    }
 }
 
 /// A `Result` type alias using [`enum@Error`].
@@ -105,6 +167,30 @@ impl Error {
             got: format!("{:?}", got),
         }
     }
+
+    /// Create an invalid type error for a TOON value.
+    ///
+    /// Unlike [`invalid_type`](Error::invalid_type), the value is described
+    /// briefly (`string "abc"`, `array of 3 elements`) rather than dumped,
+    /// which keeps messages readable for large documents.
+    pub fn invalid_value(expected: &'static str, value: &Value) -> Self {
+        Error::InvalidType {
+            expected,
+            got: describe(value),
+        }
+    }
+
+    /// Add the location of the value an error is about to its message.
+    pub(crate) fn at(self, path: &str) -> Self {
+        match self {
+            Error::InvalidType { expected, got } => Error::InvalidType {
+                expected,
+                got: format!("{} at `{}`", got, path),
+            },
+            Error::ConversionError(msg) => Error::ConversionError(format!("{} at `{}`", msg, path)),
+            other => other,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -131,4 +217,21 @@ mod tests {
         let err = Error::invalid_table("missing header");
         assert!(matches!(err, Error::InvalidTable(_)));
     }
+
+    #[test]
+    fn test_invalid_value_is_brief() {
+        let big = Value::Array(vec![Value::Null; 1000]);
+        let err = Error::invalid_value("object", &big);
+        assert_eq!(
+            err.to_string(),
+            "Invalid value type: expected object, got array of 1000 elements"
+        );
+
+        let long = Value::String("x".repeat(100));
+        let err = Error::invalid_value("i64", &long).at("/id");
+        assert_eq!(
+            err.to_string(),
+            "Invalid value type: expected i64, got string of 100 characters at `/id`"
+        );
+    }
 }
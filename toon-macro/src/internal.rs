@@ -1,58 +1,14 @@
-//! Internal helper macros and utilities.
+//! Internal helper utilities.
 //!
 //! This module contains implementation details used by the public macros.
 //! These are not part of the public API and may change without notice.
 
-/// Convert a key (identifier or literal) to a String.
-///
-/// This macro is used internally by the `toon!` macro to handle both
-/// identifier keys (like `name`) and string literal keys (like `"weird-key"`).
-#[doc(hidden)]
-#[macro_export]
-macro_rules! __toon_key_to_string {
-    ($k:ident) => {
-        stringify!($k).to_string()
-    };
-    ($k:literal) => {
-        $k.to_string()
-    };
-}
-
-/// Internal macro for constructing TOON values.
-///
-/// This handles the recursive value construction in the `toon!` macro.
-#[doc(hidden)]
-#[macro_export]
-macro_rules! __toon_value {
-    // Null literal
-    (null) => {
-        $crate::Value::Null
-    };
-
-    // Boolean literals
-    (true) => {
-        $crate::Value::Bool(true)
-    };
-    (false) => {
-        $crate::Value::Bool(false)
-    };
-
-    // Nested object
-    ({ $($key:tt : $value:tt),* $(,)? }) => {
-        $crate::toon!({ $($key : $value),* })
-    };
-
-    // Array
-    ([ $($value:tt),* $(,)? ]) => {
-        $crate::toon!([ $($value),* ])
-    };
+use std::cmp::Ordering;
+use std::fmt;
 
-    // Any other expression (numbers, strings, variables)
-    // We use a helper function to convert types properly
-    ($other:expr) => {
-        $crate::internal::into_value($other)
-    };
-}
+use crate::encode::Encoder;
+use crate::value::{compare_numbers, describe, kind};
+use crate::{Error, Result, ToToon};
 
 /// Internal helper to create a TOON Map.
 #[doc(hidden)]
@@ -74,146 +30,497 @@ pub fn map_insert(
 
 /// Trait for converting values to TOON Value type.
 ///
-/// This exists to provide conversions for types not covered by
-/// serde_toon2's From implementations (like i32, u32, etc.)
+/// Superseded by [`ToToon`], which every type implementing this trait now
+/// gets through a blanket implementation.
 #[doc(hidden)]
+#[deprecated(since = "0.2.0", note = "use `toon_macro::ToToon` instead")]
 pub trait IntoToonValueInternal {
     fn into_toon_value(self) -> serde_toon2::Value;
 }
 
-// Implement for types that already have From<T> for Value
-impl IntoToonValueInternal for bool {
+#[allow(deprecated)]
+impl<T: ToToon> IntoToonValueInternal for T {
     #[inline]
     fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::Bool(self)
+        self.into_toon()
     }
 }
 
-impl IntoToonValueInternal for i64 {
+/// Wrapper around a value expression in `toon!`.
+///
+/// Types implementing [`ToToon`] are converted by the inherent
+/// [`Embed::into_toon`] method. Types implementing only the deprecated
+/// [`IntoToonValue`](crate::table::IntoToonValue) fall back to the
+/// [`EmbedLegacy`] trait method, and with the `serde` feature any other
+/// `Serialize` type falls back to [`EmbedSerialize`], which takes `&self` so
+/// that it is only tried after the other two.
+#[doc(hidden)]
+pub struct Embed<T>(pub T);
+
+impl<T: ToToon> Embed<T> {
     #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::from(self)
+    pub fn into_toon(self) -> serde_toon2::Value {
+        self.0.into_toon()
     }
 }
 
-impl IntoToonValueInternal for u64 {
+/// Fallback conversion for values inside `toon!` that implement only the
+/// deprecated [`IntoToonValue`](crate::table::IntoToonValue).
+#[doc(hidden)]
+pub trait EmbedLegacy {
+    fn into_toon(self) -> serde_toon2::Value;
+}
+
+#[allow(deprecated)]
+impl<T: crate::table::IntoToonValue> EmbedLegacy for Embed<T> {
     #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::from(self)
+    fn into_toon(self) -> serde_toon2::Value {
+        self.0.to_toon_value()
     }
 }
 
-impl IntoToonValueInternal for f64 {
-    #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::from(self)
+/// Fallback conversion for `serde::Serialize` values inside `toon!`.
+#[doc(hidden)]
+pub trait EmbedSerialize {
+    // Same name as the other conversions so one call site dispatches to all.
+    #[allow(clippy::wrong_self_convention)]
+    fn into_toon(&self) -> serde_toon2::Value;
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> EmbedSerialize for Embed<T> {
+    #[track_caller]
+    fn into_toon(&self) -> serde_toon2::Value {
+        match crate::value::to_value(&self.0) {
+            Ok(value) => value,
+            Err(err) => panic!("cannot embed value in toon!: {}", err),
+        }
     }
 }
 
-impl IntoToonValueInternal for String {
-    #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::String(self)
+/// Convert a `#[derive(ToonTable)]` cell.
+///
+/// Goes through the deprecated [`IntoToonValue`](crate::table::IntoToonValue),
+/// which every [`ToToon`] type implements, so that types implementing only
+/// the old trait keep working as cells.
+#[doc(hidden)]
+#[inline]
+#[allow(deprecated)]
+pub fn cell_to_toon<T: crate::table::IntoToonValue + ?Sized>(cell: &T) -> serde_toon2::Value {
+    cell.to_toon_value()
+}
+
+/// Convert a cell back into a `#[derive(ToonTable)]` field, through the
+/// deprecated [`FromToonValue`](crate::table::FromToonValue) for the same
+/// reason as [`cell_to_toon`].
+#[doc(hidden)]
+#[inline]
+#[allow(deprecated)]
+pub fn cell_from_toon<T: crate::table::FromToonValue>(cell: &serde_toon2::Value) -> Result<T> {
+    T::from_toon_value(cell)
+}
+
+/// Runtime parsing for `toon_str!` with a non-literal argument.
+///
+/// Panics at the macro call site with the location of the failure inside
+/// `input` and a snippet of the offending line.
+#[doc(hidden)]
+#[track_caller]
+pub fn parse_toon_str(input: &str) -> serde_toon2::Value {
+    match serde_toon2::from_str(input) {
+        Ok(value) => value,
+        Err(err) => panic!("{}", describe_parse_error(input, &err)),
     }
 }
 
-impl IntoToonValueInternal for &str {
-    #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::String(self.to_string())
+/// Format a `serde_toon2` error for a `toon_str!` panic.
+fn describe_parse_error(input: &str, err: &serde_toon2::Error) -> String {
+    let (message, location) = split_error(err);
+    let Some((line, column)) = location else {
+        return format!("invalid TOON in toon_str!: {}", message);
+    };
+
+    let snippet = input.lines().nth(line.saturating_sub(1)).unwrap_or("");
+    let gutter = " ".repeat(line.to_string().len());
+    format!(
+        "invalid TOON in toon_str!: {message}\n\
+         {gutter}--> input line {line}, column {column}\n\
+         {gutter} |\n\
+         {line} | {snippet}\n\
+         {gutter} | {caret:>column$}",
+        caret = "^",
+    )
+}
+
+/// Split a `serde_toon2` error into its message and its line and column.
+///
+/// `serde_toon2` keeps the location of an error private and only appends it
+/// to its `Display` output as `" at line N, column M"`. The derive crate
+/// reads it back the same way.
+fn split_error(err: &serde_toon2::Error) -> (String, Option<(usize, usize)>) {
+    let message = err.to_string();
+    let location = message.rsplit_once(" at line ").and_then(|(text, loc)| {
+        let (line, column) = loc.split_once(", column ")?;
+        Some((text.len(), (line.parse().ok()?, column.parse().ok()?)))
+    });
+    match location {
+        Some((len, location)) => (message[..len].to_string(), Some(location)),
+        None => (message, None),
     }
 }
 
-impl IntoToonValueInternal for &String {
+/// Tracks the keys explicitly written in a `toon!` object.
+///
+/// Used instead of [`map_insert`] when an object has computed or guarded
+/// keys that cannot be checked for duplicates at compile time. The check is
+/// only performed in debug builds; in release builds this is a plain insert.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct KeyTracker {
+    #[cfg(debug_assertions)]
+    seen: std::collections::HashSet<String>,
+}
+
+impl KeyTracker {
     #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::String(self.clone())
+    pub fn new() -> Self {
+        Self::default()
     }
-}
 
-// Implement for smaller integer types (convert to i64/u64)
-impl IntoToonValueInternal for i8 {
+    /// Insert into `map`, asserting in debug builds that `key` was not
+    /// already written by another explicit entry.
     #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::Number(serde_toon2::Number::I64(self as i64))
+    #[track_caller]
+    pub fn insert(
+        &mut self,
+        map: &mut serde_toon2::Map<String, serde_toon2::Value>,
+        key: String,
+        value: serde_toon2::Value,
+    ) {
+        #[cfg(debug_assertions)]
+        assert!(
+            self.seen.insert(key.clone()),
+            "duplicate key `{}` in toon! object",
+            key
+        );
+        map.insert(key, value);
     }
 }
 
-impl IntoToonValueInternal for i16 {
-    #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::Number(serde_toon2::Number::I64(self as i64))
+/// Wrapper around the operand of a `..expr` spread in `toon!`.
+///
+/// `Value` and `&Value` operands are handled by inherent methods, which take
+/// precedence over the [`SpreadEntries`] and [`SpreadElements`] trait methods
+/// used for arbitrary iterators.
+#[doc(hidden)]
+pub struct Spread<T>(pub T);
+
+impl Spread<serde_toon2::Value> {
+    /// Copy all entries of an object value into `map`.
+    #[track_caller]
+    pub fn extend_object(self, map: &mut serde_toon2::Map<String, serde_toon2::Value>) {
+        match self.0 {
+            serde_toon2::Value::Object(entries) => map.extend(entries),
+            other => spread_type_panic("object", &other),
+        }
+    }
+
+    /// Append all elements of an array value to `vec`.
+    #[track_caller]
+    pub fn extend_array(self, vec: &mut Vec<serde_toon2::Value>) {
+        match self.0 {
+            serde_toon2::Value::Array(elements) => vec.extend(elements),
+            other => spread_type_panic("array", &other),
+        }
     }
 }
 
-impl IntoToonValueInternal for i32 {
-    #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::Number(serde_toon2::Number::I64(self as i64))
+impl Spread<&serde_toon2::Value> {
+    /// Copy all entries of an object value into `map`.
+    #[track_caller]
+    pub fn extend_object(self, map: &mut serde_toon2::Map<String, serde_toon2::Value>) {
+        match self.0 {
+            serde_toon2::Value::Object(entries) => {
+                map.extend(entries.iter().map(|(k, v)| (k.clone(), v.clone())))
+            }
+            other => spread_type_panic("object", other),
+        }
+    }
+
+    /// Append all elements of an array value to `vec`.
+    #[track_caller]
+    pub fn extend_array(self, vec: &mut Vec<serde_toon2::Value>) {
+        match self.0 {
+            serde_toon2::Value::Array(elements) => vec.extend(elements.iter().cloned()),
+            other => spread_type_panic("array", other),
+        }
     }
 }
 
-impl IntoToonValueInternal for isize {
+#[track_caller]
+fn spread_type_panic(expected: &str, got: &serde_toon2::Value) -> ! {
+    panic!(
+        "cannot spread {} value into {} in toon!",
+        kind(got),
+        expected
+    )
+}
+
+/// Spread support for iterators of `(key, value)` pairs inside `toon!` objects.
+#[doc(hidden)]
+pub trait SpreadEntries {
+    fn extend_object(self, map: &mut serde_toon2::Map<String, serde_toon2::Value>);
+}
+
+impl<I, K, V> SpreadEntries for Spread<I>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: Into<String>,
+    V: ToToon,
+{
     #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::Number(serde_toon2::Number::I64(self as i64))
+    fn extend_object(self, map: &mut serde_toon2::Map<String, serde_toon2::Value>) {
+        map.extend(self.0.into_iter().map(|(k, v)| (k.into(), v.into_toon())));
     }
 }
 
-impl IntoToonValueInternal for u8 {
+/// Spread support for iterators of values inside `toon!` arrays.
+#[doc(hidden)]
+pub trait SpreadElements {
+    fn extend_array(self, vec: &mut Vec<serde_toon2::Value>);
+}
+
+impl<I> SpreadElements for Spread<I>
+where
+    I: IntoIterator,
+    I::Item: ToToon,
+{
     #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::Number(serde_toon2::Number::U64(self as u64))
+    fn extend_array(self, vec: &mut Vec<serde_toon2::Value>) {
+        vec.extend(self.0.into_iter().map(ToToon::into_toon));
     }
 }
 
-impl IntoToonValueInternal for u16 {
+/// Streaming writer behind `toon_format!`.
+///
+/// Object entries with static keys are written as they are evaluated; only
+/// the values that cannot be streamed are built as [`Value`]s first. The
+/// first write error is kept and returned by [`FormatWriter::finish`], and
+/// later writes are skipped.
+///
+/// [`Value`]: serde_toon2::Value
+#[doc(hidden)]
+pub struct FormatWriter<'w, W: ?Sized> {
+    encoder: Encoder<'w, W>,
+    result: fmt::Result,
+}
+
+impl<'w, W: fmt::Write + ?Sized> FormatWriter<'w, W> {
     #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::Number(serde_toon2::Number::U64(self as u64))
+    pub fn new(out: &'w mut W) -> Self {
+        FormatWriter {
+            encoder: Encoder::new(out),
+            result: Ok(()),
+        }
+    }
+
+    /// Write a complete document.
+    pub fn value(&mut self, value: &serde_toon2::Value) {
+        if self.result.is_ok() {
+            self.result = self.encoder.value(value);
+        }
+    }
+
+    /// Write one entry of the object currently being written.
+    pub fn entry(&mut self, key: &str, value: &serde_toon2::Value) {
+        if self.result.is_ok() {
+            self.result = self.encoder.entry(key, value);
+        }
+    }
+
+    /// Start a nested object; must be paired with [`FormatWriter::end_object`].
+    pub fn begin_object(&mut self, key: &str) {
+        if self.result.is_ok() {
+            self.result = self.encoder.begin_object(key);
+        }
+    }
+
+    pub fn end_object(&mut self) {
+        self.encoder.end_object();
     }
-}
 
-impl IntoToonValueInternal for u32 {
     #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::Number(serde_toon2::Number::U64(self as u64))
+    pub fn finish(self) -> fmt::Result {
+        self.result
     }
 }
 
-impl IntoToonValueInternal for usize {
+/// Panic for `toon_format!(value)`, whose `String` form cannot return the
+/// error of a layout TOON cannot represent.
+#[doc(hidden)]
+#[cold]
+#[track_caller]
+pub fn format_failed() -> ! {
+    panic!("cannot encode value in toon_format!: an array inside an array holds arrays or objects")
+}
+
+/// Destination of `toon_format!(dst, ...)`.
+///
+/// Called with method syntax so that, like `write!`, the destination may be
+/// a `String` place as well as a `&mut impl fmt::Write`.
+#[doc(hidden)]
+pub trait FormatTarget: fmt::Write {
+    fn toon_writer(&mut self) -> FormatWriter<'_, Self>;
+}
+
+impl<W: fmt::Write + ?Sized> FormatTarget for W {
     #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::Number(serde_toon2::Number::U64(self as u64))
+    fn toon_writer(&mut self) -> FormatWriter<'_, Self> {
+        FormatWriter::new(self)
     }
 }
 
-impl IntoToonValueInternal for f32 {
-    #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        serde_toon2::Value::Number(serde_toon2::Number::F64(self as f64))
+/// Match an object pattern of `toon_let!`/`toon_match!` at `path`.
+#[doc(hidden)]
+pub fn match_object<'v>(
+    value: &'v serde_toon2::Value,
+    path: &str,
+) -> Result<&'v serde_toon2::Map<String, serde_toon2::Value>> {
+    match value {
+        serde_toon2::Value::Object(map) => Ok(map),
+        other => Err(Error::InvalidType {
+            expected: "object",
+            got: format!("{} at `{}`", kind(other), path),
+        }),
     }
 }
 
-// Implement for Value itself (passthrough)
-impl IntoToonValueInternal for serde_toon2::Value {
-    #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        self
+/// Look up a key required by an object pattern; `expected` describes it.
+#[doc(hidden)]
+pub fn match_key<'v>(
+    map: &'v serde_toon2::Map<String, serde_toon2::Value>,
+    key: &str,
+    expected: &'static str,
+    path: &str,
+) -> Result<&'v serde_toon2::Value> {
+    map.get(key).ok_or_else(|| Error::InvalidType {
+        expected,
+        got: format!("object without `{}` at `{}`", key, path),
+    })
+}
+
+/// Match an array pattern with `len` elements, or at least `len` elements
+/// when the pattern has a `..` rest; `expected` describes the pattern.
+#[doc(hidden)]
+pub fn match_array<'v>(
+    value: &'v serde_toon2::Value,
+    len: usize,
+    exact: bool,
+    expected: &'static str,
+    path: &str,
+) -> Result<&'v [serde_toon2::Value]> {
+    match value {
+        serde_toon2::Value::Array(items) if items.len() == len || (!exact && items.len() > len) => {
+            Ok(items)
+        }
+        serde_toon2::Value::Array(items) => Err(Error::InvalidType {
+            expected,
+            got: format!(
+                "array of {} element{} at `{}`",
+                items.len(),
+                if items.len() == 1 { "" } else { "s" },
+                path
+            ),
+        }),
+        other => Err(Error::InvalidType {
+            expected,
+            got: format!("{} at `{}`", kind(other), path),
+        }),
     }
 }
 
-// Implement for &Value
-impl IntoToonValueInternal for &serde_toon2::Value {
-    #[inline]
-    fn into_toon_value(self) -> serde_toon2::Value {
-        self.clone()
+/// Match a literal pattern. Numbers compare by value, so `1` matches both
+/// `Number::I64(1)` and `Number::U64(1)`.
+#[doc(hidden)]
+pub fn match_literal(
+    value: &serde_toon2::Value,
+    literal: &serde_toon2::Value,
+    expected: &'static str,
+    path: &str,
+) -> Result<()> {
+    let equal = match (value, literal) {
+        (serde_toon2::Value::Number(a), serde_toon2::Value::Number(b)) => {
+            compare_numbers(a, b) == Some(Ordering::Equal)
+        }
+        _ => value == literal,
+    };
+    if equal {
+        Ok(())
+    } else {
+        Err(Error::InvalidType {
+            expected,
+            got: format!("{} at `{}`", describe(value), path),
+        })
     }
 }
 
+/// Convert a typed binding of a pattern, adding `path` to the error.
+///
+/// Goes through the deprecated [`FromToonValue`](crate::table::FromToonValue)
+/// for the same reason as [`cell_from_toon`].
+#[doc(hidden)]
+#[allow(deprecated)]
+pub fn match_bind<T: crate::table::FromToonValue>(
+    value: &serde_toon2::Value,
+    path: &str,
+) -> Result<T> {
+    T::from_toon_value(value).map_err(|err| err.at(path))
+}
+
 /// Helper function to convert any supported type to a TOON Value.
 #[doc(hidden)]
 #[inline]
-pub fn into_value<T: IntoToonValueInternal>(value: T) -> serde_toon2::Value {
-    value.into_toon_value()
+pub fn into_value<T: ToToon>(value: T) -> serde_toon2::Value {
+    value.into_toon()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_toon2_error_format() {
+        // `split_error` depends on this exact wording.
+        let err = serde_toon2::from_str::<serde_toon2::Value>("a: 1\nb").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Missing colon after key at line 2, column 1"
+        );
+        assert_eq!(
+            split_error(&err),
+            ("Missing colon after key".to_string(), Some((2, 1)))
+        );
+    }
+
+    #[test]
+    fn test_describe_parse_error() {
+        let input = "name: \"Alice\"\nage 30";
+        let err = serde_toon2::from_str::<serde_toon2::Value>(input).unwrap_err();
+        assert_eq!(
+            describe_parse_error(input, &err),
+            "invalid TOON in toon_str!: Missing colon after key\n \
+             --> input line 2, column 1\n  \
+             |\n\
+             2 | age 30\n  \
+             | ^"
+        );
+    }
+
+    #[test]
+    fn test_describe_parse_error_without_location() {
+        assert_eq!(
+            describe_parse_error("", &serde_toon2::Error::custom("bad input")),
+            "invalid TOON in toon_str!: bad input"
+        );
+    }
 }
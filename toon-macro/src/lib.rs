@@ -8,7 +8,21 @@
 //! ## Features
 //!
 //! - **`toon!` macro**: JSON-like Rust DSL for constructing TOON values
-//! - **`toon_str!` macro**: Parse TOON-format strings at runtime
+//! - **`toon_str!` macro**: Parse TOON-format strings, validated at compile time for literals
+//! - **`toon_format!` macro**: Write the `toon!` DSL straight to TOON text
+//! - **`toon_static!` macro**: Build constant values once, with pre-encoded text for literals
+//! - **`toon_let!` / `toon_match!` macros**: Destructure values by shape into typed locals
+//! - **[`from_toon_str_borrowed`]**: Zero-copy parsing into a [`ValueRef`] borrowing from the input
+//! - **`ValueExt` trait**: JSON pointer and path lookups, updates, removals and typed getters
+//! - **[`value::Query`]**: JSONPath-like selection with wildcards, slices and filters
+//! - **[`value::Patch`]**: RFC 6902 patches, alongside RFC 7396 merge patches
+//! - **[`diff()`]**: Path-addressed changes between two values, as a patch or a readable diff
+//! - **[`value::walk`]**: Path-aware visitors and in-place transforms such as pruning nulls
+//! - **[`value::ValueStats`]**: Node counts, depth and the TOON versus JSON size of a value
+//! - **[`infer_schema`]**: Field types, optional fields, enums and ranges merged from samples, as compact TOON
+//! - **[`CanonicalValue`]**: Key-order and number-form independent equality, ordering and hashing,
+//!   with canonical TOON text and a stable content hash
+//! - **`ToToon` / `FromToon` traits**: One set of conversions shared by `toon!` and tables
 //! - **`ToonTable` trait**: Encode/decode tabular data efficiently
 //! - **`#[derive(ToonTable)]`**: Automatic table serialization (requires `derive` feature)
 //!
@@ -40,18 +54,21 @@
 //!     ]
 //! });
 //!
-//! // Using variables
+//! // Using variables and arbitrary expressions
 //! let name = "Charlie";
-//! let score = 95i64;
+//! let scores = vec![95, 87];
 //! let result = toon!({
 //!     name: name,
-//!     score: score
+//!     best: scores[0],
+//!     count: scores.len(),
+//!     offset: -5
 //! });
 //! ```
 //!
 //! ### Using `toon_str!` (TOON syntax)
 //!
-//! The `toon_str!` macro parses TOON-format text at runtime:
+//! The `toon_str!` macro parses TOON-format text. String literals are
+//! validated at compile time and expand to direct `Value` construction:
 //!
 //! ```
 //! use toon_macro::toon_str;
@@ -117,20 +134,29 @@
 #![deny(unsafe_code)]
 
 // Declare modules - macros must come first so they can be used in other modules
-#[macro_use]
 pub mod internal;
 #[macro_use]
 pub mod macros;
 
+#[cfg(test)]
+mod arbitrary;
+pub mod convert;
+mod decode;
+pub mod diff;
+mod encode;
 pub mod error;
+pub mod schema;
 pub mod ser;
 pub mod table;
 pub mod value;
 
 // Re-export core types
+pub use convert::{FromToon, ToToon};
+pub use diff::diff;
 pub use error::{Error, Result};
-pub use ser::{from_toon_str, to_toon_string};
-pub use value::Value;
+pub use schema::infer_schema;
+pub use ser::{from_toon_str, from_toon_str_borrowed, to_toon_string};
+pub use value::{CanonicalValue, Value, ValueExt, ValueRef, to_canonical_toon_string};
 
 // Re-export the ToonTable trait (always available)
 // When the derive feature is enabled, the derive macro is also re-exported
@@ -145,6 +171,28 @@ pub use table::ToonTable;
 #[cfg(feature = "derive")]
 pub use toon_macro_derive::ToonTable;
 
+// Procedural implementation of `toon!`, invoked through the `macro_rules!` wrapper
+#[doc(hidden)]
+pub use toon_macro_derive::toon_impl as __toon_impl;
+
+// Compile-time implementation of `toon_str!` for string literals
+#[doc(hidden)]
+pub use toon_macro_derive::toon_str_impl as __toon_str_impl;
+
+// Streaming implementation of `toon_format!`
+#[doc(hidden)]
+pub use toon_macro_derive::toon_format_impl as __toon_format_impl;
+
+// Lazily built statics for `toon_static!`
+#[doc(hidden)]
+pub use toon_macro_derive::toon_static_impl as __toon_static_impl;
+
+// Pattern matching behind `toon_let!` and `toon_match!`
+#[doc(hidden)]
+pub use toon_macro_derive::{
+    toon_let_impl as __toon_let_impl, toon_match_impl as __toon_match_impl,
+};
+
 // Re-export serde_toon2 types that users might need
 pub use serde_toon2::{Map, Number};
 
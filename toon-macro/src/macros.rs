@@ -3,7 +3,11 @@
 //! This module provides two macro families:
 //!
 //! - `toon!` - A Rust-DSL for constructing TOON values with JSON-like syntax
-//! - `toon_str!` - Parse TOON-format text at runtime
+//! - `toon_str!` - Parse TOON-format text, at compile time for literals
+//! - `try_toon_str!` - Fallible `toon_str!` returning a `Result`
+//! - `toon_format!` - Write the `toon!` DSL directly as TOON text
+//! - `toon_static!` - Declare `toon!` values built once on first use
+//! - `toon_let!` / `toon_match!` - Destructure a `Value` by shape
 //!
 //! # Examples
 //!
@@ -56,9 +60,41 @@
 /// - **Arrays**: `toon!([value, ...])`
 /// - **Null**: `toon!(null)`
 /// - **Booleans**: `toon!(true)`, `toon!(false)`
-/// - **Numbers**: `toon!(42)`, `toon!(3.14)`
+/// - **Numbers**: `toon!(42)`, `toon!(-5)`, `toon!(3.14)`
 /// - **Strings**: `toon!("hello")`
-/// - **Variables**: `toon!(my_var)`
+/// - **Expressions**: `toon!(my_var)`, `toon!(user.name)`, `toon!(items.len())`
+///
+/// Keys may be identifiers (`name`), literals (`"kebab-key"`) or computed
+/// at runtime with `[expr]: value`, where `expr` is any `impl Into<String>`.
+/// Any Rust expression is accepted in value position; `{ ... }` and `[ ... ]`
+/// always start a nested object or array.
+///
+/// `..expr` spreads another value into the literal being built:
+///
+/// - In objects, `expr` is a `Value::Object` (owned or borrowed) or any
+///   iterator of `(key, value)` pairs. Later keys override earlier ones.
+/// - In arrays, `expr` is a `Value::Array` (owned or borrowed) or any
+///   iterator of values.
+///
+/// Entries can be left out instead of emitting `null`:
+///
+/// - `key?: expr` inserts the key only when the `Option` `expr` is `Some`.
+/// - `if cond => key: value` (or `if cond => ..expr`) inserts only when
+///   `cond` is true. The same guard works for array elements:
+///   `[if cond => value]`.
+///
+/// Value expressions may be any primitive, string (`&str`, `String`,
+/// `Cow<str>`, `char`), `Value`, `Option` (`None` becomes `null`), sequence
+/// (`Vec`, array, slice, tuple), map with string keys (`HashMap`,
+/// `BTreeMap`) or `Box`/`Rc`/`Arc` of one of these. With the `serde`
+/// feature, any other `Serialize` type is embedded through [`to_value`].
+///
+/// [`to_value`]: crate::value::to_value
+///
+/// Arrays can be built from iterators with comprehensions:
+/// `[for pat in iter => value]`, optionally filtered with
+/// `[for pat in iter if cond => value]`. Comprehensions can be mixed with
+/// other elements and nested.
 ///
 /// # Examples
 ///
@@ -88,6 +124,25 @@
 /// });
 /// ```
 ///
+/// ## Using Expressions
+///
+/// ```
+/// use toon_macro::toon;
+///
+/// struct User { name: String, age: u32 }
+///
+/// let user = User { name: "Carol".into(), age: 41 };
+/// let items = vec![1, 2, 3];
+///
+/// let obj = toon!({
+///     name: user.name.as_str(),
+///     age_next_year: user.age + 1,
+///     count: items.len(),
+///     delta: -5,
+///     ratio: items.len() as f64 / 2.0
+/// });
+/// ```
+///
 /// ## Nested Structures
 ///
 /// ```
@@ -116,79 +171,111 @@
 /// });
 /// ```
 ///
+/// ## Computed Keys
+///
+/// ```
+/// use toon_macro::toon;
+///
+/// const PREFIX: &str = "app";
+/// let i = 2;
+/// let field = String::from("owner");
+///
+/// let obj = toon!({
+///     [format!("field_{i}")]: true,
+///     [PREFIX]: "demo",
+///     [&field]: "Alice"
+/// });
+/// ```
+///
+/// ## Spreading Existing Values
+///
+/// ```
+/// use toon_macro::toon;
+///
+/// let base = toon!({ host: "localhost", port: 8080 });
+/// let server = toon!({ ..&base, port: 9090, tls: true });
+///
+/// let items = toon!([1, 2, 3]);
+/// let more = toon!([0, ..items, 4]);
+/// let doubled = toon!([..(1..=3).map(|n| n * 2)]);
+/// ```
+///
+/// ## Optional and Conditional Entries
+///
+/// ```
+/// use toon_macro::toon;
+///
+/// let nickname: Option<&str> = None;
+/// let is_admin = true;
+///
+/// let user = toon!({
+///     name: "Alice",
+///     nickname?: nickname,
+///     if is_admin => role: "admin",
+///     tags: ["user", if is_admin => "staff"]
+/// });
+/// ```
+///
+/// ## Comprehensions
+///
+/// ```
+/// use toon_macro::toon;
+///
+/// struct User { id: u64, name: String, active: bool }
+///
+/// let users = vec![
+///     User { id: 1, name: "Alice".into(), active: true },
+///     User { id: 2, name: "Bob".into(), active: false },
+/// ];
+///
+/// let data = toon!({
+///     users: [for u in &users => { id: u.id, name: &u.name }],
+///     active: [for u in &users if u.active => u.id]
+/// });
+/// ```
+///
+/// # Duplicate Keys
+///
+/// Writing the same identifier or literal key twice in one object
+/// (including `name` and `"name"`) is a compile error. Keys that are only
+/// known at runtime (computed keys and keys under `if` guards) are checked
+/// with a debug assertion instead. Keys copied in by a `..spread` may be
+/// overridden by later entries.
+///
+/// ```compile_fail
+/// use toon_macro::toon;
+///
+/// let obj = toon!({ name: "Alice", "name": "Bob" });
+/// ```
+///
+/// # Panics
+///
+/// Panics if a spread `Value` is not an object (in an object literal) or
+/// not an array (in an array literal), and, in debug builds, if two explicit
+/// entries produce the same key at runtime.
+///
+/// With the `serde` feature, also panics if an embedded value that is only
+/// `Serialize` fails to serialize: for example a map whose keys are not
+/// strings, numbers or bools, or a `Serialize` impl that returns an error.
+///
 /// [`Value`]: crate::Value
 #[macro_export]
 macro_rules! toon {
-    //
-    // === NULL ===
-    //
-    (null) => {
-        $crate::Value::Null
-    };
-
-    //
-    // === BOOLEANS ===
-    //
-    (true) => {
-        $crate::Value::Bool(true)
-    };
-    (false) => {
-        $crate::Value::Bool(false)
-    };
-
-    //
-    // === OBJECTS ===
-    //
-    // Empty object
-    ({}) => {{
-        $crate::Value::Object($crate::internal::new_map())
-    }};
-
-    // Object with key-value pairs
-    ({ $($key:tt : $value:tt),+ $(,)? }) => {{
-        let mut map = $crate::internal::new_map();
-        $(
-            $crate::internal::map_insert(
-                &mut map,
-                $crate::__toon_key_to_string!($key),
-                $crate::__toon_value!($value),
-            );
-        )+
-        $crate::Value::Object(map)
-    }};
-
-    //
-    // === ARRAYS ===
-    //
-    // Empty array
-    ([]) => {
-        $crate::Value::Array(::std::vec::Vec::new())
-    };
-
-    // Array with elements
-    ([ $($value:tt),+ $(,)? ]) => {{
-        let vec: ::std::vec::Vec<$crate::Value> = ::std::vec![
-            $( $crate::__toon_value!($value) ),+
-        ];
-        $crate::Value::Array(vec)
-    }};
-
-    //
-    // === EXPRESSIONS ===
-    //
-    // Any other expression (string literals, numbers, variables)
-    ($other:expr) => {
-        $crate::internal::into_value($other)
+    ($($tt:tt)*) => {
+        $crate::__toon_impl!($crate; $($tt)*)
     };
 }
 
-/// Parse a TOON-format string literal at runtime.
+/// Parse TOON-format text into a [`Value`].
 ///
-/// This macro accepts a string containing TOON syntax and parses it
-/// at runtime into a [`Value`]. If parsing fails, it will panic with
-/// an error message.
+/// When the argument is a string literal, the text is parsed at compile
+/// time: invalid TOON is a compile error naming the offending line, and
+/// valid TOON expands to code that builds the [`Value`] directly, with no
+/// parsing at runtime. The result is identical to calling
+/// [`from_toon_str`] on the same text.
 ///
-/// For fallible parsing, use [`from_toon_str`] directly.
+/// Any other `&str` expression is parsed at runtime, panicking if it is
+/// not valid TOON. For fallible parsing, use [`from_toon_str`] directly.
 ///
 /// # Examples
 ///
@@ -201,27 +288,307 @@ macro_rules! toon {
 /// "#);
 /// ```
 ///
+/// # Interpolation
+///
+/// Literals may contain `#{expr}` placeholders in value position. Each
+/// placeholder is replaced by a [`Value`] built from the expression, using
+/// the same conversions as [`toon!`](crate::toon). Values are inserted into
+/// the parsed structure, never into the text, so injected strings cannot
+/// change the shape of the document. Write `##{` for a literal `#{`.
+///
+/// ```
+/// use toon_macro::{toon, toon_str};
+///
+/// let name = "Mallory\nadmin: true";
+/// let tags = vec!["a", "b"];
+/// let value = toon_str!(r#"
+/// user:
+///   name: #{name}
+///   tags: #{tags}
+/// "#);
+///
+/// assert_eq!(value, toon!({ user: { name: name, tags: ["a", "b"] } }));
+/// ```
+///
+/// A placeholder must be a whole value; it cannot be part of a larger
+/// string or an object key:
+///
+/// ```compile_fail
+/// use toon_macro::toon_str;
+///
+/// let name = "Alice";
+/// let value = toon_str!(r#"greeting: "Hello #{name}""#);
+/// ```
+///
+/// Invalid literals are rejected at compile time:
+///
+/// ```compile_fail
+/// use toon_macro::toon_str;
+///
+/// let value = toon_str!("name: \"unterminated");
+/// ```
+///
 /// # Panics
 ///
-/// Panics if the input string is not valid TOON syntax.
+/// Panics if a non-literal input string is not valid TOON syntax. The
+/// panic is reported at the macro call site and names the line and column
+/// of the failure inside the input, followed by the offending line. Use
+/// [`try_toon_str!`] to handle the error instead.
 ///
 /// [`Value`]: crate::Value
 /// [`from_toon_str`]: crate::from_toon_str
+/// [`try_toon_str!`]: crate::try_toon_str
 #[macro_export]
 macro_rules! toon_str {
+    ($s:literal) => {
+        $crate::__toon_str_impl!($crate; $s)
+    };
     ($s:expr) => {
-        match $crate::from_toon_str($s) {
-            ::std::result::Result::Ok(value) => value,
-            ::std::result::Result::Err(err) => {
-                panic!("Invalid TOON in toon_str!: {}", err)
-            }
-        }
+        $crate::internal::parse_toon_str($s)
+    };
+}
+
+/// Parse TOON-format text into a [`Value`], returning an error on failure.
+///
+/// This is the fallible companion of [`toon_str!`]: it evaluates to a
+/// [`Result<Value>`](crate::Result) instead of panicking. String literals
+/// are still validated at compile time and support `#{expr}` placeholders,
+/// so only non-literal inputs can produce an `Err`.
+///
+/// # Examples
+///
+/// ```
+/// use toon_macro::{toon, try_toon_str};
+///
+/// let fixture = String::from("name: \"Alice\"");
+/// assert_eq!(try_toon_str!(&fixture).unwrap(), toon!({ name: "Alice" }));
+///
+/// let broken = String::from("name: \"Alice");
+/// assert!(try_toon_str!(&broken).is_err());
+/// ```
+///
+/// [`Value`]: crate::Value
+#[macro_export]
+macro_rules! try_toon_str {
+    ($s:literal) => {
+        ::std::result::Result::<$crate::Value, $crate::Error>::Ok(
+            $crate::__toon_str_impl!($crate; $s)
+        )
+    };
+    ($s:expr) => {
+        $crate::from_toon_str($s)
+    };
+}
+
+/// Write TOON text using the [`toon!`](crate::toon) DSL.
+///
+/// `toon_format!(value)` evaluates to a `String` and
+/// `toon_format!(dst, value)` writes into `dst`, any [`fmt::Write`]
+/// destination (a `String`, a `fmt::Formatter`, ...), evaluating to a
+/// [`fmt::Result`]. The output is byte-for-byte identical to
+/// `to_toon_string(&toon!(value))`.
+///
+/// Only objects are streamed. An object whose keys are all identifiers or
+/// literals is written entry by entry without building a [`Value`] for the
+/// object itself, and so is any such object nested in one of its entries.
+/// Every other value is built as a [`Value`] first, exactly as `toon!`
+/// would, and then encoded: the value of each entry that is not such an
+/// object, every array with all of its items, and objects with spreads or
+/// computed keys. A document that is mostly one large array therefore
+/// saves nothing over `to_toon_string(&toon!(value))`.
+///
+/// # Examples
+///
+/// ```
+/// use toon_macro::toon_format;
+///
+/// let name = "Alice";
+/// let text = toon_format!({
+///     name: name,
+///     settings: { theme: "dark", volume: 7 },
+///     tags: ["admin", "ops"]
+/// });
+/// assert_eq!(text, "name: Alice\nsettings:\n  theme: dark\n  volume: 7\ntags[2]: admin,ops");
+/// ```
+///
+/// Writing into an existing destination, e.g. from a `Display` impl:
+///
+/// ```
+/// use std::fmt;
+/// use toon_macro::toon_format;
+///
+/// struct Point { x: i64, y: i64 }
+///
+/// impl fmt::Display for Point {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         toon_format!(f, { x: self.x, y: self.y })
+///     }
+/// }
+///
+/// assert_eq!(Point { x: 1, y: 2 }.to_string(), "x: 1\ny: 2");
+/// ```
+///
+/// # Panics
+///
+/// Panics in the same cases as [`toon!`](crate::toon). `toon_format!(value)`
+/// also panics on arrays inside arrays that hold arrays or objects, such as
+/// `[[1, [2]]]`, which TOON cannot represent; `toon_format!(dst, value)`
+/// returns `Err(fmt::Error)` for those instead.
+///
+/// ```
+/// use toon_macro::toon_format;
+///
+/// let mut out = String::new();
+/// assert!(toon_format!(&mut out, { grid: [[1, [2]]] }).is_err());
+/// ```
+///
+/// [`fmt::Write`]: std::fmt::Write
+/// [`fmt::Result`]: std::fmt::Result
+/// [`Value`]: crate::Value
+#[macro_export]
+macro_rules! toon_format {
+    ($($tt:tt)*) => {
+        $crate::__toon_format_impl!($crate; $($tt)*)
+    };
+}
+
+/// Declare statics holding [`Value`]s built once, on first use.
+///
+/// Each `static NAME = value;` item, where `value` uses the
+/// [`toon!`](crate::toon) DSL, declares a
+/// `static NAME: LazyLock<Value>`. The value is built the first time it is
+/// dereferenced and shared afterwards. Attributes and visibility are kept.
+///
+/// When `value` contains only literals (no variables, calls, spreads,
+/// guards or computed keys), it is evaluated at compile time: the static
+/// is built without any conversions, and a `NAME_TOON` constant of type
+/// `&'static str` holds its TOON text, identical to
+/// `to_toon_string(&NAME)`.
+///
+/// # Examples
+///
+/// ```
+/// use toon_macro::{toon_static, to_toon_string};
+///
+/// toon_static! {
+///     /// Default server settings.
+///     pub static DEFAULT_CONFIG = {
+///         host: "localhost",
+///         port: 8080,
+///         features: ["auth", "metrics"]
+///     };
+///
+///     static GREETING = { text: format!("Hello from {}", env!("CARGO_PKG_NAME")) };
+/// }
+///
+/// assert_eq!(DEFAULT_CONFIG_TOON, "host: localhost\nport: 8080\nfeatures[2]: auth,metrics");
+/// assert_eq!(to_toon_string(&DEFAULT_CONFIG).unwrap(), DEFAULT_CONFIG_TOON);
+/// assert!(matches!(*GREETING, toon_macro::Value::Object(_)));
+/// ```
+///
+/// [`Value`]: crate::Value
+#[macro_export]
+macro_rules! toon_static {
+    ($($tt:tt)*) => {
+        $crate::__toon_static_impl!($crate; $($tt)*);
+    };
+}
+
+/// Destructure a [`Value`] by shape into local variables.
+///
+/// `toon_let!(pattern = expr)` checks that `expr` (a `Value` or `&Value`)
+/// has the shape described by `pattern` and binds the names in it, like a
+/// `let` statement. If the value does not match, the error is returned
+/// from the enclosing function with `?`; use
+/// `toon_let!(pattern = expr else { ... })` to diverge some other way,
+/// like `let ... else`.
+///
+/// # Patterns
+///
+/// - `{ key: pattern, ... }` matches an object with (at least) these keys.
+///   Keys are identifiers or string literals; `{ name }` is short for
+///   `{ name: name }`.
+/// - `[a, b]` matches an array of exactly two elements. `..` matches any
+///   number of elements (`[first, ..]`, `[.., last]`) and `rest @ ..`
+///   binds them as a `&[Value]`.
+/// - `name` binds the matched `&Value`; `name @ Type` converts it with
+///   [`FromToon`], so any type usable in `toon!` or a table works here.
+///   Prefix a name with `mut` for a mutable binding.
+/// - `"text"`, `42`, `-1.5`, `true` and `null` match equal values;
+///   numbers compare by value, and integers without a suffix may be as
+///   wide as `u64` or `i128`.
+/// - `_` matches anything.
+///
+/// # Errors
+///
+/// A mismatch is reported as [`Error::InvalidType`], whose `got` part
+/// ends with the JSONPath-like location of the mismatch, e.g.
+/// `expected i64, got string "30" at `$.user.age``. Errors other than
+/// type mismatches from a [`FromToon`] conversion are passed through,
+/// with the location added to [`Error::ConversionError`] messages.
+///
+/// # Examples
+///
+/// ```
+/// use toon_macro::{toon, toon_let, Result};
+///
+/// fn summary(value: &toon_macro::Value) -> Result<String> {
+///     toon_let!({ user: { name: name @ String, age: age @ i64 }, tags: [first, ..] } = value);
+///     Ok(format!("{name} ({age}), first tag {first:?}"))
+/// }
+///
+/// let value = toon!({ user: { name: "Alice", age: 30 }, tags: ["admin", "ops"] });
+/// assert_eq!(summary(&value).unwrap(), "Alice (30), first tag String(\"admin\")");
+///
+/// let err = summary(&toon!({ user: { name: "Bob", age: "old" }, tags: [] })).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "Invalid value type: expected i64, got string \"old\" at `$.user.age`"
+/// );
+/// ```
+///
+/// [`Value`]: crate::Value
+/// [`FromToon`]: crate::FromToon
+/// [`Error::InvalidType`]: crate::Error::InvalidType
+/// [`Error::ConversionError`]: crate::Error::ConversionError
+#[macro_export]
+macro_rules! toon_let {
+    ($($tt:tt)*) => {
+        $crate::__toon_let_impl!($crate; $($tt)*);
+    };
+}
+
+/// Match a [`Value`] against several shape patterns.
+///
+/// `toon_match!(expr, pattern => body, ...)` tries each pattern (see
+/// [`toon_let!`](crate::toon_let)) in order and evaluates the body of the
+/// first one that matches, with its names bound. The result is
+/// `Ok(body)`, or the error of the last arm if no pattern matches.
+///
+/// # Examples
+///
+/// ```
+/// use toon_macro::{toon, toon_match};
+///
+/// let shape = toon!({ kind: "rect", w: 3, h: 4.5 });
+/// let area = toon_match!(&shape,
+///     { kind: "circle", r: r @ f64 } => std::f64::consts::PI * r * r,
+///     { kind: "rect", w: w @ f64, h: h @ f64 } => w * h,
+/// );
+/// assert_eq!(area.unwrap(), 13.5);
+/// ```
+///
+/// [`Value`]: crate::Value
+#[macro_export]
+macro_rules! toon_match {
+    ($($tt:tt)*) => {
+        $crate::__toon_match_impl!($crate; $($tt)*)
     };
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Value;
+    use crate::{Number, Value};
 
     #[test]
     fn test_toon_null() {
@@ -236,6 +603,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_toon_numbers() {
         let v = toon!(42);
         assert!(matches!(v, Value::Number(_)));
@@ -397,4 +765,707 @@ mod tests {
 
         assert!(matches!(v, Value::Object(_)));
     }
+
+    #[test]
+    fn test_toon_negative_literals() {
+        assert_eq!(toon!(-5), Value::Number(Number::I64(-5)));
+
+        let v = toon!({ x: -5, y: -2.5 });
+        if let Value::Object(map) = v {
+            assert_eq!(map.get("x"), Some(&Value::Number(Number::I64(-5))));
+            assert_eq!(map.get("y"), Some(&Value::Number(Number::F64(-2.5))));
+        } else {
+            panic!("Expected object");
+        }
+    }
+
+    #[test]
+    fn test_toon_expressions() {
+        struct User {
+            name: String,
+            age: u32,
+        }
+
+        let user = User {
+            name: "Dana".to_string(),
+            age: 41,
+        };
+        let items = [1, 2, 3];
+
+        let v = toon!({
+            name: &user.name,
+            next_age: user.age + 1,
+            count: items.len(),
+            first: items[0],
+            small: items.len() as u8,
+            label: format!("{}!", user.name)
+        });
+
+        if let Value::Object(map) = v {
+            assert_eq!(map.get("name"), Some(&Value::String("Dana".to_string())));
+            assert_eq!(map.get("next_age"), Some(&Value::Number(Number::U64(42))));
+            assert_eq!(map.get("count"), Some(&Value::Number(Number::U64(3))));
+            assert_eq!(map.get("first"), Some(&Value::Number(Number::I64(1))));
+            assert_eq!(map.get("small"), Some(&Value::Number(Number::U64(3))));
+            assert_eq!(map.get("label"), Some(&Value::String("Dana!".to_string())));
+        } else {
+            panic!("Expected object");
+        }
+    }
+
+    #[test]
+    fn test_toon_expression_in_array() {
+        let base = 10i64;
+        let v = toon!([base - 1, base, base + 1, null]);
+        assert_eq!(
+            v,
+            Value::Array(vec![
+                Value::Number(Number::I64(9)),
+                Value::Number(Number::I64(10)),
+                Value::Number(Number::I64(11)),
+                Value::Null,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_toon_local_named_map() {
+        // Locals introduced by the macro must not shadow user bindings.
+        let map = "user map";
+        let v = toon!({ nested: { value: map } });
+        if let Value::Object(outer) = v {
+            let Some(Value::Object(inner)) = outer.get("nested") else {
+                panic!("Expected nested object");
+            };
+            assert_eq!(inner.get("value"), Some(&Value::String("user map".into())));
+        } else {
+            panic!("Expected object");
+        }
+    }
+
+    #[test]
+    fn test_toon_computed_keys() {
+        const PREFIX: &str = "app";
+        let field = String::from("owner");
+        let i = 3;
+
+        let v = toon!({
+            [format!("field_{i}")]: 1,
+            [PREFIX]: { [field.as_str()]: "Alice" },
+            [&field]: "Bob",
+            plain: true
+        });
+
+        if let Value::Object(map) = v {
+            let keys: Vec<_> = map.keys().map(String::as_str).collect();
+            assert_eq!(keys, ["field_3", "app", "owner", "plain"]);
+            let Some(Value::Object(inner)) = map.get("app") else {
+                panic!("Expected nested object");
+            };
+            assert_eq!(inner.get("owner"), Some(&Value::String("Alice".into())));
+        } else {
+            panic!("Expected object");
+        }
+    }
+
+    #[test]
+    fn test_toon_object_spread() {
+        let base = toon!({ host: "localhost", port: 8080, debug: false });
+
+        let v = toon!({ ..&base, port: 9090, tls: true });
+        assert_eq!(
+            v,
+            toon!({ host: "localhost", port: 9090, debug: false, tls: true })
+        );
+
+        // Later spreads override earlier keys as well.
+        let v = toon!({ port: 1, ..base });
+        if let Value::Object(map) = v {
+            assert_eq!(map.get("port"), Some(&Value::Number(Number::I64(8080))));
+            assert_eq!(map.len(), 3);
+        } else {
+            panic!("Expected object");
+        }
+    }
+
+    #[test]
+    fn test_toon_object_spread_from_pairs() {
+        let pairs = vec![("a", 1), ("b", 2)];
+        let v = toon!({ ..pairs, c: 3 });
+        assert_eq!(v, toon!({ a: 1, b: 2, c: 3 }));
+    }
+
+    #[test]
+    fn test_toon_array_spread() {
+        let items = toon!([1, 2, 3]);
+        assert_eq!(toon!([..&items, 4]), toon!([1, 2, 3, 4]));
+        assert_eq!(toon!([0, ..items]), toon!([0, 1, 2, 3]));
+
+        let names = vec!["a", "b"];
+        assert_eq!(toon!([..names, "c"]), toon!(["a", "b", "c"]));
+        assert_eq!(toon!([..(1..=3).map(|n| n * 2)]), toon!([2, 4, 6]));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot spread array value into object")]
+    fn test_toon_object_spread_type_mismatch() {
+        let items = toon!([1, 2]);
+        let _ = toon!({ ..items });
+    }
+
+    #[test]
+    fn test_toon_optional_entries() {
+        let nickname: Option<&str> = None;
+        let email = Some("alice@example.com".to_string());
+
+        let v = toon!({
+            name: "Alice",
+            nickname?: nickname,
+            email?: email,
+            age?: Some(30)
+        });
+        assert_eq!(
+            v,
+            toon!({ name: "Alice", email: "alice@example.com", age: 30 })
+        );
+    }
+
+    #[test]
+    fn test_toon_guarded_entries() {
+        let is_admin = true;
+        let extra = toon!({ debug: true });
+
+        let v = toon!({
+            name: "Alice",
+            if is_admin => role: "admin",
+            if !is_admin => role: "user",
+            if extra.as_object().is_some() => ..&extra,
+            if false => ..extra
+        });
+        assert_eq!(v, toon!({ name: "Alice", role: "admin", debug: true }));
+    }
+
+    #[test]
+    fn test_toon_guarded_array_elements() {
+        let n = 3;
+        let v = toon!([1, if n > 2 => 2, if n > 5 => 3, if n == 3 => ..[4, 5]]);
+        assert_eq!(v, toon!([1, 2, 4, 5]));
+
+        // A plain `if` expression is still accepted as a value.
+        let v = toon!([if n > 2 { "big" } else { "small" }]);
+        assert_eq!(v, toon!(["big"]));
+    }
+
+    #[test]
+    fn test_toon_comprehension() {
+        struct User {
+            id: u64,
+            name: String,
+            active: bool,
+        }
+
+        let users = vec![
+            User {
+                id: 1,
+                name: "Alice".into(),
+                active: true,
+            },
+            User {
+                id: 2,
+                name: "Bob".into(),
+                active: false,
+            },
+        ];
+
+        let v = toon!({
+            users: [for u in &users => { id: u.id, name: &u.name }],
+            active: [for u in &users if u.active => u.id]
+        });
+
+        assert_eq!(
+            v,
+            toon!({
+                users: [
+                    { id: 1u64, name: "Alice" },
+                    { id: 2u64, name: "Bob" }
+                ],
+                active: [1u64]
+            })
+        );
+    }
+
+    #[test]
+    fn test_toon_comprehension_mixed_and_nested() {
+        let v = toon!([0, for i in 1..3 => i, 9]);
+        assert_eq!(v, toon!([0, 1, 2, 9]));
+
+        let v = toon!([for (i, row) in [[1, 2], [3, 4]].iter().enumerate() => {
+            index: i,
+            cells: [for c in row if *c % 2 == 0 => *c]
+        }]);
+        assert_eq!(
+            v,
+            toon!([
+                { index: 0usize, cells: [2] },
+                { index: 1usize, cells: [4] }
+            ])
+        );
+
+        let empty: Vec<i32> = Vec::new();
+        assert_eq!(toon!([for x in empty => x]), toon!([]));
+    }
+
+    #[test]
+    fn test_toon_spread_keys_can_be_overridden() {
+        let base = toon!({ a: 1 });
+        let key = "a";
+        let v = toon!({ ..base, [key]: 2 });
+        assert_eq!(v, toon!({ a: 2 }));
+    }
+
+    #[test]
+    fn test_toon_guarded_duplicate_keys() {
+        let flag = false;
+        let v = toon!({ if flag => mode: "on", if !flag => mode: "off" });
+        assert_eq!(v, toon!({ mode: "off" }));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "duplicate key `name` in toon! object")]
+    fn test_toon_computed_key_collision() {
+        let key = "name";
+        let _ = toon!({ name: "Alice", [key]: "Bob" });
+    }
+
+    #[test]
+    fn test_toon_std_conversions() {
+        use std::borrow::Cow;
+        use std::collections::BTreeMap;
+        use std::rc::Rc;
+        use std::sync::Arc;
+
+        let scores = vec![1u8, 2, 3];
+        let mut limits = BTreeMap::new();
+        limits.insert("cpu".to_string(), 2);
+        limits.insert("mem".to_string(), 512);
+        let n = 7i32;
+
+        let v = toon!({
+            missing: Option::<i32>::None,
+            present: Some("x"),
+            scores: &scores,
+            slice: &scores[..2],
+            pair: ("a", 1),
+            initial: 'A',
+            big: 1u128 << 70,
+            exact: -5i128,
+            label: Cow::Borrowed("cow"),
+            boxed: Box::new(1.5),
+            shared: Rc::<str>::from("rc"),
+            sync: Arc::new(vec![true]),
+            by_ref: &n,
+            limits: limits
+        });
+
+        let expected = toon!({
+            missing: null,
+            present: "x",
+            scores: [1u8, 2u8, 3u8],
+            slice: [1u8, 2u8],
+            pair: ["a", 1],
+            initial: "A",
+            big: (1u128 << 70) as f64,
+            exact: -5,
+            label: "cow",
+            boxed: 1.5,
+            shared: "rc",
+            sync: [true],
+            by_ref: 7,
+            limits: { cpu: 2, mem: 512 }
+        });
+        assert_eq!(v, expected);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_toon_serialize_fallback() {
+        #[derive(serde::Serialize)]
+        struct Point {
+            x: i64,
+            y: i64,
+        }
+
+        let point = Point { x: 1, y: -2 };
+        let v = toon!({ origin: &point, points: [for p in [point] => p] });
+        assert_eq!(
+            v,
+            toon!({ origin: { x: 1, y: -2 }, points: [{ x: 1, y: -2 }] })
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    #[should_panic(expected = "cannot embed value in toon!: no TOON form")]
+    fn test_toon_serialize_error_panics() {
+        struct Opaque;
+
+        impl serde::Serialize for Opaque {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("no TOON form"))
+            }
+        }
+
+        let _ = toon!({ value: Opaque });
+    }
+
+    #[test]
+    fn test_toon_str_literal_matches_runtime() {
+        const SRC: &str = r#"
+title: "Report"
+count: 3
+ratio: 0.5
+delta: -7
+active: true
+missing: null
+tags[2]: a,b
+users[2]{id,name}:
+  1,Alice
+  2,Bob
+nested:
+  inner: "x"
+"#;
+        let compiled = toon_str!(
+            r#"
+title: "Report"
+count: 3
+ratio: 0.5
+delta: -7
+active: true
+missing: null
+tags[2]: a,b
+users[2]{id,name}:
+  1,Alice
+  2,Bob
+nested:
+  inner: "x"
+"#
+        );
+        assert_eq!(compiled, crate::from_toon_str(SRC).unwrap());
+    }
+
+    #[test]
+    fn test_toon_str_runtime_expression() {
+        let text = String::from("name: \"dynamic\"");
+        assert_eq!(toon_str!(&text), toon!({ name: "dynamic" }));
+    }
+
+    #[test]
+    fn test_toon_str_interpolation() {
+        let name = "Eve, \"quoted\"\nrole: admin";
+        let id = 7u32;
+        let rows = [(1, "x"), (2, "y")];
+        let v = toon_str!(
+            r###"
+name: #{name}
+id: #{id + 1}
+rows: #{rows}
+flags[2]: #{true},#{Option::<i32>::None}
+users[1]{id,name}:
+  #{id},#{name.len()}
+literal: "##{not interpolated}"
+"###
+        );
+
+        let expected = toon!({
+            name: name,
+            id: 8u32,
+            rows: [[1, "x"], [2, "y"]],
+            flags: [true, null],
+            users: [{ id: id, name: name.len() }],
+            literal: "#{not interpolated}"
+        });
+        assert_eq!(v, expected);
+    }
+
+    #[test]
+    fn test_toon_str_interpolation_brace_literals() {
+        let text = "a}b{";
+        let v = toon_str!(
+            r###"
+close: #{text.find('}')}
+open: #{text.contains('{')}
+raw: #{text.replace(r"}", "")}
+"###
+        );
+        assert_eq!(v, toon!({ close: 1usize, open: true, raw: "ab{" }));
+    }
+
+    #[test]
+    #[should_panic(expected = "--> input line 2, column 1")]
+    fn test_toon_str_runtime_panic_location() {
+        let text = String::from("ok: 1\nbroken");
+        let _ = toon_str!(&text);
+    }
+
+    #[test]
+    fn test_try_toon_str() {
+        let v = try_toon_str!("a: 1").unwrap();
+        assert_eq!(v, toon!({ a: 1 }));
+
+        let text = String::from("a: [2]: 1");
+        let err = try_toon_str!(text.as_str()).unwrap_err();
+        assert!(matches!(err, crate::Error::Deserialize(_)));
+    }
+
+    #[test]
+    fn test_toon_format_matches_to_toon_string() {
+        let name = "Alice, \"A\"";
+        let nickname: Option<&str> = Some("Al");
+        let missing: Option<i32> = None;
+        let admin = true;
+        let base = toon!({ host: "localhost", port: 8080 });
+        let key = "computed key";
+
+        macro_rules! assert_parity {
+            ($($tt:tt)*) => {
+                assert_eq!(
+                    toon_format!($($tt)*),
+                    crate::to_toon_string(&toon!($($tt)*)).unwrap()
+                );
+            };
+        }
+
+        assert_parity!(null);
+        assert_parity!(-1.5);
+        assert_parity!(name);
+        assert_parity!({});
+        assert_parity!([]);
+        assert_parity!([1, "two", [3, 4], { five: 5 }]);
+        assert_parity!({
+            name: name,
+            "odd key": { "": null, inner: {} },
+            empty: {},
+            nickname?: nickname,
+            missing?: missing,
+            if admin => role: "admin",
+            if !admin => role: "user",
+            numbers: [1, 2.5, -0.0, 1e21],
+            "list key": [[1, 2], ["a"]],
+            rows: [{ id: 1, "x y": "a:b" }, { "x y": "c", id: 2 }],
+            mixed: [1, { a: [1, 2], b: { c: 1 } }, [true]],
+            nested: { server: { ..&base, tls: true }, [key]: 1 },
+            last: "007"
+        });
+    }
+
+    #[test]
+    fn test_toon_format_into_writer() {
+        use std::fmt::Write;
+
+        let mut out = String::from("# header\n");
+        toon_format!(out, { a: 1, b: [1, 2] }).unwrap();
+        assert_eq!(out, "# header\na: 1\nb[2]: 1,2");
+
+        let dst: &mut String = &mut String::new();
+        toon_format!(dst, [1, 2]).unwrap();
+        writeln!(dst).unwrap();
+        assert_eq!(dst, "[2]: 1,2\n");
+    }
+
+    #[test]
+    fn test_toon_format_unrepresentable_layout() {
+        let mut out = String::new();
+        assert!(toon_format!(out, [1, [2, [3]]]).is_err());
+        assert!(toon_format!(out, { grid: [[{ a: 1 }]] }).is_err());
+
+        let result = std::panic::catch_unwind(|| toon_format!([1, [2, [3]]]));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_toon_let_binds_typed_locals() -> crate::Result<()> {
+        let value = toon!({
+            user: { name: "Alice", age: 30, "e-mail": "a@example.com" },
+            tags: ["admin", "ops", "dev"],
+            scores: [1, 2, 3],
+            active: true
+        });
+
+        toon_let!({
+            user: { name: name @ String, age: age @ u8, "e-mail": email },
+            tags: [first @ String, rest @ .., last],
+            scores: mut scores @ Vec<i64>,
+            active
+        } = &value);
+        scores.push(4);
+
+        assert_eq!(name, "Alice");
+        assert_eq!(age, 30);
+        assert_eq!(email, &toon!("a@example.com"));
+        assert_eq!(first, "admin");
+        assert_eq!(rest, &[toon!("ops")]);
+        assert_eq!(last, &toon!("dev"));
+        assert_eq!(scores, [1, 2, 3, 4]);
+        assert_eq!(active, &Value::Bool(true));
+        Ok(())
+    }
+
+    #[test]
+    fn test_toon_let_errors() {
+        fn name(value: &Value) -> crate::Result<String> {
+            toon_let!({ kind: "user", user: { name: name @ String }, ids: [_, _] } = value);
+            Ok(name)
+        }
+
+        let message = |value: Value| name(&value).unwrap_err().to_string();
+        assert_eq!(
+            message(toon!([])),
+            "Invalid value type: expected object, got array at `$`"
+        );
+        assert_eq!(
+            message(toon!({ kind: "admin" })),
+            "Invalid value type: expected `\"user\"`, got string \"admin\" at `$.kind`"
+        );
+        assert_eq!(
+            message(toon!({ kind: "user", user: {} })),
+            "Invalid value type: expected object with key `name`, got object without `name` at `$.user`"
+        );
+        assert_eq!(
+            message(toon!({ kind: "user", user: { name: 1 }, ids: [] })),
+            "Invalid value type: expected string, got number 1 at `$.user.name`"
+        );
+        assert_eq!(
+            message(toon!({ kind: "user", user: { name: "A" }, ids: [1] })),
+            "Invalid value type: expected array of 2 elements, got array of 1 element at `$.ids`"
+        );
+        assert!(matches!(
+            name(&toon!({ kind: "user", user: { name: "A" }, ids: [1, 2] })),
+            Ok(name) if name == "A"
+        ));
+
+        let err = (|| -> crate::Result<u8> {
+            toon_let!({ "big number": n @ u8 } = toon!({ "big number": 300 }));
+            Ok(n)
+        })()
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Conversion error: number is out of range for u8 at `$[\"big number\"]`"
+        );
+    }
+
+    #[test]
+    fn test_toon_let_else() {
+        let value = toon!({ n: null });
+        toon_let!({ n: null } = value else { panic!("expected null") });
+        toon_let!({ n: n @ i64 } = value else { return });
+        unreachable!("n should not convert: {}", n);
+    }
+
+    #[test]
+    fn test_toon_match_arms() {
+        let describe = |value: &Value| {
+            toon_match!(value,
+                { kind: "point" } => "point".to_string(),
+                { kind: "line", from: [x @ i64, -1], to } => format!("line from {} to {:?}", x, to),
+                [.., last @ f64] => { format!("last {}", last) }
+                [] => "empty".into(),
+            )
+        };
+
+        assert_eq!(describe(&toon!({ kind: "point" })).unwrap(), "point");
+        assert_eq!(
+            describe(&toon!({ kind: "line", from: [2, -1], to: null })).unwrap(),
+            "line from 2 to Null"
+        );
+        assert_eq!(describe(&toon!([1, 2.5])).unwrap(), "last 2.5");
+        assert_eq!(describe(&toon!([])).unwrap(), "empty");
+        assert_eq!(
+            describe(&toon!({ kind: "circle" }))
+                .unwrap_err()
+                .to_string(),
+            "Invalid value type: expected array of 0 elements, got object at `$`"
+        );
+
+        let wildcard = toon_match!(toon!(1), 1u64 => true, _ => false);
+        assert!(wildcard.unwrap());
+    }
+
+    #[test]
+    fn test_toon_match_wide_integer_literals() {
+        let sizes = |value: Value| {
+            toon_match!(value,
+                { size: 3000000000 } => "3e9",
+                { size: 18446744073709551615 } => "u64::MAX",
+                { size: -9223372036854775808 } => "i64::MIN",
+                { size: -10000000000000000000 } => "-1e19",
+                { size: 5 } => "5",
+            )
+            .unwrap()
+        };
+
+        assert_eq!(sizes(toon!({ size: 3_000_000_000u64 })), "3e9");
+        assert_eq!(sizes(toon!({ size: u64::MAX })), "u64::MAX");
+        assert_eq!(sizes(toon!({ size: i64::MIN })), "i64::MIN");
+        assert_eq!(
+            sizes(toon!({ size: -10_000_000_000_000_000_000i128 })),
+            "-1e19"
+        );
+        assert_eq!(sizes(toon!({ size: 5u8 })), "5");
+    }
+
+    #[test]
+    fn test_toon_static() {
+        toon_static! {
+            static LITERAL = {
+                name: "demo",
+                limits: { min: -1, max: 2.5, step: 1u8 },
+                tags: ["a", "b c"],
+                empty: null
+            };
+            pub(crate) static COMPUTED = { len: "abc".len(), items: [for i in 0..2 => i] };
+        }
+
+        assert_eq!(
+            *LITERAL,
+            toon!({
+                name: "demo",
+                limits: { min: -1, max: 2.5, step: 1u8 },
+                tags: ["a", "b c"],
+                empty: null
+            })
+        );
+        assert_eq!(LITERAL_TOON, crate::to_toon_string(&LITERAL).unwrap());
+        assert_eq!(*COMPUTED, toon!({ len: 3usize, items: [0, 1] }));
+        assert!(std::ptr::eq(&*COMPUTED, &*COMPUTED));
+    }
+
+    #[test]
+    fn test_toon_static_wide_integers_match_toon() {
+        toon_static! {
+            static WIDE = [
+                18446744073709551615u64,
+                18446744073709551615u128,
+                18446744073709551615i128,
+                9223372036854775808i128,
+                -9223372036854775809i128,
+                340282366920938463463374607431768211455u128,
+                -170141183460469231731687303715884105728i128
+            ];
+        }
+
+        let expected = toon!([
+            18446744073709551615u64,
+            18446744073709551615u128,
+            18446744073709551615i128,
+            9223372036854775808i128,
+            -9223372036854775809i128,
+            340282366920938463463374607431768211455u128,
+            -170141183460469231731687303715884105728i128
+        ]);
+        // `Number` equality is derived, so this also compares the variants.
+        assert_eq!(*WIDE, expected);
+        assert_eq!(WIDE_TOON, crate::to_toon_string(&expected).unwrap());
+    }
 }
@@ -0,0 +1,464 @@
+//! Structural schemas inferred from sample values.
+//!
+//! [`infer_schema`] merges sample documents into a [`Schema`] recording,
+//! at every position, which kinds of value were seen: numbers with their
+//! range, strings with their distinct values while there are few of them,
+//! arrays with the schema of their elements and objects with their fields,
+//! each marked required when every sample object had it.
+//!
+//! A schema renders as compact TOON, to describe the shape of the data in a
+//! prompt. Each field maps to its type, arrays show a single element
+//! standing for all of them, and a `?` after a key marks a field that may be
+//! missing or null:
+//!
+//! ```text
+//! id: integer 1..3
+//! role: enum(admin|user)
+//! tags[1]: string
+//! "email?": string
+//! ```
+//!
+//! A position that held several kinds of value lists them, separated by
+//! `|`, with arrays written inline as `[element]` and objects as
+//! `{key: field}`. Schemas nesting arrays deeper than TOON can lay out are
+//! written on one line the same way.
+
+use std::fmt;
+
+use crate::Value;
+use crate::convert::ToToon;
+use crate::encode::{Encoder, write_key, write_number};
+use crate::value::{Map, Number, compare_numbers};
+
+/// Strings are no longer treated as enum-like once more than this many
+/// distinct values were seen.
+pub const MAX_ENUM_VALUES: usize = 10;
+
+/// The shape of a set of sample values, as built by [`infer_schema`].
+///
+/// A schema is a union: each field records one kind of value, and is set
+/// when at least one sample of that kind was merged.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Schema {
+    /// Whether a sample was `null`.
+    pub null: bool,
+    /// Whether a sample was a boolean.
+    pub boolean: bool,
+    /// The numbers seen, if any.
+    pub number: Option<NumberSchema>,
+    /// The strings seen, if any.
+    pub string: Option<StringSchema>,
+    /// The schema of the elements of the arrays seen, if any.
+    pub array: Option<Box<Schema>>,
+    /// The fields of the objects seen, if any, in order of first appearance.
+    pub object: Option<Map<String, FieldSchema>>,
+}
+
+/// The numbers seen at one position of a [`Schema`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberSchema {
+    /// Whether every number was an integer rather than a float.
+    pub integer: bool,
+    /// The smallest number seen.
+    pub min: Number,
+    /// The largest number seen.
+    pub max: Number,
+}
+
+/// The strings seen at one position of a [`Schema`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringSchema {
+    /// The distinct strings, in order of first appearance, or `None` once
+    /// there were more than [`MAX_ENUM_VALUES`].
+    pub values: Option<Vec<String>>,
+    /// How many strings were seen.
+    pub count: usize,
+}
+
+/// One field of an object [`Schema`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSchema {
+    /// The schema of the field's values.
+    pub schema: Schema,
+    /// Whether every object seen had the field.
+    pub required: bool,
+}
+
+/// Infer the schema shared by `samples`.
+///
+/// An empty slice gives an empty schema, which renders as `any`.
+///
+/// # Example
+///
+/// ```
+/// use toon_macro::{infer_schema, toon};
+///
+/// let samples = [
+///     toon!({ id: 1, role: "admin", tags: ["a"], score: 9.5 }),
+///     toon!({ id: 2, role: "user", tags: [], email: null }),
+///     toon!({ id: 3, role: "admin", tags: ["b", "c"], score: 7, email: "c@x.io" }),
+/// ];
+/// let schema = infer_schema(&samples);
+///
+/// let fields = schema.object.as_ref().unwrap();
+/// assert!(fields["id"].required);
+/// assert!(!fields["email"].required);
+/// assert_eq!(
+///     schema.to_string(),
+///     "id: integer 1..3\n\
+///      role: enum(admin|user)\n\
+///      tags[1]: string\n\
+///      \"score?\": number 7..9.5\n\
+///      \"email?\": string"
+/// );
+/// ```
+pub fn infer_schema(samples: &[Value]) -> Schema {
+    let mut schema = Schema::default();
+    for sample in samples {
+        schema.merge(sample);
+    }
+    schema
+}
+
+impl Schema {
+    /// Widen this schema to cover `value` as well.
+    pub fn merge(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.null = true,
+            Value::Bool(_) => self.boolean = true,
+            Value::Number(n) => match &mut self.number {
+                Some(number) => number.merge(n),
+                None => {
+                    self.number = Some(NumberSchema {
+                        integer: !matches!(n, Number::F64(_)),
+                        min: n.clone(),
+                        max: n.clone(),
+                    })
+                }
+            },
+            Value::String(s) => self
+                .string
+                .get_or_insert_with(|| StringSchema {
+                    values: Some(Vec::new()),
+                    count: 0,
+                })
+                .merge(s),
+            Value::Array(items) => {
+                let schema = self.array.get_or_insert_with(Box::default);
+                for item in items {
+                    schema.merge(item);
+                }
+            }
+            Value::Object(map) => {
+                let first = self.object.is_none();
+                let fields = self.object.get_or_insert_with(Map::new);
+                for (key, field) in fields.iter_mut() {
+                    if !map.contains_key(key) {
+                        field.required = false;
+                    }
+                }
+                for (key, value) in map {
+                    fields
+                        .entry(key.clone())
+                        .or_insert_with(|| FieldSchema {
+                            schema: Schema::default(),
+                            required: first,
+                        })
+                        .schema
+                        .merge(value);
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if no sample was merged.
+    pub fn is_empty(&self) -> bool {
+        *self == Schema::default()
+    }
+
+    /// Render this schema as a value. `null` is left out of the type when
+    /// `nullable` is false, for fields whose key carries a `?` instead.
+    fn render(&self, nullable: bool) -> Value {
+        let primitives = self.boolean || self.number.is_some() || self.string.is_some();
+        let others = self.null && nullable || primitives;
+        match (&self.array, &self.object) {
+            (Some(items), None) if !others => Value::Array(vec![items.render(true)]),
+            (None, Some(fields)) if !others => Value::Object(
+                fields
+                    .iter()
+                    .map(|(key, field)| (field.key(key), field.schema.render(false)))
+                    .collect(),
+            ),
+            // A plain `null` rather than a string, which would be quoted.
+            (None, None) if !primitives && self.null => Value::Null,
+            _ => Value::String(self.inline(nullable)),
+        }
+    }
+
+    /// Render this schema on one line: its kinds separated by ` | `, with
+    /// arrays as `[element]` and objects as `{key: field}`.
+    fn inline(&self, nullable: bool) -> String {
+        let mut kinds = Vec::new();
+        if self.boolean {
+            kinds.push("boolean".to_string());
+        }
+        if let Some(number) = &self.number {
+            kinds.push(number.to_string());
+        }
+        if let Some(string) = &self.string {
+            kinds.push(string.to_string());
+        }
+        if let Some(items) = &self.array {
+            kinds.push(format!("[{}]", items.inline(true)));
+        }
+        if let Some(fields) = &self.object {
+            let mut object = String::from("{");
+            for (i, (key, field)) in fields.iter().enumerate() {
+                if i > 0 {
+                    object.push_str(", ");
+                }
+                // Writing to a `String` cannot fail.
+                let _ = write_key(&mut object, &field.key(key));
+                object.push_str(": ");
+                object.push_str(&field.schema.inline(false));
+            }
+            object.push('}');
+            kinds.push(object);
+        }
+        if kinds.is_empty() {
+            return if self.null { "null" } else { "any" }.to_string();
+        }
+        if self.null && nullable {
+            kinds.push("null".to_string());
+        }
+        kinds.join(" | ")
+    }
+}
+
+impl FieldSchema {
+    /// The key of this field in a rendered schema: `key?` for a field that
+    /// may be missing or null.
+    fn key(&self, key: &str) -> String {
+        if self.required && !self.schema.null {
+            key.to_string()
+        } else {
+            format!("{}?", key)
+        }
+    }
+}
+
+impl NumberSchema {
+    fn merge(&mut self, n: &Number) {
+        self.integer &= !matches!(n, Number::F64(_));
+        if compare_numbers(n, &self.min).is_some_and(|o| o.is_lt()) {
+            self.min = n.clone();
+        }
+        if compare_numbers(n, &self.max).is_some_and(|o| o.is_gt()) {
+            self.max = n.clone();
+        }
+    }
+}
+
+impl fmt::Display for NumberSchema {
+    /// Writes `integer 1..42`, `number 0.5..2`, or `integer 7` when every
+    /// number was the same.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.integer { "integer " } else { "number " })?;
+        write_number(f, &self.min)?;
+        if compare_numbers(&self.min, &self.max).is_none_or(|o| o.is_ne()) {
+            f.write_str("..")?;
+            write_number(f, &self.max)?;
+        }
+        Ok(())
+    }
+}
+
+impl StringSchema {
+    fn merge(&mut self, s: &str) {
+        self.count += 1;
+        if let Some(values) = &mut self.values
+            && !values.iter().any(|value| value == s)
+        {
+            if values.len() == MAX_ENUM_VALUES {
+                self.values = None;
+            } else {
+                values.push(s.to_string());
+            }
+        }
+    }
+
+    /// Returns the distinct values if the strings look like an enum: at
+    /// most [`MAX_ENUM_VALUES`] of them, with at least one repeated.
+    pub fn enum_values(&self) -> Option<&[String]> {
+        self.values
+            .as_deref()
+            .filter(|values| values.len() < self.count)
+    }
+}
+
+impl fmt::Display for StringSchema {
+    /// Writes `enum(a|b|c)` for enum-like strings and `string` otherwise.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.enum_values() {
+            Some(values) => write!(f, "enum({})", values.join("|")),
+            None => f.write_str("string"),
+        }
+    }
+}
+
+impl ToToon for Schema {
+    fn to_toon(&self) -> Value {
+        self.render(true)
+    }
+}
+
+impl fmt::Display for Schema {
+    /// Writes the schema as compact TOON, or on one line if it nests
+    /// arrays too deeply for TOON to lay it out.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_toon();
+        let mut text = String::new();
+        if Encoder::new(&mut text)
+            .quote_header_keys()
+            .value(&value)
+            .is_err()
+        {
+            text.clear();
+            Encoder::new(&mut text).inline_value(&value)?;
+        }
+        f.write_str(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(samples: &[Value]) -> String {
+        infer_schema(samples).to_string()
+    }
+
+    #[test]
+    fn test_primitives() {
+        assert_eq!(render(&[]), "any");
+        assert!(infer_schema(&[]).is_empty());
+        assert_eq!(render(&[toon!(null)]), "null");
+        assert_eq!(render(&[toon!(true), toon!(false)]), "boolean");
+        assert_eq!(render(&[toon!(3), toon!(-2), toon!(10)]), "integer -2..10");
+        assert_eq!(render(&[toon!(1), toon!(2.5)]), "number 1..2.5");
+        assert_eq!(render(&[toon!(7), toon!(7u64)]), "integer 7");
+        assert_eq!(render(&[toon!("a"), toon!("b")]), "string");
+        assert_eq!(
+            render(&[toon!(1), toon!("x"), toon!(null)]),
+            "integer 1 | string | null"
+        );
+    }
+
+    #[test]
+    fn test_enum_strings() {
+        let samples: Vec<Value> = ["red", "green", "red", "blue"].map(|s| toon!(s)).into();
+        let schema = infer_schema(&samples);
+        let string = schema.string.as_ref().unwrap();
+        assert_eq!(string.count, 4);
+        assert_eq!(string.enum_values().unwrap(), ["red", "green", "blue"]);
+        assert_eq!(schema.to_string(), "enum(red|green|blue)");
+
+        let many: Vec<Value> = (0..=MAX_ENUM_VALUES)
+            .chain(0..=MAX_ENUM_VALUES)
+            .map(|i| Value::String(format!("v{}", i)))
+            .collect();
+        let schema = infer_schema(&many);
+        assert_eq!(schema.string.as_ref().unwrap().values, None);
+        assert_eq!(schema.to_string(), "string");
+    }
+
+    #[test]
+    fn test_required_and_optional_fields() {
+        let schema = infer_schema(&[
+            toon!({ a: 1, b: "x" }),
+            toon!({ a: 2, c: true }),
+            toon!({ a: 3, b: "y", c: null }),
+        ]);
+        let fields = schema.object.as_ref().unwrap();
+        assert_eq!(fields.keys().collect::<Vec<_>>(), ["a", "b", "c"]);
+        assert!(fields["a"].required);
+        assert!(!fields["b"].required);
+        assert!(!fields["c"].required);
+        assert!(fields["c"].schema.null);
+
+        // Present everywhere but sometimes null is still marked with `?`.
+        let schema = infer_schema(&[toon!({ a: 1, n: null }), toon!({ a: 2, n: 5 })]);
+        assert!(schema.object.as_ref().unwrap()["n"].required);
+        assert_eq!(schema.to_string(), "a: integer 1..2\n\"n?\": integer 5");
+    }
+
+    #[test]
+    fn test_arrays_and_nesting() {
+        let schema = infer_schema(&[
+            toon!({
+                user: { name: "a", address: { city: "x" } },
+                orders: [{ id: 1, total: 9.5 }, { id: 2, total: 3 }],
+                matrix: [[1, 2], [3]]
+            }),
+            toon!({
+                user: { name: "b", address: null },
+                orders: [{ id: 3, total: 4, note: "gift" }],
+                matrix: []
+            }),
+        ]);
+        assert_eq!(
+            schema.to_string(),
+            "user:\n  name: string\n  \"address?\":\n    city: string\n\
+             orders[1]{id,total,\"note?\"}:\n  integer 1..3,number 3..9.5,string\n\
+             matrix[1]:\n  - [1]: integer 1..3"
+        );
+
+        let items = schema.object.as_ref().unwrap()["orders"]
+            .schema
+            .array
+            .as_ref()
+            .unwrap();
+        assert!(items.object.as_ref().unwrap()["id"].required);
+        assert!(!items.object.as_ref().unwrap()["note"].required);
+
+        assert_eq!(render(&[toon!([])]), "[1]: any");
+        assert_eq!(render(&[toon!([1, null])]), "[1]: integer 1 | null");
+    }
+
+    #[test]
+    fn test_unions_show_nested_schemas() {
+        assert_eq!(
+            render(&[toon!({ v: [1] }), toon!({ v: { a: 1 } }), toon!({ v: "x" })]),
+            "v: \"string | [integer 1] | {a: integer 1}\""
+        );
+        assert_eq!(render(&[toon!([1]), toon!(null)]), "\"[integer 1] | null\"");
+        assert_eq!(
+            render(&[
+                toon!({ a: [[1, [2]], "x"], b: { c: [{ d: 1 }, 2] } }),
+                toon!({ a: 1, e: ["p", "q"] }),
+            ]),
+            "a: \"integer 1 | [string | [integer 1 | [integer 2]]]\"\n\
+             \"b?\":\n  c[1]: \"integer 2 | {d: integer 1}\"\n\
+             \"e?\"[1]: string"
+        );
+        assert_eq!(
+            render(&[toon!({ a: 1 }), toon!({ a: { b: 2 }, c: [true] })]),
+            "a: \"integer 1 | {b: integer 2}\"\n\"c?\"[1]: boolean"
+        );
+    }
+
+    #[test]
+    fn test_deeply_nested_arrays_render_inline() {
+        assert_eq!(
+            render(&[toon!({ x: [[[{ a: 1, b: null }, { a: 2 }]]] })]),
+            "{x: [[[{a: integer 1..2, \"b?\": null}]]]}"
+        );
+        assert_eq!(render(&[toon!([[[1]]])]), "[[[integer 1]]]");
+    }
+
+    #[test]
+    fn test_to_toon() {
+        let schema = infer_schema(&[toon!({ id: 1, ok: true })]);
+        assert_eq!(schema.to_toon(), toon!({ id: "integer 1", ok: "boolean" }));
+    }
+}
@@ -3,7 +3,7 @@
 //! This module provides functions for parsing TOON text and converting
 //! between TOON values and strings.
 
-use crate::{Error, Result, Value};
+use crate::{Error, Result, Value, ValueRef};
 
 /// Parse a TOON-format string into a [`Value`].
 ///
@@ -32,6 +32,50 @@ pub fn from_toon_str(s: &str) -> Result<Value> {
     serde_toon2::from_str(s).map_err(|e| Error::Deserialize(e.to_string()))
 }
 
+/// Parse a TOON-format string into a [`ValueRef`] that borrows its keys and
+/// strings from `s`.
+///
+/// Only strings written with escape sequences are copied, so this is the
+/// cheaper choice for reading a few fields out of a large document. Call
+/// [`ValueRef::into_owned`] for a [`Value`].
+///
+/// This is a separate parser from [`from_toon_str`]. Both give the same
+/// value for the documents [`to_toon_string`] writes, with these exceptions:
+///
+/// - Quoted keys with escapes, `:` or non-ASCII characters, and unquoted
+///   non-ASCII keys, are read as written here; `from_toon_str` misreads
+///   some of them and panics on others.
+/// - Arrays in the second and later fields of a list item object, one level
+///   below the field, are read here; `from_toon_str` reports missing rows
+///   or items.
+/// - Lines indented deeper than their context, or following a root array,
+///   are an error here; `from_toon_str` silently drops them.
+/// - The text after `key: ` is always a primitive, so `a: [draft]` and
+///   `a: x[2]: y` hold strings here; `from_toon_str` looks for an array
+///   header anywhere in the line and reports an error.
+/// - Malformed headers such as `tags[2: a,b` or `[0]]:` are an error here;
+///   `from_toon_str` reads them as keys or ignores the extra text.
+///
+/// # Examples
+///
+/// ```
+/// use toon_macro::from_toon_str_borrowed;
+///
+/// let response = "status: ok\nitems[3]{id,title}:\n  1,First\n  2,Second\n  3,Third";
+/// let value = from_toon_str_borrowed(response).unwrap();
+/// let items = value.as_object().unwrap()["items"].as_array().unwrap();
+/// assert_eq!(items[1].as_object().unwrap()["title"].as_str(), Some("Second"));
+/// ```
+///
+/// # Errors
+///
+/// Returns an [`Error::Deserialize`] if the input is not valid TOON syntax.
+///
+/// [`Error::Deserialize`]: crate::Error::Deserialize
+pub fn from_toon_str_borrowed(s: &str) -> Result<ValueRef<'_>> {
+    crate::decode::parse(s)
+}
+
 /// Serialize a [`Value`] to a TOON string.
 ///
 /// # Examples
@@ -152,7 +196,6 @@ pub fn deserialize<'a, T: serde::Deserialize<'a>>(s: &'a str) -> Result<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::toon;
 
     #[test]
     fn test_from_toon_str_simple() {
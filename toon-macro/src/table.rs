@@ -30,7 +30,7 @@
 //! let decoded: Vec<User> = User::from_toon_table(&table_value).unwrap();
 //! ```
 
-use crate::{Error, Result, Value};
+use crate::{Error, FromToon, Result, ToToon, Value};
 
 /// A trait for types that can be encoded as TOON tables.
 ///
@@ -199,137 +199,53 @@ pub fn get_cell(row: &Value, index: usize) -> Result<&Value> {
 }
 
 /// Helper to convert a Value to a specific type.
+///
+/// Superseded by [`FromToon`], which every type implementing this trait now
+/// gets through a blanket implementation.
+#[deprecated(since = "0.2.0", note = "use `toon_macro::FromToon` instead")]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be converted from a TOON value",
+    note = "implement `toon_macro::FromToon` for `{Self}`"
+)]
 pub trait FromToonValue: Sized {
     /// Convert a TOON value to this type.
     fn from_toon_value(value: &Value) -> Result<Self>;
 }
 
-impl FromToonValue for String {
-    fn from_toon_value(value: &Value) -> Result<Self> {
-        match value {
-            Value::String(s) => Ok(s.clone()),
-            Value::Null => Ok(String::new()),
-            _ => Err(Error::invalid_type("string", value)),
-        }
-    }
-}
-
-impl FromToonValue for i64 {
-    fn from_toon_value(value: &Value) -> Result<Self> {
-        match value {
-            Value::Number(n) => n
-                .as_i64()
-                .ok_or_else(|| Error::ConversionError("number is not an i64".into())),
-            _ => Err(Error::invalid_type("i64", value)),
-        }
-    }
-}
-
-impl FromToonValue for u64 {
+#[allow(deprecated)]
+impl<T: FromToon> FromToonValue for T {
+    #[inline]
     fn from_toon_value(value: &Value) -> Result<Self> {
-        match value {
-            Value::Number(n) => n
-                .as_u64()
-                .ok_or_else(|| Error::ConversionError("number is not a u64".into())),
-            _ => Err(Error::invalid_type("u64", value)),
-        }
-    }
-}
-
-impl FromToonValue for f64 {
-    fn from_toon_value(value: &Value) -> Result<Self> {
-        match value {
-            Value::Number(n) => Ok(n.as_f64()),
-            _ => Err(Error::invalid_type("f64", value)),
-        }
-    }
-}
-
-impl FromToonValue for bool {
-    fn from_toon_value(value: &Value) -> Result<Self> {
-        match value {
-            Value::Bool(b) => Ok(*b),
-            _ => Err(Error::invalid_type("bool", value)),
-        }
-    }
-}
-
-impl<T: FromToonValue> FromToonValue for Option<T> {
-    fn from_toon_value(value: &Value) -> Result<Self> {
-        match value {
-            Value::Null => Ok(None),
-            _ => T::from_toon_value(value).map(Some),
-        }
+        T::from_toon(value)
     }
 }
 
 /// Helper to convert a type to a TOON Value for table cells.
+///
+/// Superseded by [`ToToon`], which every type implementing this trait now
+/// gets through a blanket implementation.
+#[deprecated(since = "0.2.0", note = "use `toon_macro::ToToon` instead")]
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be converted to a TOON value",
+    note = "implement `toon_macro::ToToon` for `{Self}`"
+)]
 pub trait IntoToonValue {
     /// Convert this value to a TOON Value.
     fn to_toon_value(&self) -> Value;
 }
 
-impl IntoToonValue for String {
+#[allow(deprecated)]
+impl<T: ToToon + ?Sized> IntoToonValue for T {
+    #[inline]
     fn to_toon_value(&self) -> Value {
-        Value::String(self.clone())
-    }
-}
-
-impl IntoToonValue for &str {
-    fn to_toon_value(&self) -> Value {
-        Value::String((*self).to_string())
-    }
-}
-
-impl IntoToonValue for i64 {
-    fn to_toon_value(&self) -> Value {
-        Value::from(*self)
-    }
-}
-
-impl IntoToonValue for u64 {
-    fn to_toon_value(&self) -> Value {
-        Value::from(*self)
-    }
-}
-
-impl IntoToonValue for i32 {
-    fn to_toon_value(&self) -> Value {
-        Value::from(*self as i64)
-    }
-}
-
-impl IntoToonValue for u32 {
-    fn to_toon_value(&self) -> Value {
-        Value::from(*self as u64)
-    }
-}
-
-impl IntoToonValue for f64 {
-    fn to_toon_value(&self) -> Value {
-        Value::from(*self)
-    }
-}
-
-impl IntoToonValue for bool {
-    fn to_toon_value(&self) -> Value {
-        Value::Bool(*self)
-    }
-}
-
-impl<T: IntoToonValue> IntoToonValue for Option<T> {
-    fn to_toon_value(&self) -> Value {
-        match self {
-            Some(v) => v.to_toon_value(),
-            None => Value::Null,
-        }
+        self.to_toon()
     }
 }
 
 #[cfg(test)]
+#[allow(deprecated)]
 mod tests {
     use super::*;
-    use crate::toon;
 
     #[test]
     fn test_extract_columns() {
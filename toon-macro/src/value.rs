@@ -7,97 +7,127 @@ pub use serde_toon2::Map;
 pub use serde_toon2::Number;
 pub use serde_toon2::Value;
 
+use std::cmp::Ordering;
+
+use crate::ToToon;
+
+mod borrowed;
+mod canonical;
+#[cfg(feature = "serde")]
+mod de;
+mod patch;
+mod path;
+mod query;
+#[cfg(feature = "serde")]
+mod ser;
+mod stats;
+mod visit;
+
+pub use borrowed::{ValueIndex, ValueRef};
+pub use canonical::{CanonicalValue, content_hash, to_canonical_toon_string};
+pub use patch::{Patch, PatchOperation};
+pub use path::ValueExt;
+pub(crate) use path::to_pointer;
+pub use query::Query;
+pub use stats::ValueStats;
+pub use visit::{
+    Visitor, VisitorMut, Walk, map_numbers, map_strings, prune_empty, prune_nulls, retain_keys,
+    sort_keys, walk, walk_mut,
+};
+
+#[cfg(feature = "serde")]
+pub use de::{Deserializer, OwnedDeserializer};
+#[cfg(feature = "serde")]
+pub use ser::Serializer;
+
 /// Extension trait for constructing Value from additional types.
 ///
-/// This provides `into_value()` methods for types not covered by serde_toon2's
-/// built-in `From` implementations.
+/// Superseded by [`ToToon`], which every type implementing this trait now
+/// gets through a blanket implementation.
+#[deprecated(since = "0.2.0", note = "use `toon_macro::ToToon` instead")]
 pub trait IntoValue {
     /// Convert this value into a TOON [`Value`].
     fn into_value(self) -> Value;
 }
 
-impl IntoValue for i8 {
+#[allow(deprecated)]
+impl<T: ToToon> IntoValue for T {
     #[inline]
     fn into_value(self) -> Value {
-        Value::Number(Number::I64(self as i64))
+        self.into_toon()
     }
 }
 
-impl IntoValue for i16 {
-    #[inline]
-    fn into_value(self) -> Value {
-        Value::Number(Number::I64(self as i64))
+/// Short name of the type of `value`, for messages.
+pub(crate) fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
     }
 }
 
-impl IntoValue for i32 {
-    #[inline]
-    fn into_value(self) -> Value {
-        Value::Number(Number::I64(self as i64))
-    }
-}
-
-impl IntoValue for isize {
-    #[inline]
-    fn into_value(self) -> Value {
-        Value::Number(Number::I64(self as i64))
+/// Short description of `value` for error messages: its type, with the
+/// value itself for scalars and the size for containers.
+pub(crate) fn describe(value: &Value) -> String {
+    const MAX_CHARS: usize = 40;
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => format!("bool {}", b),
+        Value::Number(Number::I64(n)) => format!("number {}", n),
+        Value::Number(Number::U64(n)) => format!("number {}", n),
+        Value::Number(Number::F64(n)) => format!("number {}", n),
+        Value::String(s) if s.chars().count() <= MAX_CHARS => format!("string {:?}", s),
+        Value::String(s) => format!("string of {} characters", s.chars().count()),
+        Value::Array(items) => format!("array of {} element{}", items.len(), plural(items.len())),
+        Value::Object(map) => format!("object with {} key{}", map.len(), plural(map.len())),
     }
 }
 
-impl IntoValue for u8 {
-    #[inline]
-    fn into_value(self) -> Value {
-        Value::Number(Number::U64(self as u64))
-    }
-}
-
-impl IntoValue for u16 {
-    #[inline]
-    fn into_value(self) -> Value {
-        Value::Number(Number::U64(self as u64))
-    }
-}
-
-impl IntoValue for u32 {
-    #[inline]
-    fn into_value(self) -> Value {
-        Value::Number(Number::U64(self as u64))
-    }
-}
-
-impl IntoValue for usize {
-    #[inline]
-    fn into_value(self) -> Value {
-        Value::Number(Number::U64(self as u64))
+/// Compare two numbers by value, so `I64(1)` equals `U64(1)`. Integers
+/// compare exactly; a float on either side compares as `f64`, and `NaN` is
+/// unordered.
+pub(crate) fn compare_numbers(a: &Number, b: &Number) -> Option<Ordering> {
+    fn integer(n: &Number) -> Option<i128> {
+        match n {
+            Number::I64(n) => Some(*n as i128),
+            Number::U64(n) => Some(*n as i128),
+            Number::F64(_) => None,
+        }
     }
-}
-
-impl IntoValue for f32 {
-    #[inline]
-    fn into_value(self) -> Value {
-        Value::Number(Number::F64(self as f64))
+    match (integer(a), integer(b)) {
+        (Some(a), Some(b)) => Some(a.cmp(&b)),
+        _ => a.as_f64().partial_cmp(&b.as_f64()),
     }
 }
 
-impl<T: Into<Value>> IntoValue for Option<T> {
-    #[inline]
-    fn into_value(self) -> Value {
-        match self {
-            Some(v) => v.into(),
-            None => Value::Null,
+/// Structural equality with numbers compared by value, as
+/// [`compare_numbers`] does; object key order is ignored.
+pub(crate) fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => compare_numbers(a, b) == Some(Ordering::Equal),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| values_equal(a, b))
         }
-    }
-}
-
-impl<T: Into<Value> + Clone, const N: usize> IntoValue for [T; N] {
-    #[inline]
-    fn into_value(self) -> Value {
-        Value::Array(self.into_iter().map(Into::into).collect())
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| values_equal(a, b)))
+        }
+        _ => a == b,
     }
 }
 
 /// Convert any serializable type to a TOON [`Value`].
 ///
+/// The value is built directly by [`Serializer`], without going through
+/// TOON text, so number types (`2.0` stays a float) and string contents
+/// are preserved exactly.
+///
 /// # Example
 ///
 /// ```
@@ -115,13 +145,14 @@ impl<T: Into<Value> + Clone, const N: usize> IntoValue for [T; N] {
 /// ```
 #[cfg(feature = "serde")]
 pub fn to_value<T: serde::Serialize>(value: &T) -> Result<Value, serde_toon2::Error> {
-    // Serialize to TOON string then parse back to Value
-    let s = serde_toon2::to_string(value)?;
-    serde_toon2::from_str(&s)
+    value.serialize(Serializer)
 }
 
 /// Convert a TOON [`Value`] to any deserializable type.
 ///
+/// The value is read directly by [`Deserializer`], without going through
+/// TOON text; `T` may borrow strings from `value`.
+///
 /// # Example
 ///
 /// ```
@@ -139,12 +170,14 @@ pub fn to_value<T: serde::Serialize>(value: &T) -> Result<Value, serde_toon2::Er
 /// assert_eq!(point, Point { x: 10, y: 20 });
 /// ```
 #[cfg(feature = "serde")]
-pub fn from_value<T: serde::de::DeserializeOwned>(value: &Value) -> Result<T, serde_toon2::Error> {
-    let s = serde_toon2::to_string(value)?;
-    serde_toon2::from_str(&s)
+pub fn from_value<'de, T: serde::Deserialize<'de>>(
+    value: &'de Value,
+) -> Result<T, serde_toon2::Error> {
+    T::deserialize(Deserializer::new(value))
 }
 
 #[cfg(test)]
+#[allow(deprecated)]
 mod tests {
     use super::*;
 
@@ -195,4 +228,106 @@ mod tests {
         let v: Value = vec![1i64, 2, 3].into();
         assert!(matches!(v, Value::Array(_)));
     }
+
+    #[cfg(feature = "serde")]
+    mod serde_impls {
+        use super::super::*;
+        use serde::{Deserialize, Serialize};
+        use std::collections::BTreeMap;
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        enum Shape {
+            Empty,
+            Circle(f64),
+            Line(i32, i32),
+            Rect { w: u32, h: u32 },
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Drawing {
+            name: String,
+            scale: f64,
+            shapes: Vec<Shape>,
+            layers: BTreeMap<u8, bool>,
+            note: Option<String>,
+            id: i128,
+        }
+
+        fn drawing() -> Drawing {
+            Drawing {
+                name: "plan: \"A\"".into(),
+                scale: 2.0,
+                shapes: vec![
+                    Shape::Empty,
+                    Shape::Circle(0.5),
+                    Shape::Line(-1, 1),
+                    Shape::Rect { w: 3, h: 4 },
+                ],
+                layers: BTreeMap::from([(1, true), (2, false)]),
+                note: None,
+                id: 7,
+            }
+        }
+
+        #[test]
+        fn test_to_value_is_native() {
+            let value = to_value(&drawing()).unwrap();
+            assert_eq!(
+                value,
+                toon!({
+                    name: "plan: \"A\"",
+                    scale: 2.0,
+                    shapes: [
+                        "Empty",
+                        { Circle: 0.5 },
+                        { Line: [-1, 1] },
+                        { Rect: { w: 3u32, h: 4u32 } }
+                    ],
+                    layers: { "1": true, "2": false },
+                    note: null,
+                    id: 7
+                })
+            );
+        }
+
+        #[test]
+        fn test_value_roundtrip() {
+            let value = to_value(&drawing()).unwrap();
+            assert_eq!(from_value::<Drawing>(&value).unwrap(), drawing());
+            assert_eq!(
+                Drawing::deserialize(OwnedDeserializer::new(value)).unwrap(),
+                drawing()
+            );
+        }
+
+        #[test]
+        fn test_from_value_borrows_and_coerces() {
+            #[derive(Deserialize)]
+            struct Borrowed<'a> {
+                name: &'a str,
+                count: u8,
+            }
+
+            let value = toon!({ name: "Alice", count: 3.0 });
+            let borrowed: Borrowed = from_value(&value).unwrap();
+            assert_eq!((borrowed.name, borrowed.count), ("Alice", 3));
+
+            assert!(from_value::<u8>(&toon!(3.5)).is_err());
+            assert!(from_value::<u8>(&toon!(300)).is_err());
+        }
+
+        #[test]
+        fn test_from_value_errors() {
+            let err = from_value::<Shape>(&toon!({ Circle: 1, Line: [1, 2] })).unwrap_err();
+            assert!(
+                err.to_string()
+                    .contains("a string or an object with a single key")
+            );
+
+            let err = from_value::<(i32, i32)>(&toon!([1, 2, 3])).unwrap_err();
+            assert!(err.to_string().contains("invalid length"));
+
+            assert!(to_value(&BTreeMap::from([((1, 2), "x")])).is_err());
+        }
+    }
 }
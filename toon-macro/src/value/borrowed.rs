@@ -0,0 +1,456 @@
+//! Values borrowing their strings from the parsed text.
+
+use std::borrow::Cow;
+use std::ops::Index;
+
+use super::{Map, Number, Value};
+
+/// A parsed TOON value whose keys and strings borrow from the input text.
+///
+/// Returned by [`from_toon_str_borrowed`](crate::from_toon_str_borrowed).
+/// Strings are only copied when they contain escape sequences, which makes
+/// parsing a large document to read a few fields cheaper than
+/// [`from_toon_str`](crate::from_toon_str). Use [`into_owned`] to turn it
+/// into a [`Value`].
+///
+/// [`into_owned`]: ValueRef::into_owned
+///
+/// # Example
+///
+/// ```
+/// use std::borrow::Cow;
+/// use toon_macro::{from_toon_str_borrowed, toon};
+///
+/// let text = "id: 7\nname: Alice\nnote: \"line 1\\nline 2\"";
+/// let value = from_toon_str_borrowed(text).unwrap();
+///
+/// let user = value.as_object().unwrap();
+/// assert_eq!(user["id"].as_u64(), Some(7));
+/// assert!(matches!(user["name"], toon_macro::ValueRef::String(Cow::Borrowed("Alice"))));
+/// assert_eq!(user["note"].as_str(), Some("line 1\nline 2"));
+///
+/// assert_eq!(value.into_owned(), toon!({ id: 7, name: "Alice", note: "line 1\nline 2" }));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueRef<'a> {
+    /// `null`.
+    Null,
+    /// `true` or `false`.
+    Bool(bool),
+    /// A number.
+    Number(Number),
+    /// A string, borrowed unless it was written with escape sequences.
+    String(Cow<'a, str>),
+    /// An array.
+    Array(Vec<ValueRef<'a>>),
+    /// An object, with keys borrowed like strings.
+    Object(Map<Cow<'a, str>, ValueRef<'a>>),
+}
+
+impl<'a> ValueRef<'a> {
+    /// Copy the borrowed strings into an owned [`Value`].
+    pub fn into_owned(self) -> Value {
+        match self {
+            ValueRef::Null => Value::Null,
+            ValueRef::Bool(b) => Value::Bool(b),
+            ValueRef::Number(n) => Value::Number(n),
+            ValueRef::String(s) => Value::String(s.into_owned()),
+            ValueRef::Array(items) => {
+                Value::Array(items.into_iter().map(ValueRef::into_owned).collect())
+            }
+            ValueRef::Object(map) => Value::Object(
+                map.into_iter()
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Look up a key of an object or an element of an array, returning
+    /// `None` if this value is neither or has no such entry.
+    ///
+    /// Indexing with `value[key]` or `value[i]` does the same, but gives
+    /// [`ValueRef::Null`] for a missing entry so lookups can be chained.
+    ///
+    /// ```
+    /// use toon_macro::from_toon_str_borrowed;
+    ///
+    /// let value = from_toon_str_borrowed("users[2]{id,name}:\n  1,Alice\n  2,Bob").unwrap();
+    /// let user = value.get("users").and_then(|users| users.get(1)).unwrap();
+    /// assert_eq!(user["name"].as_str(), Some("Bob"));
+    /// assert_eq!(value["users"][0]["id"].as_u64(), Some(1));
+    /// assert!(value.get("groups").is_none());
+    /// assert!(value["users"][5]["name"].is_null());
+    /// ```
+    pub fn get<I: ValueIndex>(&self, index: I) -> Option<&ValueRef<'a>> {
+        index.index_into(self)
+    }
+
+    /// Returns `true` if this value is null.
+    pub fn is_null(&self) -> bool {
+        matches!(self, ValueRef::Null)
+    }
+
+    /// Returns `true` if this value is a boolean.
+    pub fn is_bool(&self) -> bool {
+        matches!(self, ValueRef::Bool(_))
+    }
+
+    /// Returns `true` if this value is a number.
+    pub fn is_number(&self) -> bool {
+        matches!(self, ValueRef::Number(_))
+    }
+
+    /// Returns `true` if this value is a string.
+    pub fn is_string(&self) -> bool {
+        matches!(self, ValueRef::String(_))
+    }
+
+    /// Returns `true` if this value is an array.
+    pub fn is_array(&self) -> bool {
+        matches!(self, ValueRef::Array(_))
+    }
+
+    /// Returns `true` if this value is an object.
+    pub fn is_object(&self) -> bool {
+        matches!(self, ValueRef::Object(_))
+    }
+
+    /// If this value is a boolean, returns it. Otherwise returns `None`.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ValueRef::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// If this value is a number, tries to convert it to `i64`. Otherwise returns `None`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            ValueRef::Number(n) => n.as_i64(),
+            _ => None,
+        }
+    }
+
+    /// If this value is a number, tries to convert it to `u64`. Otherwise returns `None`.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            ValueRef::Number(n) => n.as_u64(),
+            _ => None,
+        }
+    }
+
+    /// If this value is a number, returns it as `f64`. Otherwise returns `None`.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ValueRef::Number(n) => Some(n.as_f64()),
+            _ => None,
+        }
+    }
+
+    /// If this value is a string, returns it. Otherwise returns `None`.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ValueRef::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// If this value is an array, returns its elements. Otherwise returns `None`.
+    pub fn as_array(&self) -> Option<&Vec<ValueRef<'a>>> {
+        match self {
+            ValueRef::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// If this value is an object, returns its map. Otherwise returns `None`.
+    pub fn as_object(&self) -> Option<&Map<Cow<'a, str>, ValueRef<'a>>> {
+        match self {
+            ValueRef::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+}
+
+/// A key or array position that can look up an entry in a [`ValueRef`].
+///
+/// Implemented for `usize` (array elements) and `str` and `String` (object
+/// keys), and references to them; see [`ValueRef::get`].
+pub trait ValueIndex: private::Sealed {
+    #[doc(hidden)]
+    fn index_into<'v, 'a>(&self, value: &'v ValueRef<'a>) -> Option<&'v ValueRef<'a>>;
+}
+
+impl ValueIndex for usize {
+    fn index_into<'v, 'a>(&self, value: &'v ValueRef<'a>) -> Option<&'v ValueRef<'a>> {
+        value.as_array()?.get(*self)
+    }
+}
+
+impl ValueIndex for str {
+    fn index_into<'v, 'a>(&self, value: &'v ValueRef<'a>) -> Option<&'v ValueRef<'a>> {
+        value.as_object()?.get(self)
+    }
+}
+
+impl ValueIndex for String {
+    fn index_into<'v, 'a>(&self, value: &'v ValueRef<'a>) -> Option<&'v ValueRef<'a>> {
+        self.as_str().index_into(value)
+    }
+}
+
+impl<T: ValueIndex + ?Sized> ValueIndex for &T {
+    fn index_into<'v, 'a>(&self, value: &'v ValueRef<'a>) -> Option<&'v ValueRef<'a>> {
+        (**self).index_into(value)
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for usize {}
+    impl Sealed for str {}
+    impl Sealed for String {}
+    impl<T: Sealed + ?Sized> Sealed for &T {}
+}
+
+impl<'a, I: ValueIndex> Index<I> for ValueRef<'a> {
+    type Output = ValueRef<'a>;
+
+    /// The entry at `index`, or [`ValueRef::Null`] if there is none.
+    fn index(&self, index: I) -> &ValueRef<'a> {
+        static NULL: ValueRef<'static> = ValueRef::Null;
+        index.index_into(self).unwrap_or(&NULL)
+    }
+}
+
+impl From<ValueRef<'_>> for Value {
+    fn from(value: ValueRef<'_>) -> Self {
+        value.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_toon_str, from_toon_str_borrowed, to_toon_string};
+
+    /// Whether every string and key in `value` borrows from the input.
+    fn all_borrowed(value: &ValueRef<'_>) -> bool {
+        match value {
+            ValueRef::String(s) => matches!(s, Cow::Borrowed(_)),
+            ValueRef::Array(items) => items.iter().all(all_borrowed),
+            ValueRef::Object(map) => map
+                .iter()
+                .all(|(key, value)| matches!(key, Cow::Borrowed(_)) && all_borrowed(value)),
+            _ => true,
+        }
+    }
+
+    #[test]
+    fn test_matches_from_toon_str() {
+        let documents = [
+            "",
+            "42",
+            "-7",
+            "1.5e3",
+            "007",
+            "hello world",
+            "\"quoted: \\\"yes\\\"\"",
+            "name: Alice\nage: 30\nactive: true\nnothing: null",
+            "a:\n  b:\n    c: 1\n  d: \"x, y\"\ne: 2",
+            "empty:\nnext: 1",
+            "tags[3]: a,b,c",
+            "tags[3|]: a|b c|\"d|e\"",
+            "tags[2\t]: a b\tc",
+            "tags[0]:",
+            "[3]: 1,two,3.5",
+            "[2]:\n  - [2]: 1,2\n  - [0]:",
+            "users[2]{id,name}:\n  1,Alice\n  2,\"Bob, Jr\"",
+            "users[2|]{id|\"full name\"}:\n  1|A B\n  2|C",
+            "[2]{a,b}:\n  1,2\n  3,4",
+            "items[3]:\n  - 1\n  - x\n  -",
+            "items[2]:\n  - id: 1\n    name: a\n  - id: 2\n    tags[2]: x,y",
+            "items[1]:\n  - tags[2]: x,y\n    id: 1",
+            "items[1]:\n  - rows[2]{a}:\n    1\n    2\n    id: 1",
+            "items[1]:\n  - nested:\n      a: 1\n    id: 2",
+            "  \nkey: value\n\n",
+            "\"key with space\": 1\n\"esc\\tkey\": 2",
+            "url: http://x.io/a?b=c",
+            "text:   padded   ",
+        ];
+        for text in documents {
+            let expected = from_toon_str(text).unwrap();
+            let value = from_toon_str_borrowed(text).unwrap();
+            assert_eq!(value.clone().into_owned(), expected, "for {:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_round_trips_encoded_values() {
+        let values = [
+            toon!({ a: 1, b: [1, 2.5, "x y", ""], c: { d: null, e: { f: false } } }),
+            toon!([{ id: 1, name: "a" }, { id: 2, name: "b" }]),
+            toon!({ list: [1, [1, 2], { a: 1 }, "-dash", "true", "007"] }),
+            toon!({ items: [{ a: 1, b: [{ c: 1 }, { c: 2 }], d: { e: 3 } }, {}] }),
+            toon!({ matrix: [[1, 2], [], ["a", "b:c"]] }),
+            toon!({ "weird key": "line\nbreak", "a\"b": "\\" }),
+            toon!({ n: -0.5, big: 18446744073709551615u64, neg: -9007199254740993i64 }),
+        ];
+        for value in values {
+            let text = to_toon_string(&value).unwrap();
+            let parsed = from_toon_str_borrowed(&text).unwrap();
+            assert_eq!(parsed.into_owned(), value, "for\n{}", text);
+        }
+    }
+
+    #[test]
+    fn test_strings_borrow_from_input() {
+        let text =
+            "users[2]{id,name}:\n  1,Alice\n  2,\"Bob, Jr\"\nnote: hi\nlist[2]:\n  - a: x\n  - b";
+        let value = from_toon_str_borrowed(text).unwrap();
+        assert!(all_borrowed(&value));
+
+        let escaped = from_toon_str_borrowed("a: \"x\\ny\"").unwrap();
+        assert!(!all_borrowed(&escaped));
+        assert_eq!(escaped.as_object().unwrap()["a"].as_str(), Some("x\ny"));
+    }
+
+    #[test]
+    fn test_accessors() {
+        let value =
+            from_toon_str_borrowed("n: -3\nu: 3\nf: 0.5\nb: true\nz: null\ns: x\nl[1]: 1").unwrap();
+        let map = value.as_object().unwrap();
+        assert_eq!(map["n"].as_i64(), Some(-3));
+        assert_eq!(map["n"].as_u64(), None);
+        assert_eq!(map["u"].as_u64(), Some(3));
+        assert_eq!(map["f"].as_f64(), Some(0.5));
+        assert_eq!(map["b"].as_bool(), Some(true));
+        assert!(map["z"].is_null());
+        assert_eq!(map["s"].as_str(), Some("x"));
+        assert_eq!(map["l"].as_array().map(Vec::len), Some(1));
+        assert_eq!(map["s"].as_array(), None);
+        assert_eq!(value.as_str(), None);
+
+        assert!(map["b"].is_bool() && !map["z"].is_bool());
+        assert!(map["f"].is_number() && !map["s"].is_number());
+        assert!(map["s"].is_string() && !map["l"].is_string());
+        assert!(map["l"].is_array() && !value.is_array());
+        assert!(value.is_object() && !map["l"].is_object());
+    }
+
+    #[test]
+    fn test_get_and_index() {
+        let value =
+            from_toon_str_borrowed("rows[2]{id,tags}:\n  1,a\n  2,b\nmeta:\n  count: 2").unwrap();
+        let key = String::from("meta");
+
+        assert_eq!(
+            value.get("rows").and_then(|rows| rows.get(1)),
+            Some(&value["rows"][1])
+        );
+        assert_eq!(value["rows"][1]["tags"].as_str(), Some("b"));
+        assert_eq!(
+            value
+                .get(&key)
+                .and_then(|meta| meta.get("count"))
+                .and_then(ValueRef::as_u64),
+            Some(2)
+        );
+        assert_eq!(value[&key]["count"], value[key.as_str()]["count"]);
+
+        assert_eq!(value.get("missing"), None);
+        assert_eq!(value.get(0), None);
+        assert_eq!(value["rows"].get(2), None);
+        assert_eq!(value["rows"].get("id"), None);
+        assert!(value["missing"]["deeper"][3].is_null());
+    }
+
+    /// Whether every key in `value` is one that both parsers read the same
+    /// way; see the exceptions on `from_toon_str_borrowed`.
+    fn plain_keys(value: &Value) -> bool {
+        match value {
+            Value::Array(items) => items.iter().all(plain_keys),
+            Value::Object(map) => map.iter().all(|(key, value)| {
+                !key.is_empty()
+                    && key.is_ascii()
+                    && !key.contains([':', '"', '\\'])
+                    && plain_keys(value)
+            }),
+            _ => true,
+        }
+    }
+
+    #[test]
+    fn test_matches_serde_toon2_on_generated_documents() {
+        let mut compared = 0;
+        for value in crate::arbitrary::values(3000).filter(plain_keys) {
+            let mut text = String::new();
+            if crate::encode::Encoder::new(&mut text)
+                .value(&value)
+                .is_err()
+            {
+                continue;
+            }
+            // `from_toon_str` also fails on arrays in later list item
+            // fields, which are read here.
+            if let Ok(expected) = serde_toon2::from_str::<Value>(&text) {
+                let parsed = from_toon_str_borrowed(&text).map(ValueRef::into_owned);
+                assert_eq!(parsed.ok(), Some(expected), "for\n{}", text);
+                compared += 1;
+            }
+        }
+        assert!(compared > 1500, "only {} documents compared", compared);
+    }
+
+    #[test]
+    fn test_documented_differences_from_from_toon_str() {
+        for (text, expected) in [
+            ("\"q\\\"q\"[1]: 1", Some(toon!({ "q\"q": [1] }))),
+            (
+                "items[1]:\n  - id: 1\n    tags[2]:\n      - a\n      - b",
+                Some(toon!({ items: [{ id: 1, tags: ["a", "b"] }] })),
+            ),
+            ("a: 1\n   b: 2", None),
+            ("[1]: 1\nb: 2", None),
+            ("a: [draft]", Some(toon!({ a: "[draft]" }))),
+            ("tags[2: a,b", None),
+            ("[0]]:", None),
+        ] {
+            let parsed = from_toon_str_borrowed(text).ok().map(ValueRef::into_owned);
+            assert_eq!(parsed, expected, "for {:?}", text);
+            assert_ne!(from_toon_str(text).ok(), expected, "for {:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        for (text, message) in [
+            ("a: 1\n    b: 2", "Unexpected indentation at line 2"),
+            ("  a: 1", "First line must be at depth 0 at line 1"),
+            ("a: 1\nb", "Missing colon after key at line 2"),
+            ("tags[3]: a,b", "Expected 3 values, got 2 at line 1"),
+            ("rows[2]{a,b}:\n  1,2", "Expected 2 rows, got 1 at line 1"),
+            (
+                "rows[1]{a,b}:\n  1,2,3",
+                "Expected 2 values, got 3 at line 2",
+            ),
+            (
+                "list[2]:\n  - 1\nnext: 2",
+                "Expected 2 items, got 1 at line 1",
+            ),
+            ("tags[x]: a", "Invalid array length: x at line 1"),
+            (
+                "a: \"open",
+                "String starting with quote must end with quote at line 1",
+            ),
+            ("a: \"bad \\q\"", "Invalid escape sequence: \\q at line 1"),
+        ] {
+            let err = from_toon_str_borrowed(text).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("TOON deserialization error: {}", message)
+            );
+        }
+    }
+}
@@ -0,0 +1,438 @@
+//! Canonical comparison, encoding and hashing of values.
+//!
+//! Two values are canonically equal when they differ only in object key
+//! order and number representation: `1`, `1u64` and `1.0` are the same
+//! number, and so are `0.0` and `-0.0`. `NaN` equals itself and sorts after
+//! every other number. Values of different types order as `null`, bools,
+//! numbers, strings, arrays, objects; strings compare by bytes, arrays
+//! lexicographically, and objects as their entries sorted by key.
+
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+use super::{Map, Number, Value};
+use crate::encode::Encoder;
+
+/// A [`Value`] compared, ordered and hashed canonically, for use as a
+/// `HashMap`/`BTreeMap` key or in a `HashSet`.
+///
+/// # Example
+///
+/// ```
+/// use std::collections::HashSet;
+/// use toon_macro::{toon, CanonicalValue};
+///
+/// let mut seen = HashSet::new();
+/// assert!(seen.insert(CanonicalValue(toon!({ a: 1, b: [2.5, "x"] }))));
+/// assert!(!seen.insert(CanonicalValue(toon!({ b: [2.5, "x"], a: 1.0 }))));
+///
+/// assert!(CanonicalValue(toon!(2)) < CanonicalValue(toon!(2.5)));
+/// assert!(CanonicalValue(toon!(null)) < CanonicalValue(toon!(false)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CanonicalValue(pub Value);
+
+impl CanonicalValue {
+    /// Unwrap the value.
+    pub fn into_inner(self) -> Value {
+        self.0
+    }
+
+    /// The canonical TOON text of the value; see [`to_canonical_toon_string`].
+    pub fn to_canonical_toon_string(&self) -> String {
+        to_canonical_toon_string(&self.0)
+    }
+
+    /// The stable content hash of the value; see [`content_hash`].
+    pub fn content_hash(&self) -> u64 {
+        content_hash(&self.0)
+    }
+}
+
+impl From<Value> for CanonicalValue {
+    fn from(value: Value) -> Self {
+        CanonicalValue(value)
+    }
+}
+
+impl From<CanonicalValue> for Value {
+    fn from(value: CanonicalValue) -> Self {
+        value.0
+    }
+}
+
+impl AsRef<Value> for CanonicalValue {
+    fn as_ref(&self) -> &Value {
+        &self.0
+    }
+}
+
+impl PartialEq for CanonicalValue {
+    fn eq(&self, other: &Self) -> bool {
+        compare(&self.0, &other.0) == Ordering::Equal
+    }
+}
+
+impl Eq for CanonicalValue {}
+
+impl PartialOrd for CanonicalValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CanonicalValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare(&self.0, &other.0)
+    }
+}
+
+impl Hash for CanonicalValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        feed(&self.0, state);
+    }
+}
+
+/// Encode `value` as canonical TOON text: object keys sorted by bytes,
+/// integral floats written as integers, `-0.0` as `0`, and other floats
+/// with their shortest exact decimal form. Canonically equal values have
+/// the same text.
+///
+/// # Panics
+///
+/// On the same nested array layouts as [`to_toon_string`], which TOON
+/// cannot represent.
+///
+/// [`to_toon_string`]: crate::to_toon_string
+///
+/// # Example
+///
+/// ```
+/// use toon_macro::{toon, to_canonical_toon_string};
+///
+/// let value = toon!({ b: 2.0, a: { y: -0.0, x: 0.000001 } });
+/// assert_eq!(to_canonical_toon_string(&value), "a:\n  x: 0.000001\n  y: 0\nb: 2");
+/// ```
+pub fn to_canonical_toon_string(value: &Value) -> String {
+    let mut out = String::new();
+    // Writing to a `String` only fails on layouts TOON cannot represent.
+    if Encoder::new(&mut out)
+        .exact_floats()
+        .value(&canonical(value))
+        .is_err()
+    {
+        panic!(
+            "cannot encode value as canonical TOON: an array inside an array holds arrays or objects"
+        );
+    }
+    out
+}
+
+/// A 64-bit hash of the content of `value`, equal for canonically equal
+/// values.
+///
+/// Unlike [`Hash`] implementations, the result is stable across program
+/// runs, platforms and releases of this crate, so it can be stored, e.g. as
+/// a cache key. It is the FNV-1a hash of a structural encoding of the
+/// value; it is not a cryptographic hash.
+///
+/// ```
+/// use toon_macro::{toon, value::content_hash};
+///
+/// assert_eq!(
+///     content_hash(&toon!({ id: 7, tags: ["a"] })),
+///     content_hash(&toon!({ tags: ["a"], id: 7u64 }))
+/// );
+/// ```
+pub fn content_hash(value: &Value) -> u64 {
+    let mut hasher = Fnv1a(0xcbf2_9ce4_8422_2325);
+    feed(value, &mut hasher);
+    hasher.0
+}
+
+/// A number reduced to one form per value: integral values as integers.
+#[derive(Clone, Copy)]
+enum Canonical {
+    Int(i128),
+    /// A non-integral, infinite, `NaN` or integral but out of `i128` range
+    /// float.
+    Float(f64),
+}
+
+/// 2^127, the first float above the `i128` range.
+const I128_LIMIT: f64 = 170_141_183_460_469_231_731_687_303_715_884_105_728.0;
+
+fn number(n: &Number) -> Canonical {
+    match *n {
+        Number::I64(n) => Canonical::Int(n as i128),
+        Number::U64(n) => Canonical::Int(n as i128),
+        Number::F64(f) if f.fract() == 0.0 && f.abs() < I128_LIMIT => Canonical::Int(f as i128),
+        Number::F64(f) => Canonical::Float(f),
+    }
+}
+
+fn compare_numbers(a: &Number, b: &Number) -> Ordering {
+    fn int_float(int: i128, float: f64) -> Ordering {
+        if float.is_nan() || float >= I128_LIMIT {
+            Ordering::Less
+        } else if float < -I128_LIMIT {
+            Ordering::Greater
+        } else if int <= float.floor() as i128 {
+            // `float` is not integral here, so it is above its floor.
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        }
+    }
+
+    match (number(a), number(b)) {
+        (Canonical::Int(a), Canonical::Int(b)) => a.cmp(&b),
+        (Canonical::Float(a), Canonical::Float(b)) => match (a.is_nan(), b.is_nan()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => a.partial_cmp(&b).expect("neither is NaN"),
+        },
+        (Canonical::Int(a), Canonical::Float(b)) => int_float(a, b),
+        (Canonical::Float(a), Canonical::Int(b)) => int_float(b, a).reverse(),
+    }
+}
+
+fn rank(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    }
+}
+
+fn sorted(map: &Map<String, Value>) -> Vec<(&String, &Value)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => compare_numbers(a, b),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Value::Object(a), Value::Object(b)) => {
+            let (a, b) = (sorted(a), sorted(b));
+            a.iter()
+                .zip(&b)
+                .map(|((ka, va), (kb, vb))| ka.cmp(kb).then_with(|| compare(va, vb)))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len()))
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Feed a structural encoding of `value` to `state`, equal for canonically
+/// equal values. Only `Hasher::write` is used, with fixed-endian integers,
+/// so that [`content_hash`] does not depend on the platform.
+fn feed<H: Hasher>(value: &Value, state: &mut H) {
+    state.write(&[rank(value)]);
+    match value {
+        Value::Null => {}
+        Value::Bool(b) => state.write(&[*b as u8]),
+        Value::Number(n) => match number(n) {
+            Canonical::Int(n) => {
+                state.write(&[0]);
+                state.write(&n.to_le_bytes());
+            }
+            Canonical::Float(f) => {
+                let f = if f.is_nan() { f64::NAN } else { f };
+                state.write(&[1]);
+                state.write(&f.to_bits().to_le_bytes());
+            }
+        },
+        Value::String(s) => feed_str(s, state),
+        Value::Array(items) => {
+            state.write(&(items.len() as u64).to_le_bytes());
+            for item in items {
+                feed(item, state);
+            }
+        }
+        Value::Object(map) => {
+            state.write(&(map.len() as u64).to_le_bytes());
+            for (key, value) in sorted(map) {
+                feed_str(key, state);
+                feed(value, state);
+            }
+        }
+    }
+}
+
+fn feed_str<H: Hasher>(s: &str, state: &mut H) {
+    state.write(&(s.len() as u64).to_le_bytes());
+    state.write(s.as_bytes());
+}
+
+/// `value` with sorted keys and numbers in their canonical form.
+fn canonical(value: &Value) -> Value {
+    match value {
+        Value::Number(n) => Value::Number(match number(n) {
+            Canonical::Int(n) => match (i64::try_from(n), u64::try_from(n)) {
+                (Ok(n), _) => Number::I64(n),
+                (_, Ok(n)) => Number::U64(n),
+                _ => Number::F64(n as f64),
+            },
+            Canonical::Float(f) => Number::F64(f),
+        }),
+        Value::Array(items) => Value::Array(items.iter().map(canonical).collect()),
+        Value::Object(map) => Value::Object(
+            sorted(map)
+                .into_iter()
+                .map(|(key, value)| (key.clone(), canonical(value)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// The 64-bit FNV-1a hash.
+struct Fnv1a(u64);
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeSet, HashMap};
+
+    fn canon(value: Value) -> CanonicalValue {
+        CanonicalValue(value)
+    }
+
+    #[test]
+    fn test_numbers_compare_by_value() {
+        let equal = [
+            (toon!(1), toon!(1u64)),
+            (toon!(1), toon!(1.0)),
+            (toon!(0.0), toon!(-0.0)),
+            (toon!(9223372036854775808u64), toon!(9223372036854775808.0)),
+            (toon!(f64::NAN), toon!(-f64::NAN)),
+        ];
+        for (a, b) in equal {
+            assert_eq!(canon(a.clone()), canon(b.clone()));
+            assert_eq!(content_hash(&a), content_hash(&b));
+        }
+
+        let ascending = [
+            toon!(f64::NEG_INFINITY),
+            toon!(i64::MIN),
+            toon!(-1.5),
+            toon!(-1),
+            toon!(0),
+            toon!(0.5),
+            toon!(1),
+            toon!(9007199254740993i64),
+            toon!(9007199254740994.0),
+            toon!(u64::MAX),
+            toon!(1e30),
+            toon!(f64::INFINITY),
+            toon!(f64::NAN),
+        ];
+        for pair in ascending.windows(2) {
+            assert!(
+                canon(pair[0].clone()) < canon(pair[1].clone()),
+                "{:?} < {:?}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn test_type_and_structure_order() {
+        let ascending = [
+            toon!(null),
+            toon!(false),
+            toon!(true),
+            toon!(-5),
+            toon!(""),
+            toon!("a"),
+            toon!("b"),
+            toon!([]),
+            toon!([1]),
+            toon!([1, 2]),
+            toon!([2]),
+            toon!({}),
+            toon!({ a: 1 }),
+            toon!({ a: 1, b: 0 }),
+            toon!({ a: 2 }),
+            toon!({ b: 0 }),
+        ];
+        let set: BTreeSet<_> = ascending.iter().rev().cloned().map(canon).collect();
+        let sorted: Vec<_> = set.into_iter().map(CanonicalValue::into_inner).collect();
+        assert_eq!(sorted, ascending);
+    }
+
+    #[test]
+    fn test_hash_map_keys_ignore_key_order() {
+        let mut cache = HashMap::new();
+        cache.insert(
+            canon(toon!({ model: "m", params: { t: 0.5, n: 1 } })),
+            "hit",
+        );
+        let key = canon(toon!({ params: { n: 1.0, t: 0.5 }, model: "m" }));
+        assert_eq!(cache.get(&key), Some(&"hit"));
+        assert!(!cache.contains_key(&canon(toon!({ model: "m" }))));
+    }
+
+    #[test]
+    fn test_canonical_toon_string() {
+        let a = toon!({ z: [3.0, 0.1], a: { c: true, b: null }, big: 1e20 });
+        let b = toon!({ a: { b: null, c: true }, big: 100000000000000000000u128, z: [3u8, 0.1] });
+        let text = to_canonical_toon_string(&a);
+        assert_eq!(
+            text,
+            "a:\n  b: null\n  c: true\nbig: 100000000000000000000\nz[2]: 3,0.1"
+        );
+        assert_eq!(to_canonical_toon_string(&b), text);
+        assert_eq!(
+            to_canonical_toon_string(&toon!([1e-20, -2.5e-7])),
+            "[2]: 0.00000000000000000001,-0.00000025"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot encode value as canonical TOON")]
+    fn test_canonical_toon_string_unrepresentable() {
+        to_canonical_toon_string(&toon!([[1, [2]]]));
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        // Pinned: changing these values breaks stored hashes.
+        assert_eq!(content_hash(&toon!(null)), 0xaf63_bd4c_8601_b7df);
+        assert_eq!(content_hash(&toon!({ a: [1, "x"] })), 0xe1e4_d6d0_62f1_acdc);
+        assert_ne!(content_hash(&toon!("1")), content_hash(&toon!(1)));
+        assert_ne!(
+            content_hash(&toon!(["a", "b"])),
+            content_hash(&toon!(["ab"]))
+        );
+    }
+}
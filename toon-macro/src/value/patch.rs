@@ -0,0 +1,438 @@
+//! Partial updates: RFC 7396 merge patches and RFC 6902 operation patches.
+//!
+//! A merge patch is a value shaped like the document it updates; an
+//! operation patch is a [`Patch`], a list of [`PatchOperation`]s addressed
+//! with JSON pointers. Both are applied through [`ValueExt`].
+
+use super::path::{lookup_mut, mismatch, not_found, parse_index, parse_pointer};
+use super::{Map, Value, ValueExt, kind, values_equal};
+use crate::{Error, FromToon, Result, ToToon};
+
+/// A single RFC 6902 operation. Paths are JSON pointers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchOperation {
+    /// Insert `value` at `path`, replacing an existing object member or
+    /// shifting later array elements. The parent must exist.
+    Add {
+        /// Where to insert.
+        path: String,
+        /// The value to insert.
+        value: Value,
+    },
+    /// Remove the value at `path`.
+    Remove {
+        /// What to remove.
+        path: String,
+    },
+    /// Replace the existing value at `path`.
+    Replace {
+        /// What to replace.
+        path: String,
+        /// The new value.
+        value: Value,
+    },
+    /// Remove the value at `from` and add it at `path`.
+    Move {
+        /// What to move.
+        from: String,
+        /// Where to add it.
+        path: String,
+    },
+    /// Add a copy of the value at `from` at `path`.
+    Copy {
+        /// What to copy.
+        from: String,
+        /// Where to add it.
+        path: String,
+    },
+    /// Check that the value at `path` equals `value`.
+    Test {
+        /// What to check.
+        path: String,
+        /// The expected value; numbers compare by value.
+        value: Value,
+    },
+}
+
+/// An RFC 6902 patch: operations applied in order.
+///
+/// Patches convert from and to their standard document form through
+/// [`FromToon`] and [`ToToon`], so a patch returned by a model can be read
+/// straight from the parsed response.
+///
+/// # Example
+///
+/// ```
+/// use toon_macro::{toon, FromToon, ValueExt};
+/// use toon_macro::value::Patch;
+///
+/// let mut doc = toon!({ title: "Draft", tags: ["a"] });
+/// let patch = Patch::from_toon(&toon!([
+///     { op: "replace", path: "/title", value: "Final" },
+///     { op: "add", path: "/tags/-", value: "b" },
+///     { op: "test", path: "/tags/0", value: "a" }
+/// ]))
+/// .unwrap();
+///
+/// doc.apply_patch(&patch).unwrap();
+/// assert_eq!(doc, toon!({ title: "Final", tags: ["a", "b"] }));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Patch(pub Vec<PatchOperation>);
+
+impl PatchOperation {
+    fn name(&self) -> &'static str {
+        match self {
+            PatchOperation::Add { .. } => "add",
+            PatchOperation::Remove { .. } => "remove",
+            PatchOperation::Replace { .. } => "replace",
+            PatchOperation::Move { .. } => "move",
+            PatchOperation::Copy { .. } => "copy",
+            PatchOperation::Test { .. } => "test",
+        }
+    }
+
+    fn apply(&self, target: &mut Value) -> Result<()> {
+        match self {
+            PatchOperation::Add { path, value } => add(target, &segments(path)?, value.clone()),
+            PatchOperation::Remove { path } => target.remove_path(&segments(path)?).map(drop),
+            PatchOperation::Replace { path, value } => {
+                *lookup_mut(target, &segments(path)?)? = value.clone();
+                Ok(())
+            }
+            PatchOperation::Move { from, path } => {
+                let from_segments = segments(from)?;
+                let path_segments = segments(path)?;
+                if path_segments.len() > from_segments.len()
+                    && path_segments.starts_with(&from_segments)
+                {
+                    return Err(Error::InvalidPath(format!(
+                        "cannot move `{}` into its own child `{}`",
+                        from, path
+                    )));
+                }
+                let value = target.remove_path(&from_segments)?;
+                add(target, &path_segments, value)
+            }
+            PatchOperation::Copy { from, path } => {
+                let value = target.get_path(&segments(from)?)?.clone();
+                add(target, &segments(path)?, value)
+            }
+            PatchOperation::Test { path, value } => {
+                if values_equal(target.get_path(&segments(path)?)?, value) {
+                    Ok(())
+                } else {
+                    Err(Error::PatchTestFailed { path: path.clone() })
+                }
+            }
+        }
+    }
+}
+
+/// Apply a merge patch to `target`.
+pub(super) fn merge(target: &mut Value, patch: &Value) {
+    let Value::Object(members) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !matches!(target, Value::Object(_)) {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(map) = target else {
+        unreachable!("target was just made an object");
+    };
+    for (key, value) in members {
+        if value.is_null() {
+            map.shift_remove(key);
+        } else {
+            merge(map.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Apply every operation of `patch` to a copy of `target`, replacing
+/// `target` only if they all succeed.
+pub(super) fn apply(target: &mut Value, patch: &Patch) -> Result<()> {
+    let mut patched = target.clone();
+    for operation in &patch.0 {
+        operation.apply(&mut patched)?;
+    }
+    *target = patched;
+    Ok(())
+}
+
+fn segments(pointer: &str) -> Result<Vec<String>> {
+    parse_pointer(pointer)
+        .ok_or_else(|| Error::InvalidPath(format!("`{}` is not a JSON pointer", pointer)))
+}
+
+fn add(target: &mut Value, path: &[String], value: Value) -> Result<()> {
+    let Some((last, parents)) = path.split_last() else {
+        *target = value;
+        return Ok(());
+    };
+    match lookup_mut(target, parents)? {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+            Ok(())
+        }
+        Value::Array(elements) => match parse_index(last, elements.len()) {
+            Some(index) if index <= elements.len() => {
+                elements.insert(index, value);
+                Ok(())
+            }
+            Some(_) => Err(not_found(parents, last)),
+            None => Err(mismatch("array", parents, last)),
+        },
+        other => Err(mismatch(kind(other), parents, last)),
+    }
+}
+
+impl ToToon for PatchOperation {
+    fn to_toon(&self) -> Value {
+        let mut map = Map::new();
+        map.insert("op".to_string(), self.name().to_toon());
+        match self {
+            PatchOperation::Move { from, path } | PatchOperation::Copy { from, path } => {
+                map.insert("from".to_string(), from.to_toon());
+                map.insert("path".to_string(), path.to_toon());
+            }
+            PatchOperation::Remove { path } => {
+                map.insert("path".to_string(), path.to_toon());
+            }
+            PatchOperation::Add { path, value }
+            | PatchOperation::Replace { path, value }
+            | PatchOperation::Test { path, value } => {
+                map.insert("path".to_string(), path.to_toon());
+                map.insert("value".to_string(), value.clone());
+            }
+        }
+        Value::Object(map)
+    }
+}
+
+impl FromToon for PatchOperation {
+    fn from_toon(value: &Value) -> Result<Self> {
+        let Value::Object(map) = value else {
+            return Err(Error::invalid_value("patch operation object", value));
+        };
+        let string = |name: &str| match map.get(name) {
+            Some(Value::String(s)) => Ok(s.clone()),
+            Some(other) => Err(Error::InvalidPatch(format!(
+                "`{}` must be a string, got {}",
+                name,
+                kind(other)
+            ))),
+            None => Err(Error::InvalidPatch(format!("missing `{}`", name))),
+        };
+        let value = || {
+            map.get("value")
+                .cloned()
+                .ok_or_else(|| Error::InvalidPatch("missing `value`".into()))
+        };
+
+        let path = string("path")?;
+        match string("op")?.as_str() {
+            "add" => Ok(PatchOperation::Add {
+                path,
+                value: value()?,
+            }),
+            "remove" => Ok(PatchOperation::Remove { path }),
+            "replace" => Ok(PatchOperation::Replace {
+                path,
+                value: value()?,
+            }),
+            "move" => Ok(PatchOperation::Move {
+                from: string("from")?,
+                path,
+            }),
+            "copy" => Ok(PatchOperation::Copy {
+                from: string("from")?,
+                path,
+            }),
+            "test" => Ok(PatchOperation::Test {
+                path,
+                value: value()?,
+            }),
+            other => Err(Error::InvalidPatch(format!(
+                "unknown operation `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+impl ToToon for Patch {
+    fn to_toon(&self) -> Value {
+        self.0.to_toon()
+    }
+}
+
+impl FromToon for Patch {
+    fn from_toon(value: &Value) -> Result<Self> {
+        let Value::Array(operations) = value else {
+            return Err(Error::invalid_value("array of patch operations", value));
+        };
+        operations
+            .iter()
+            .enumerate()
+            .map(|(index, operation)| {
+                PatchOperation::from_toon(operation).map_err(|err| match err {
+                    Error::InvalidPatch(msg) => {
+                        Error::InvalidPatch(format!("operation {}: {}", index, msg))
+                    }
+                    other => other,
+                })
+            })
+            .collect::<Result<_>>()
+            .map(Patch)
+    }
+}
+
+impl FromIterator<PatchOperation> for Patch {
+    fn from_iter<I: IntoIterator<Item = PatchOperation>>(iter: I) -> Self {
+        Patch(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(value: Value) -> Patch {
+        Patch::from_toon(&value).unwrap()
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        // Examples from RFC 7396, appendix A.
+        let cases = [
+            (toon!({ a: "b" }), toon!({ a: "c" }), toon!({ a: "c" })),
+            (
+                toon!({ a: "b" }),
+                toon!({ b: "c" }),
+                toon!({ a: "b", b: "c" }),
+            ),
+            (toon!({ a: "b" }), toon!({ a: null }), toon!({})),
+            (
+                toon!({ a: "b", b: "c" }),
+                toon!({ a: null }),
+                toon!({ b: "c" }),
+            ),
+            (toon!({ a: ["b"] }), toon!({ a: "c" }), toon!({ a: "c" })),
+            (toon!({ a: "c" }), toon!({ a: ["b"] }), toon!({ a: ["b"] })),
+            (
+                toon!({ a: { b: "c" } }),
+                toon!({ a: { b: "d", c: null } }),
+                toon!({ a: { b: "d" } }),
+            ),
+            (
+                toon!({ a: [{ b: "c" }] }),
+                toon!({ a: [1] }),
+                toon!({ a: [1] }),
+            ),
+            (toon!(["a", "b"]), toon!(["c", "d"]), toon!(["c", "d"])),
+            (toon!({ a: "b" }), toon!(["c"]), toon!(["c"])),
+            (toon!({ a: "foo" }), toon!(null), toon!(null)),
+            (
+                toon!({ e: null }),
+                toon!({ a: 1 }),
+                toon!({ e: null, a: 1 }),
+            ),
+            (toon!([1, 2]), toon!({ a: "b", c: null }), toon!({ a: "b" })),
+            (
+                toon!({}),
+                toon!({ a: { bb: { ccc: null } } }),
+                toon!({ a: { bb: {} } }),
+            ),
+        ];
+        for (mut target, patch, expected) in cases {
+            target.merge_patch(&patch);
+            assert_eq!(target, expected);
+        }
+    }
+
+    #[test]
+    fn test_operations() {
+        let mut doc = toon!({ foo: ["bar", "baz"], obj: { a: 1 } });
+        doc.apply_patch(&patch(toon!([
+            { op: "add", path: "/foo/1", value: "qux" },
+            { op: "remove", path: "/foo/0" },
+            { op: "replace", path: "/obj/a", value: 2 },
+            { op: "copy", from: "/obj", path: "/copy" },
+            { op: "move", from: "/obj/a", path: "/moved" },
+            { op: "test", path: "/copy/a", value: 2.0 },
+            { op: "add", path: "/foo/-", value: "end" }
+        ])))
+        .unwrap();
+        assert_eq!(
+            doc,
+            toon!({
+                foo: ["qux", "baz", "end"],
+                obj: {},
+                copy: { a: 2 },
+                moved: 2
+            })
+        );
+
+        doc.apply_patch(&patch(toon!([{ op: "add", path: "", value: [1] }])))
+            .unwrap();
+        assert_eq!(doc, toon!([1]));
+    }
+
+    #[test]
+    fn test_failed_patch_is_atomic() {
+        let original = toon!({ a: 1, list: [1, 2] });
+        let mut doc = original.clone();
+
+        let err = doc
+            .apply_patch(&patch(toon!([
+                { op: "replace", path: "/a", value: 2 },
+                { op: "test", path: "/a", value: 3 }
+            ])))
+            .unwrap_err();
+        assert!(matches!(err, Error::PatchTestFailed { ref path } if path == "/a"));
+        assert_eq!(doc, original);
+
+        let failing = [
+            toon!({ op: "add", path: "/missing/x", value: 1 }),
+            toon!({ op: "add", path: "/list/3", value: 1 }),
+            toon!({ op: "replace", path: "/b", value: 1 }),
+            toon!({ op: "remove", path: "/list/x" }),
+            toon!({ op: "move", from: "/list", path: "/list/0" }),
+            toon!({ op: "copy", from: "/nope", path: "/b" }),
+            toon!({ op: "add", path: "a", value: 1 }),
+        ];
+        for operation in failing {
+            let operations = toon!([{ op: "remove", path: "/a" }, operation]);
+            assert!(doc.apply_patch(&patch(operations)).is_err());
+            assert_eq!(doc, original);
+        }
+    }
+
+    #[test]
+    fn test_patch_documents() {
+        let value = toon!([
+            { op: "move", from: "/a", path: "/b" },
+            { op: "test", path: "/b", value: [1, 2] }
+        ]);
+        let parsed = patch(value.clone());
+        assert_eq!(parsed.0.len(), 2);
+        assert_eq!(parsed.to_toon(), value);
+
+        let err =
+            Patch::from_toon(&toon!([{ op: "remove", path: "/a" }, { op: "add", path: "/b" }]))
+                .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid patch: operation 1: missing `value`"
+        );
+
+        let err = Patch::from_toon(&toon!([{ op: "frobnicate", path: "" }])).unwrap_err();
+        assert!(err.to_string().contains("unknown operation `frobnicate`"));
+        assert!(matches!(
+            Patch::from_toon(&toon!({ op: "add" })),
+            Err(Error::InvalidType { .. })
+        ));
+    }
+}
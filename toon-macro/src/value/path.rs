@@ -0,0 +1,647 @@
+//! Pointer-style access to nested values.
+//!
+//! Paths are sequences of segments: a key for objects and a decimal index
+//! for arrays. They are written either as RFC 6901 JSON pointers
+//! (`"/users/0/name"`, with `~1` for `/` and `~0` for `~` inside a segment)
+//! or as pre-split segments (`&["users", "0", "name"]`).
+
+use super::{Map, Patch, Query, Value, kind};
+use crate::{Error, Result, ToToon};
+
+/// Path-based lookup and modification of nested [`Value`]s.
+///
+/// # Example
+///
+/// ```
+/// use toon_macro::{toon, ValueExt};
+///
+/// let mut value = toon!({ users: [{ name: "Alice" }] });
+///
+/// assert_eq!(value.pointer("/users/0/name"), Some(&toon!("Alice")));
+/// assert_eq!(value.get_path(&["users", "0", "name"]).unwrap(), &toon!("Alice"));
+///
+/// value.set_path(&["users", "1", "name"], "Bob").unwrap();
+/// value.set_path(&["meta", "tags", "0"], "beta").unwrap();
+/// assert_eq!(
+///     value,
+///     toon!({
+///         users: [{ name: "Alice" }, { name: "Bob" }],
+///         meta: { tags: ["beta"] }
+///     })
+/// );
+///
+/// let err = value.get_path(&["users", "0", "email"]).unwrap_err();
+/// assert_eq!(err.to_string(), "Path not found: `/users/0` has no `email`");
+/// ```
+pub trait ValueExt {
+    /// Look up a value by JSON pointer.
+    ///
+    /// The empty pointer refers to the whole value. Returns `None` if the
+    /// pointer is malformed or does not resolve.
+    fn pointer(&self, pointer: &str) -> Option<&Value>;
+
+    /// Mutable version of [`pointer`](ValueExt::pointer).
+    fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value>;
+
+    /// Look up a value by path segments.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::PathNotFound`] if a key or index is missing, and
+    /// [`Error::PathTypeMismatch`] if a segment reaches a scalar or a
+    /// non-numeric segment reaches an array.
+    fn get_path<S: AsRef<str>>(&self, path: &[S]) -> Result<&Value>;
+
+    /// Set the value at `path`, returning the value it replaces.
+    ///
+    /// Missing containers along the path are created: an array when the
+    /// segment that indexes them is numeric, an object otherwise. `null`
+    /// values on the way are replaced in the same manner. An array index
+    /// equal to the array length, or `-`, appends. Nothing is modified when
+    /// an error is returned.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::PathNotFound`] for an index past the end of an array, and
+    /// [`Error::PathTypeMismatch`] as for [`get_path`](ValueExt::get_path).
+    fn set_path<S: AsRef<str>>(&mut self, path: &[S], value: impl ToToon) -> Result<Option<Value>>;
+
+    /// Remove and return the value at `path`.
+    ///
+    /// Object entries keep the order of the remaining keys, and later array
+    /// elements shift down.
+    ///
+    /// # Errors
+    ///
+    /// As for [`get_path`](ValueExt::get_path), and [`Error::InvalidPath`]
+    /// for the empty path.
+    fn remove_path<S: AsRef<str>>(&mut self, path: &[S]) -> Result<Value>;
+
+    /// Select nodes with a JSONPath-like [`Query`], in document order.
+    ///
+    /// ```
+    /// use toon_macro::{toon, ValueExt};
+    ///
+    /// let value = toon!({ users: [{ name: "Alice", role: "admin" }, { name: "Bob" }] });
+    /// let names = value.query("$.users[?(@.role == 'admin')].name").unwrap();
+    /// assert_eq!(names, [&toon!("Alice")]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// [`Error::InvalidQuery`] if `query` does not parse.
+    fn query(&self, query: &str) -> Result<Vec<&Value>>;
+
+    /// Apply an RFC 7396 merge patch.
+    ///
+    /// Object members of `patch` are merged recursively, `null` members
+    /// remove the key, and any other patch replaces the value.
+    ///
+    /// ```
+    /// use toon_macro::{toon, ValueExt};
+    ///
+    /// let mut doc = toon!({ title: "Draft", author: { name: "Al", email: "al@x" } });
+    /// doc.merge_patch(&toon!({ title: "Final", author: { email: null } }));
+    /// assert_eq!(doc, toon!({ title: "Final", author: { name: "Al" } }));
+    /// ```
+    fn merge_patch(&mut self, patch: &Value);
+
+    /// Apply an RFC 6902 [`Patch`] atomically: either every operation
+    /// succeeds or the value is left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// The path errors of the failing operation, [`Error::InvalidPath`] for
+    /// a malformed pointer or a move into a child of the moved value, and
+    /// [`Error::PatchTestFailed`] for a failed `test`.
+    fn apply_patch(&mut self, patch: &Patch) -> Result<()>;
+
+    /// Convert the value at `field` with [`FromToon`](crate::FromToon).
+    ///
+    /// The bound is the deprecated
+    /// [`FromToonValue`](crate::table::FromToonValue), which every
+    /// `FromToon` type implements, so types written against 0.1 can be
+    /// required too.
+    ///
+    /// `field` is a key of this object, or a JSON pointer when it starts
+    /// with `/`. The same applies to the typed getters below.
+    ///
+    /// ```
+    /// use toon_macro::{toon, ValueExt};
+    ///
+    /// let value = toon!({ user: { name: "Alice", age: "thirty" } });
+    /// assert_eq!(value.get_str("/user/name").unwrap(), "Alice");
+    ///
+    /// let err = value.require::<u32>("/user/age").unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Invalid value type: expected u32, got string \"thirty\" at `/user/age`"
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The errors of [`get_path`](ValueExt::get_path) if `field` does not
+    /// resolve, and the conversion error, with the location of the value
+    /// added, if it does not convert.
+    #[allow(deprecated)]
+    fn require<T: crate::table::FromToonValue>(&self, field: &str) -> Result<T>;
+
+    /// The string at `field`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`require`](ValueExt::require), with [`Error::InvalidType`]
+    /// if the value is not a string.
+    fn get_str(&self, field: &str) -> Result<&str>;
+
+    /// The boolean at `field`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`require`](ValueExt::require).
+    fn get_bool(&self, field: &str) -> Result<bool>;
+
+    /// The number at `field` as an `i64`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`require`](ValueExt::require).
+    fn get_i64(&self, field: &str) -> Result<i64>;
+
+    /// The number at `field` as a `u64`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`require`](ValueExt::require).
+    fn get_u64(&self, field: &str) -> Result<u64>;
+
+    /// The number at `field` as an `f64`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`require`](ValueExt::require).
+    fn get_f64(&self, field: &str) -> Result<f64>;
+
+    /// The elements of the array at `field`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`get_str`](ValueExt::get_str).
+    fn get_array(&self, field: &str) -> Result<&Vec<Value>>;
+
+    /// The members of the object at `field`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`get_str`](ValueExt::get_str).
+    fn get_object(&self, field: &str) -> Result<&Map<String, Value>>;
+}
+
+impl ValueExt for Value {
+    fn pointer(&self, pointer: &str) -> Option<&Value> {
+        parse_pointer(pointer)?
+            .iter()
+            .try_fold(self, |value, segment| child(value, segment))
+    }
+
+    fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        parse_pointer(pointer)?
+            .iter()
+            .try_fold(self, |value, segment| child_mut(value, segment))
+    }
+
+    fn get_path<S: AsRef<str>>(&self, path: &[S]) -> Result<&Value> {
+        let mut value = self;
+        for (depth, segment) in path.iter().enumerate() {
+            value = child(value, segment.as_ref())
+                .ok_or_else(|| step_error(value, &path[..depth], segment.as_ref()))?;
+        }
+        Ok(value)
+    }
+
+    fn set_path<S: AsRef<str>>(&mut self, path: &[S], value: impl ToToon) -> Result<Option<Value>> {
+        let mut current = self;
+        for (depth, segment) in path.iter().enumerate() {
+            let segment = segment.as_ref();
+            let rest = &path[depth + 1..];
+            match current {
+                Value::Object(map) => match map.get_index_of(segment) {
+                    Some(index) if rest.is_empty() => {
+                        return Ok(Some(std::mem::replace(&mut map[index], value.into_toon())));
+                    }
+                    Some(index) => current = &mut map[index],
+                    None => {
+                        check_new(path, depth + 1)?;
+                        map.insert(segment.to_string(), build(rest, value));
+                        return Ok(None);
+                    }
+                },
+                Value::Array(elements) => {
+                    let len = elements.len();
+                    match parse_index(segment, len) {
+                        Some(index) if index < len && rest.is_empty() => {
+                            return Ok(Some(std::mem::replace(
+                                &mut elements[index],
+                                value.into_toon(),
+                            )));
+                        }
+                        Some(index) if index < len => current = &mut elements[index],
+                        Some(index) if index == len => {
+                            check_new(path, depth + 1)?;
+                            elements.push(build(rest, value));
+                            return Ok(None);
+                        }
+                        Some(_) => return Err(not_found(&path[..depth], segment)),
+                        None => return Err(mismatch("array", &path[..depth], segment)),
+                    }
+                }
+                Value::Null => {
+                    check_new(path, depth)?;
+                    *current = build(&path[depth..], value);
+                    return Ok(None);
+                }
+                other => return Err(mismatch(kind(other), &path[..depth], segment)),
+            }
+        }
+        Ok(Some(std::mem::replace(current, value.into_toon())))
+    }
+
+    fn remove_path<S: AsRef<str>>(&mut self, path: &[S]) -> Result<Value> {
+        let Some((last, parents)) = path.split_last() else {
+            return Err(Error::InvalidPath("cannot remove the root value".into()));
+        };
+        let parent = lookup_mut(self, parents)?;
+        let last = last.as_ref();
+        let removed = match &mut *parent {
+            Value::Object(map) => map.shift_remove(last),
+            Value::Array(elements) => match parse_index(last, elements.len()) {
+                Some(index) if index < elements.len() => Some(elements.remove(index)),
+                _ => None,
+            },
+            _ => None,
+        };
+        removed.ok_or_else(|| step_error(parent, parents, last))
+    }
+
+    fn query(&self, query: &str) -> Result<Vec<&Value>> {
+        Ok(Query::parse(query)?.select(self))
+    }
+
+    fn merge_patch(&mut self, patch: &Value) {
+        super::patch::merge(self, patch);
+    }
+
+    fn apply_patch(&mut self, patch: &Patch) -> Result<()> {
+        super::patch::apply(self, patch)
+    }
+
+    #[allow(deprecated)]
+    fn require<T: crate::table::FromToonValue>(&self, field: &str) -> Result<T> {
+        let (value, pointer) = field_value(self, field)?;
+        crate::internal::match_bind(value, &pointer)
+    }
+
+    fn get_str(&self, field: &str) -> Result<&str> {
+        match field_value(self, field)? {
+            (Value::String(s), _) => Ok(s),
+            (other, pointer) => Err(Error::invalid_value("string", other).at(&pointer)),
+        }
+    }
+
+    fn get_bool(&self, field: &str) -> Result<bool> {
+        self.require(field)
+    }
+
+    fn get_i64(&self, field: &str) -> Result<i64> {
+        self.require(field)
+    }
+
+    fn get_u64(&self, field: &str) -> Result<u64> {
+        self.require(field)
+    }
+
+    fn get_f64(&self, field: &str) -> Result<f64> {
+        self.require(field)
+    }
+
+    fn get_array(&self, field: &str) -> Result<&Vec<Value>> {
+        match field_value(self, field)? {
+            (Value::Array(items), _) => Ok(items),
+            (other, pointer) => Err(Error::invalid_value("array", other).at(&pointer)),
+        }
+    }
+
+    fn get_object(&self, field: &str) -> Result<&Map<String, Value>> {
+        match field_value(self, field)? {
+            (Value::Object(map), _) => Ok(map),
+            (other, pointer) => Err(Error::invalid_value("object", other).at(&pointer)),
+        }
+    }
+}
+
+/// Resolve the `field` argument of the typed getters, returning the value
+/// and its JSON pointer.
+fn field_value<'v>(value: &'v Value, field: &str) -> Result<(&'v Value, String)> {
+    let path = if field.starts_with('/') {
+        parse_pointer(field).expect("pointer starts with `/`")
+    } else {
+        vec![field.to_string()]
+    };
+    Ok((value.get_path(&path)?, to_pointer(&path)))
+}
+
+/// Mutable version of [`ValueExt::get_path`].
+pub(super) fn lookup_mut<'v, S: AsRef<str>>(
+    value: &'v mut Value,
+    path: &[S],
+) -> Result<&'v mut Value> {
+    let mut value = value;
+    for (depth, segment) in path.iter().enumerate() {
+        let segment = segment.as_ref();
+        if child(value, segment).is_none() {
+            return Err(step_error(value, &path[..depth], segment));
+        }
+        value = child_mut(value, segment).expect("child exists");
+    }
+    Ok(value)
+}
+
+/// Split a JSON pointer into unescaped segments.
+pub(super) fn parse_pointer(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    let segments = pointer.strip_prefix('/')?;
+    Some(
+        segments
+            .split('/')
+            .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+    )
+}
+
+/// Render path segments as a JSON pointer.
+pub(crate) fn to_pointer<S: AsRef<str>>(path: &[S]) -> String {
+    let mut pointer = String::new();
+    for segment in path {
+        pointer.push('/');
+        pointer.push_str(&segment.as_ref().replace('~', "~0").replace('/', "~1"));
+    }
+    pointer
+}
+
+/// The array index named by `segment`: a decimal number without leading
+/// zeros, or `-` for the position past the end.
+pub(super) fn parse_index(segment: &str, len: usize) -> Option<usize> {
+    if segment == "-" {
+        return Some(len);
+    }
+    let leading_zero = segment.len() > 1 && segment.starts_with('0');
+    if leading_zero || !segment.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    segment.parse().ok()
+}
+
+fn child<'v>(value: &'v Value, segment: &str) -> Option<&'v Value> {
+    match value {
+        Value::Object(map) => map.get(segment),
+        Value::Array(elements) => elements.get(parse_index(segment, elements.len())?),
+        _ => None,
+    }
+}
+
+fn child_mut<'v>(value: &'v mut Value, segment: &str) -> Option<&'v mut Value> {
+    match value {
+        Value::Object(map) => map.get_mut(segment),
+        Value::Array(elements) => {
+            let index = parse_index(segment, elements.len())?;
+            elements.get_mut(index)
+        }
+        _ => None,
+    }
+}
+
+/// The error for `segment` not resolving in `value`, found at `path`.
+fn step_error<S: AsRef<str>>(value: &Value, path: &[S], segment: &str) -> Error {
+    match value {
+        Value::Object(_) => not_found(path, segment),
+        Value::Array(_) if parse_index(segment, 0).is_some() => not_found(path, segment),
+        other => mismatch(kind(other), path, segment),
+    }
+}
+
+pub(super) fn not_found<S: AsRef<str>>(path: &[S], segment: &str) -> Error {
+    Error::PathNotFound {
+        path: to_pointer(path),
+        segment: segment.to_string(),
+    }
+}
+
+pub(super) fn mismatch<S: AsRef<str>>(got: &'static str, path: &[S], segment: &str) -> Error {
+    Error::PathTypeMismatch {
+        path: to_pointer(path),
+        segment: segment.to_string(),
+        got,
+    }
+}
+
+/// Check that containers can be created for `path[from..]`: a new array
+/// only accepts index `0` or `-`.
+fn check_new<S: AsRef<str>>(path: &[S], from: usize) -> Result<()> {
+    for depth in from..path.len() {
+        let segment = path[depth].as_ref();
+        if parse_index(segment, 0).is_some_and(|index| index != 0) {
+            return Err(not_found(&path[..depth], segment));
+        }
+    }
+    Ok(())
+}
+
+/// Build `value` nested in new containers for `path`.
+fn build<S: AsRef<str>>(path: &[S], value: impl ToToon) -> Value {
+    let Some((first, rest)) = path.split_first() else {
+        return value.into_toon();
+    };
+    let inner = build(rest, value);
+    if parse_index(first.as_ref(), 0).is_some() {
+        Value::Array(vec![inner])
+    } else {
+        let mut map = Map::new();
+        map.insert(first.as_ref().to_string(), inner);
+        Value::Object(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Value {
+        toon!({
+            users: [
+                { name: "Alice", role: "admin" },
+                { name: "Bob", role: "user" }
+            ],
+            "a/b": { "c~d": 1 },
+            count: 2
+        })
+    }
+
+    #[test]
+    fn test_pointer() {
+        let value = sample();
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(value.pointer("/users/1/name"), Some(&toon!("Bob")));
+        assert_eq!(value.pointer("/a~1b/c~0d"), Some(&toon!(1)));
+        assert_eq!(value.pointer("/users/2"), None);
+        assert_eq!(value.pointer("/users/01"), None);
+        assert_eq!(value.pointer("/users/-"), None);
+        assert_eq!(value.pointer("/count/x"), None);
+        assert_eq!(value.pointer("users"), None);
+    }
+
+    #[test]
+    fn test_pointer_mut() {
+        let mut value = sample();
+        *value.pointer_mut("/users/0/role").unwrap() = toon!("owner");
+        assert_eq!(value.pointer("/users/0/role"), Some(&toon!("owner")));
+        assert!(value.pointer_mut("/nope").is_none());
+    }
+
+    #[test]
+    fn test_get_path_errors() {
+        let value = sample();
+        let err = value.get_path(&["users", "5"]).unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::PathNotFound { path, segment } if path == "/users" && segment == "5"
+        ));
+
+        let err = value.get_path(&["users", "name"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Path type mismatch: cannot index array at `/users` with `name`"
+        );
+
+        let err = value.get_path(&["count", "x"]).unwrap_err();
+        assert!(matches!(err, Error::PathTypeMismatch { got: "number", .. }));
+
+        let err = value.get_path(&["missing"]).unwrap_err();
+        assert_eq!(err.to_string(), "Path not found: the root has no `missing`");
+    }
+
+    #[test]
+    fn test_set_path() {
+        let mut value = sample();
+        let old = value.set_path(&["users", "0", "role"], "owner").unwrap();
+        assert_eq!(old, Some(toon!("admin")));
+
+        assert_eq!(
+            value.set_path(&["users", "-", "name"], "Carol").unwrap(),
+            None
+        );
+        assert_eq!(value.pointer("/users/2"), Some(&toon!({ name: "Carol" })));
+
+        value.set_path(&["config", "ports", "0"], 8080).unwrap();
+        assert_eq!(value.pointer("/config"), Some(&toon!({ ports: [8080] })));
+
+        let mut value = Value::Null;
+        value.set_path(&["a", "b"], true).unwrap();
+        assert_eq!(value, toon!({ a: { b: true } }));
+
+        let mut value = toon!(1);
+        assert_eq!(value.set_path::<&str>(&[], "x").unwrap(), Some(toon!(1)));
+        assert_eq!(value, toon!("x"));
+    }
+
+    #[test]
+    fn test_set_path_errors_leave_value_unchanged() {
+        let mut value = sample();
+        let err = value.set_path(&["users", "3"], 1).unwrap_err();
+        assert!(matches!(err, Error::PathNotFound { ref segment, .. } if segment == "3"));
+
+        let err = value.set_path(&["new", "list", "2"], 1).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::PathNotFound { ref path, .. } if path == "/new/list"
+        ));
+
+        let err = value.set_path(&["count", "x"], 1).unwrap_err();
+        assert!(matches!(err, Error::PathTypeMismatch { got: "number", .. }));
+        assert_eq!(value, sample());
+    }
+
+    #[test]
+    fn test_remove_path() {
+        let mut value = sample();
+        assert_eq!(
+            value.remove_path(&["users", "0", "name"]).unwrap(),
+            toon!("Alice")
+        );
+        assert_eq!(
+            value.remove_path(&["users", "0"]).unwrap(),
+            toon!({ role: "admin" })
+        );
+        assert_eq!(value.pointer("/users/0/name"), Some(&toon!("Bob")));
+
+        value.remove_path(&["a/b"]).unwrap();
+        let keys: Vec<_> = value.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["users", "count"]);
+
+        assert!(matches!(
+            value.remove_path(&["users", "1"]),
+            Err(Error::PathNotFound { .. })
+        ));
+        assert!(matches!(
+            value.remove_path::<&str>(&[]),
+            Err(Error::InvalidPath(_))
+        ));
+    }
+
+    #[test]
+    fn test_typed_getters() {
+        let value = sample();
+        assert_eq!(value.get_str("/users/0/name").unwrap(), "Alice");
+        assert_eq!(value.get_i64("count").unwrap(), 2);
+        assert_eq!(value.get_u64("count").unwrap(), 2);
+        assert_eq!(value.get_f64("/a~1b/c~0d").unwrap(), 1.0);
+        assert_eq!(value.get_array("users").unwrap().len(), 2);
+        assert!(value.get_object("a/b").unwrap().contains_key("c~d"));
+        assert_eq!(value.require::<Vec<String>>("/users/1/role").ok(), None);
+        assert_eq!(
+            value.require::<String>("/users/1/role").unwrap(),
+            "user".to_string()
+        );
+
+        let err = value.get_str("count").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid value type: expected string, got number 2 at `/count`"
+        );
+        let err = value.get_object("users").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid value type: expected object, got array of 2 elements at `/users`"
+        );
+        let err = value.get_bool("/users/0").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid value type: expected bool, got object with 2 keys at `/users/0`"
+        );
+        let err = value.get_i64("/users/0/age").unwrap_err();
+        assert_eq!(err.to_string(), "Path not found: `/users/0` has no `age`");
+        let err = toon!({ n: 300 }).require::<u8>("n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Conversion error: number is out of range for u8 at `/n`"
+        );
+    }
+
+    #[test]
+    fn test_to_pointer() {
+        assert_eq!(to_pointer::<&str>(&[]), "");
+        assert_eq!(to_pointer(&["a/b", "c~d", "0"]), "/a~1b/c~0d/0");
+    }
+}
@@ -0,0 +1,785 @@
+//! A small JSONPath-like query language over values.
+//!
+//! A query starts at the root `$` and applies segments in turn, each one
+//! selecting nodes out of the nodes selected so far:
+//!
+//! | Syntax | Selects |
+//! |--------|---------|
+//! | `.name`, `['name']` | the member `name` of objects |
+//! | `.*`, `[*]` | every member or element |
+//! | `[0]`, `[-1]` | an array element, counting from the end when negative |
+//! | `[1:3]`, `[::-1]` | a slice of an array, as `start:end:step` |
+//! | `[?(@.age >= 18)]` | members or elements for which the filter holds |
+//! | `['a', 0, 1:]` | the union of several selectors |
+//! | `..name`, `..*`, `..[0]` | the same selectors on every descendant |
+//!
+//! Filters compare singular paths, relative to the tested node (`@.a.b`,
+//! `@['a'][0]`) or to the root (`$.limit`), and literals (numbers, quoted
+//! strings, `true`, `false`, `null`) with `==`, `!=`, `<`, `<=`, `>`, `>=`;
+//! combine tests with `&&`, `||`, `!` and parentheses; and test that a path
+//! exists by naming it alone (`[?(@.email)]`). Numbers compare by value and
+//! only numbers and strings are ordered. A path that does not resolve equals
+//! only another path that does not resolve.
+
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use super::{Value, compare_numbers, values_equal};
+use crate::{Error, Number, Result};
+
+/// A parsed query, reusable across values.
+///
+/// [`ValueExt::query`](super::ValueExt::query) parses and runs a query in
+/// one call.
+///
+/// # Example
+///
+/// ```
+/// use toon_macro::{toon, value::Query};
+///
+/// let response = toon!({
+///     users: [
+///         { name: "Alice", role: "admin" },
+///         { name: "Bob", role: "user" },
+///         { name: "Carol", role: "admin" }
+///     ]
+/// });
+///
+/// let admins = Query::parse("$.users[?(@.role == 'admin')].name").unwrap();
+/// assert_eq!(admins.select(&response), [&toon!("Alice"), &toon!("Carol")]);
+///
+/// let err = Query::parse("$.users[?(@.role = 'admin')]").unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "Invalid query: expected `)` at position 17 in `$.users[?(@.role = 'admin')]`"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    segments: Vec<Segment>,
+}
+
+impl Query {
+    /// Parse a query.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::InvalidQuery`] with the byte position of the problem.
+    pub fn parse(query: &str) -> Result<Self> {
+        Parser { query, pos: 0 }.query()
+    }
+
+    /// Select the matching nodes of `value`, in document order.
+    pub fn select<'v>(&self, value: &'v Value) -> Vec<&'v Value> {
+        let mut nodes = vec![value];
+        for segment in &self.segments {
+            let mut selected = Vec::new();
+            for node in nodes {
+                match segment {
+                    Segment::Child(selectors) => {
+                        for selector in selectors {
+                            selector.select(node, value, &mut selected);
+                        }
+                    }
+                    Segment::Descendant(selectors) => {
+                        let mut descendants = Vec::new();
+                        collect_descendants(node, &mut descendants);
+                        for descendant in descendants {
+                            for selector in selectors {
+                                selector.select(descendant, value, &mut selected);
+                            }
+                        }
+                    }
+                }
+            }
+            nodes = selected;
+        }
+        nodes
+    }
+}
+
+impl FromStr for Query {
+    type Err = Error;
+
+    fn from_str(query: &str) -> Result<Self> {
+        Query::parse(query)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Child(Vec<Selector>),
+    Descendant(Vec<Selector>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice {
+        start: Option<i64>,
+        end: Option<i64>,
+        step: Option<i64>,
+    },
+    Filter(Filter),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Or(Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Exists(SingularPath),
+    Compare(Operand, Op, Operand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Path(SingularPath),
+    Literal(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A path inside a filter, selecting at most one node.
+#[derive(Debug, Clone, PartialEq)]
+struct SingularPath {
+    from_root: bool,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Name(String),
+    Index(i64),
+}
+
+impl Selector {
+    fn select<'v>(&self, node: &'v Value, root: &'v Value, out: &mut Vec<&'v Value>) {
+        match (self, node) {
+            (Selector::Name(name), Value::Object(map)) => out.extend(map.get(name)),
+            (Selector::Wildcard, _) => out.extend(children(node)),
+            (Selector::Index(index), Value::Array(elements)) => {
+                out.extend(element(elements, *index));
+            }
+            (Selector::Slice { start, end, step }, Value::Array(elements)) => {
+                let step = step.unwrap_or(1);
+                let len = elements.len() as i64;
+                let bound = |index: i64| if index < 0 { len + index } else { index };
+                // Bounds are clamped into the array, and stepping goes
+                // through `step_by`, so no arithmetic can overflow.
+                let stride = usize::try_from(step.unsigned_abs()).unwrap_or(usize::MAX);
+                if step > 0 {
+                    let lower = bound(start.unwrap_or(0)).clamp(0, len) as usize;
+                    let upper = bound(end.unwrap_or(len)).clamp(0, len) as usize;
+                    out.extend((lower..upper).step_by(stride).map(|i| &elements[i]));
+                } else if step < 0 {
+                    let upper = bound(start.unwrap_or(len - 1)).clamp(-1, len - 1);
+                    let lower = end.map_or(-1, |end| bound(end).clamp(-1, len - 1));
+                    let indexes = (lower + 1) as usize..(upper + 1) as usize;
+                    out.extend(indexes.rev().step_by(stride).map(|i| &elements[i]));
+                }
+            }
+            (Selector::Filter(filter), _) => {
+                out.extend(children(node).filter(|child| filter.test(child, root)));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Filter {
+    fn test(&self, current: &Value, root: &Value) -> bool {
+        match self {
+            Filter::Or(a, b) => a.test(current, root) || b.test(current, root),
+            Filter::And(a, b) => a.test(current, root) && b.test(current, root),
+            Filter::Not(filter) => !filter.test(current, root),
+            Filter::Exists(path) => path.resolve(current, root).is_some(),
+            Filter::Compare(a, op, b) => {
+                let a = a.resolve(current, root);
+                let b = b.resolve(current, root);
+                match op {
+                    Op::Eq => equal(a, b),
+                    Op::Ne => !equal(a, b),
+                    Op::Lt => less(a, b),
+                    Op::Le => less(a, b) || equal(a, b),
+                    Op::Gt => less(b, a),
+                    Op::Ge => less(b, a) || equal(a, b),
+                }
+            }
+        }
+    }
+}
+
+impl Operand {
+    fn resolve<'v>(&'v self, current: &'v Value, root: &'v Value) -> Option<&'v Value> {
+        match self {
+            Operand::Path(path) => path.resolve(current, root),
+            Operand::Literal(value) => Some(value),
+        }
+    }
+}
+
+impl SingularPath {
+    fn resolve<'v>(&self, current: &'v Value, root: &'v Value) -> Option<&'v Value> {
+        let start = if self.from_root { root } else { current };
+        self.steps
+            .iter()
+            .try_fold(start, |node, step| match (step, node) {
+                (Step::Name(name), Value::Object(map)) => map.get(name),
+                (Step::Index(index), Value::Array(elements)) => element(elements, *index),
+                _ => None,
+            })
+    }
+}
+
+fn children(node: &Value) -> Box<dyn Iterator<Item = &Value> + '_> {
+    match node {
+        Value::Object(map) => Box::new(map.values()),
+        Value::Array(elements) => Box::new(elements.iter()),
+        _ => Box::new(std::iter::empty()),
+    }
+}
+
+/// `node` followed by all of its descendants, depth first.
+fn collect_descendants<'v>(node: &'v Value, out: &mut Vec<&'v Value>) {
+    out.push(node);
+    for child in children(node) {
+        collect_descendants(child, out);
+    }
+}
+
+fn element(elements: &[Value], index: i64) -> Option<&Value> {
+    let index = if index < 0 {
+        elements.len().checked_sub(index.unsigned_abs() as usize)?
+    } else {
+        index as usize
+    };
+    elements.get(index)
+}
+
+fn equal(a: Option<&Value>, b: Option<&Value>) -> bool {
+    match (a, b) {
+        (None, None) => true,
+        (Some(a), Some(b)) => values_equal(a, b),
+        _ => false,
+    }
+}
+
+fn less(a: Option<&Value>, b: Option<&Value>) -> bool {
+    match (a, b) {
+        (Some(Value::Number(a)), Some(Value::Number(b))) => {
+            compare_numbers(a, b) == Some(Ordering::Less)
+        }
+        (Some(Value::String(a)), Some(Value::String(b))) => a < b,
+        _ => false,
+    }
+}
+
+struct Parser<'q> {
+    query: &'q str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error_at(&self, position: usize, message: impl Into<String>) -> Error {
+        Error::InvalidQuery {
+            query: self.query.to_string(),
+            position,
+            message: message.into(),
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        self.error_at(self.pos, message)
+    }
+
+    fn rest(&self) -> &str {
+        &self.query[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`", token)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start_matches([' ', '\t', '\n', '\r']);
+        self.pos = self.query.len() - trimmed.len();
+    }
+
+    fn query(mut self) -> Result<Query> {
+        self.skip_whitespace();
+        self.expect("$")?;
+        let segments = self.segments()?;
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(Query { segments }),
+            Some(c) => Err(self.error(format!("unexpected `{}`", c))),
+        }
+    }
+
+    fn segments(&mut self) -> Result<Vec<Segment>> {
+        let mut segments = Vec::new();
+        loop {
+            if self.eat("..") {
+                let selectors = match self.peek() {
+                    Some('[') => self.bracket()?,
+                    _ => vec![self.dot_selector()?],
+                };
+                segments.push(Segment::Descendant(selectors));
+            } else if self.eat(".") {
+                segments.push(Segment::Child(vec![self.dot_selector()?]));
+            } else if self.peek() == Some('[') {
+                segments.push(Segment::Child(self.bracket()?));
+            } else {
+                return Ok(segments);
+            }
+        }
+    }
+
+    /// The selector after `.` or `..`: a member name or `*`.
+    fn dot_selector(&mut self) -> Result<Selector> {
+        if self.eat("*") {
+            Ok(Selector::Wildcard)
+        } else {
+            self.name().map(Selector::Name)
+        }
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let start = self.pos;
+        let first = self.peek().filter(|&c| c == '_' || c.is_alphabetic());
+        if first.is_none() {
+            return Err(self.error("expected a member name"));
+        }
+        while self.peek().is_some_and(|c| c == '_' || c.is_alphanumeric()) {
+            self.bump();
+        }
+        Ok(self.query[start..self.pos].to_string())
+    }
+
+    fn bracket(&mut self) -> Result<Vec<Selector>> {
+        self.expect("[")?;
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_whitespace();
+            selectors.push(self.selector()?);
+            self.skip_whitespace();
+            if !self.eat(",") {
+                self.expect("]")?;
+                return Ok(selectors);
+            }
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector> {
+        match self.peek() {
+            Some('\'' | '"') => self.string().map(Selector::Name),
+            Some('*') => {
+                self.bump();
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.bump();
+                self.or().map(Selector::Filter)
+            }
+            Some(':' | '-' | '0'..='9') => self.index_or_slice(),
+            _ => Err(self.error("expected a selector")),
+        }
+    }
+
+    fn index_or_slice(&mut self) -> Result<Selector> {
+        let start = self.optional_integer()?;
+        self.skip_whitespace();
+        if !self.eat(":") {
+            return start
+                .map(Selector::Index)
+                .ok_or_else(|| self.error("expected an index"));
+        }
+        self.skip_whitespace();
+        let end = self.optional_integer()?;
+        self.skip_whitespace();
+        let step = if self.eat(":") {
+            self.skip_whitespace();
+            self.optional_integer()?
+        } else {
+            None
+        };
+        Ok(Selector::Slice { start, end, step })
+    }
+
+    fn optional_integer(&mut self) -> Result<Option<i64>> {
+        match self.peek() {
+            Some('-' | '0'..='9') => self.integer().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn integer(&mut self) -> Result<i64> {
+        let start = self.pos;
+        self.eat("-");
+        let digits = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+        }
+        if self.pos == digits {
+            return Err(self.error("expected digits"));
+        }
+        self.query[start..self.pos]
+            .parse()
+            .map_err(|_| self.error_at(start, "integer out of range"))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let start = self.pos;
+        let quote = self.bump().expect("caller checked for a quote");
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error_at(start, "unterminated string")),
+                Some(c) if c == quote => return Ok(out),
+                Some('\\') => {
+                    let escape = self.pos;
+                    let c = match self.bump() {
+                        Some(c @ ('\\' | '/' | '\'' | '"')) => c,
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let code = self
+                                .rest()
+                                .get(..4)
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .and_then(char::from_u32);
+                            let Some(c) = code else {
+                                return Err(self.error_at(escape, "invalid unicode escape"));
+                            };
+                            self.pos += 4;
+                            c
+                        }
+                        _ => return Err(self.error_at(escape, "invalid escape")),
+                    };
+                    out.push(c);
+                }
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn or(&mut self) -> Result<Filter> {
+        let mut filter = self.and()?;
+        loop {
+            self.skip_whitespace();
+            if !self.eat("||") {
+                return Ok(filter);
+            }
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+    }
+
+    fn and(&mut self) -> Result<Filter> {
+        let mut filter = self.unary()?;
+        loop {
+            self.skip_whitespace();
+            if !self.eat("&&") {
+                return Ok(filter);
+            }
+            filter = Filter::And(Box::new(filter), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Filter> {
+        self.skip_whitespace();
+        if self.eat("!") {
+            return Ok(Filter::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let filter = self.or()?;
+            self.skip_whitespace();
+            self.expect(")")?;
+            return Ok(filter);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Filter> {
+        let start = self.pos;
+        let left = self.operand()?;
+        self.skip_whitespace();
+        let Some(op) = self.op() else {
+            return match left {
+                Operand::Path(path) => Ok(Filter::Exists(path)),
+                Operand::Literal(_) => Err(self.error_at(start, "expected a path to test")),
+            };
+        };
+        self.skip_whitespace();
+        let right = self.operand()?;
+        Ok(Filter::Compare(left, op, right))
+    }
+
+    fn op(&mut self) -> Option<Op> {
+        let ops = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+        ops.into_iter()
+            .find(|(token, _)| self.eat(token))
+            .map(|(_, op)| op)
+    }
+
+    fn operand(&mut self) -> Result<Operand> {
+        match self.peek() {
+            Some('@') => {
+                self.bump();
+                self.singular_path(false).map(Operand::Path)
+            }
+            Some('$') => {
+                self.bump();
+                self.singular_path(true).map(Operand::Path)
+            }
+            Some('\'' | '"') => Ok(Operand::Literal(Value::String(self.string()?))),
+            Some('-' | '0'..='9') => self.number().map(Operand::Literal),
+            _ => {
+                let start = self.pos;
+                let literal = match self.name() {
+                    Ok(word) if word == "true" => Value::Bool(true),
+                    Ok(word) if word == "false" => Value::Bool(false),
+                    Ok(word) if word == "null" => Value::Null,
+                    _ => return Err(self.error_at(start, "expected a path or a literal")),
+                };
+                Ok(Operand::Literal(literal))
+            }
+        }
+    }
+
+    fn singular_path(&mut self, from_root: bool) -> Result<SingularPath> {
+        let mut steps = Vec::new();
+        loop {
+            if self.rest().starts_with("..") {
+                return Err(self.error("descendant segments are not allowed in filter paths"));
+            } else if self.eat(".") {
+                steps.push(Step::Name(self.name()?));
+            } else if self.eat("[") {
+                self.skip_whitespace();
+                let step = match self.peek() {
+                    Some('\'' | '"') => Step::Name(self.string()?),
+                    Some('-' | '0'..='9') => Step::Index(self.integer()?),
+                    _ => return Err(self.error("expected a name or an index")),
+                };
+                steps.push(step);
+                self.skip_whitespace();
+                self.expect("]")?;
+            } else {
+                return Ok(SingularPath { from_root, steps });
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let start = self.pos;
+        self.eat("-");
+        let mut float = false;
+        while let Some(c) = self.peek() {
+            match c {
+                '0'..='9' => {}
+                '.' | 'e' | 'E' => float = true,
+                '+' | '-' if float => {}
+                _ => break,
+            }
+            self.bump();
+        }
+        let text = &self.query[start..self.pos];
+        let number = if float {
+            text.parse().ok().map(Number::F64)
+        } else {
+            text.parse()
+                .map(Number::I64)
+                .or_else(|_| text.parse().map(Number::F64))
+                .ok()
+        };
+        number
+            .map(Value::Number)
+            .ok_or_else(|| self.error_at(start, "invalid number"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ValueExt;
+
+    fn store() -> Value {
+        toon!({
+            store: {
+                books: [
+                    { title: "Sayings", author: "Rees", price: 8.95, tags: ["quotes"] },
+                    { title: "Sword", author: "Waugh", price: 12.99 },
+                    { title: "Moby Dick", author: "Melville", price: 8.99, isbn: "0-553" },
+                    { title: "Rings", author: "Tolkien", price: 22u64, isbn: "0-395" }
+                ],
+                bicycle: { color: "red", price: 399 }
+            },
+            limit: 10
+        })
+    }
+
+    fn titles(query: &str) -> Vec<String> {
+        store()
+            .query(query)
+            .unwrap()
+            .into_iter()
+            .map(|title| title.as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_names_and_wildcards() {
+        let value = store();
+        assert_eq!(value.query("$").unwrap(), [&value]);
+        assert_eq!(
+            value.query("$.store.bicycle['color']").unwrap(),
+            [&toon!("red")]
+        );
+        assert_eq!(value.query("$.store.*").unwrap().len(), 2);
+        assert_eq!(titles("$.store.books[*].title").len(), 4);
+        assert!(value.query("$.nothing.here").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_indexes_and_slices() {
+        assert_eq!(titles("$.store.books[0].title"), ["Sayings"]);
+        assert_eq!(titles("$.store.books[-1].title"), ["Rings"]);
+        assert!(titles("$.store.books[4].title").is_empty());
+        assert_eq!(titles("$.store.books[1:3].title"), ["Sword", "Moby Dick"]);
+        assert_eq!(titles("$.store.books[:-3].title"), ["Sayings"]);
+        assert_eq!(titles("$.store.books[::2].title"), ["Sayings", "Moby Dick"]);
+        assert_eq!(
+            titles("$.store.books[::-1].title"),
+            ["Rings", "Moby Dick", "Sword", "Sayings"]
+        );
+        assert_eq!(titles("$.store.books[0, -1].title"), ["Sayings", "Rings"]);
+    }
+
+    #[test]
+    fn test_slices_with_extreme_bounds() {
+        let value = toon!([1, 2, 3, 4]);
+        let query =
+            |q: &str| -> Vec<Value> { value.query(q).unwrap().into_iter().cloned().collect() };
+        let (min, max) = (i64::MIN, i64::MAX);
+
+        assert_eq!(query(&format!("$[1::{}]", max)), [toon!(2)]);
+        assert_eq!(query(&format!("$[::{}]", min)), [toon!(4)]);
+        assert_eq!(query(&format!("$[-2::{}]", min)), [toon!(3)]);
+        assert_eq!(query(&format!("$[{}:{}]", min, max)).len(), 4);
+        assert_eq!(query(&format!("$[{}:{}:-1]", max, min)).len(), 4);
+        assert_eq!(query(&format!("$[{}:{}:{}]", max, min, min)), [toon!(4)]);
+        assert_eq!(query(&format!("$[{}:{}:{}]", min, max, max)), [toon!(1)]);
+        assert!(query(&format!("$[{}:{}]", max, min)).is_empty());
+        assert!(query(&format!("$[{}]", min)).is_empty());
+        assert!(query(&format!("$[{}]", max)).is_empty());
+        assert!(query("$[0:4:0]").is_empty());
+        assert!(
+            toon!([])
+                .query(&format!("$[::{}]", min))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(value.query("$[::-9223372036854775809]").is_err());
+    }
+
+    #[test]
+    fn test_descendants() {
+        let value = store();
+        let prices = value.query("$..price").unwrap();
+        assert_eq!(prices.len(), 5);
+        assert_eq!(prices[4], &toon!(399));
+        assert_eq!(titles("$..books[1].title"), ["Sword"]);
+        assert_eq!(value.query("$..*").unwrap().len(), 26);
+    }
+
+    #[test]
+    fn test_filters() {
+        assert_eq!(
+            titles("$..books[?(@.price < 10)].title"),
+            ["Sayings", "Moby Dick"]
+        );
+        assert_eq!(titles("$..books[?@.isbn].title"), ["Moby Dick", "Rings"]);
+        assert_eq!(
+            titles("$..books[?(!@.isbn && @.price > 10)].title"),
+            ["Sword"]
+        );
+        assert_eq!(
+            titles("$..books[?(@.price >= $.limit)].title"),
+            ["Sword", "Rings"]
+        );
+        assert_eq!(titles("$..books[?(@.price == 22)].title"), ["Rings"]);
+        assert_eq!(
+            titles("$..books[?(@.author == \"Rees\" || @.tags[0] == 'x')].title"),
+            ["Sayings"]
+        );
+        assert_eq!(
+            titles("$..books[?(@['tags'][0] == 'quotes')].title"),
+            ["Sayings"]
+        );
+        assert_eq!(titles("$..books[?(@.missing == null)].title").len(), 0);
+        assert_eq!(titles("$..books[?(@.missing == @.other)].title").len(), 4);
+    }
+
+    #[test]
+    fn test_errors_point_into_query() {
+        let position = |query: &str| match Query::parse(query).unwrap_err() {
+            Error::InvalidQuery { position, .. } => position,
+            other => panic!("unexpected error: {other}"),
+        };
+        assert_eq!(position("users"), 0);
+        assert_eq!(position("$.users["), 8);
+        assert_eq!(position("$.users[?(@.name == 'x)]"), 20);
+        assert_eq!(position("$.users[?(@..name)]"), 11);
+        assert_eq!(position("$.users[99999999999999999999]"), 8);
+        assert_eq!(position("$.users[?(1)]"), 10);
+        assert_eq!(position("$.users] "), 7);
+
+        let err = Query::parse("$..").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid query: expected a member name at position 3 in `$..`"
+        );
+    }
+}
@@ -0,0 +1,353 @@
+//! A `serde::Serializer` producing [`Value`]s directly.
+
+use serde::ser::{self, Serialize};
+
+use crate::{Map, ToToon, Value};
+
+type Error = serde_toon2::Error;
+type Result<T> = std::result::Result<T, Error>;
+
+/// Serializer whose output is a TOON [`Value`].
+///
+/// This is what [`to_value`](crate::value::to_value) uses. Numbers keep
+/// their Rust type (`I64` for signed integers, `U64` for unsigned, `F64`
+/// for floats), enums use the externally tagged representation
+/// (`"Variant"` or `{ Variant: content }`), and map keys that are numbers
+/// or bools are converted to strings.
+///
+/// # Example
+///
+/// ```
+/// use serde::Serialize;
+/// use toon_macro::{toon, value::Serializer};
+///
+/// let value = (1u8, -2, 2.0).serialize(Serializer).unwrap();
+/// assert_eq!(value, toon!([1u8, -2, 2.0]));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = Error;
+
+    type SerializeSeq = SerializeVec;
+    type SerializeTuple = SerializeVec;
+    type SerializeTupleStruct = SerializeVec;
+    type SerializeTupleVariant = SerializeVec;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeMap;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> {
+        Ok(v.to_toon())
+    }
+
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        Ok(Value::String(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        let mut map = Map::new();
+        map.insert(variant.to_owned(), value.serialize(self)?);
+        Ok(Value::Object(map))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeVec> {
+        Ok(SerializeVec {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeVec> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeVec> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVec> {
+        Ok(SerializeVec {
+            items: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap> {
+        Ok(SerializeMap {
+            map: Map::with_capacity(len.unwrap_or(0)),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeMap> {
+        Ok(SerializeMap {
+            map: Map::with_capacity(len),
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+/// Wrap `value` as `{ variant: value }` for non-unit enum variants.
+fn tagged(variant: Option<&'static str>, value: Value) -> Value {
+    match variant {
+        Some(variant) => {
+            let mut map = Map::new();
+            map.insert(variant.to_owned(), value);
+            Value::Object(map)
+        }
+        None => value,
+    }
+}
+
+/// Collects sequences, tuples and tuple variants.
+#[doc(hidden)]
+pub struct SerializeVec {
+    items: Vec<Value>,
+    variant: Option<&'static str>,
+}
+
+impl ser::SerializeSeq for SerializeVec {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.items.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(tagged(self.variant, Value::Array(self.items)))
+    }
+}
+
+impl ser::SerializeTuple for SerializeVec {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeVec {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVec {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// Collects maps, structs and struct variants.
+#[doc(hidden)]
+pub struct SerializeMap {
+    map: Map<String, Value>,
+    key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
+        let key = match key.serialize(Serializer)? {
+            Value::String(s) => s,
+            Value::Number(n) => match n {
+                crate::Number::I64(n) => n.to_string(),
+                crate::Number::U64(n) => n.to_string(),
+                crate::Number::F64(n) => n.to_string(),
+            },
+            Value::Bool(b) => b.to_string(),
+            _ => return Err(Error::custom("map keys must be strings, numbers or bools")),
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::custom("serialize_value called before serialize_key"))?;
+        self.map.insert(key, value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(tagged(self.variant, Value::Object(self.map)))
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.map
+            .insert(key.to_owned(), value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeMap {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        ser::SerializeMap::end(self)
+    }
+}
@@ -0,0 +1,396 @@
+//! Size and shape statistics of values.
+
+use std::fmt::{self, Write};
+
+use super::{Number, Value};
+use crate::encode::Encoder;
+
+/// Size and shape of a value, and how much shorter it is as TOON than as
+/// JSON.
+///
+/// The value is walked twice. The first pass counts the nodes and adds up
+/// the JSON length; the second runs the TOON encoder into a byte counter,
+/// so that `toon_len` cannot drift from what
+/// [`to_toon_string`](crate::to_toon_string) writes. Nothing is allocated
+/// for either text.
+///
+/// # Example
+///
+/// ```
+/// use toon_macro::{toon, value::ValueStats};
+///
+/// let stats = ValueStats::of(&toon!({
+///     users: [
+///         { id: 1, name: "Alice", admin: true },
+///         { id: 2, name: "Bob", admin: false }
+///     ]
+/// }));
+///
+/// assert_eq!(stats.nodes, 10);
+/// assert_eq!(stats.max_depth, 3);
+/// assert_eq!(stats.longest_array, 2);
+/// assert_eq!(stats.string_bytes, 8);
+/// assert_eq!((stats.arrays, stats.objects, stats.strings), (1, 3, 2));
+/// assert_eq!(stats.toon_len, Some(53));
+/// assert_eq!(stats.json_len, 84);
+/// assert_eq!(stats.to_string(), "10 nodes, depth 3, longest array 2, 8 string bytes; \
+///     TOON 53 bytes vs JSON 84 bytes (36.9% smaller)");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValueStats {
+    /// Number of values, including the root and every element and member.
+    pub nodes: usize,
+    /// Deepest nesting of arrays and objects: 0 for a primitive, 1 for an
+    /// array or object of primitives.
+    pub max_depth: usize,
+    /// Number of elements of the longest array.
+    pub longest_array: usize,
+    /// Total UTF-8 length of string values, not counting keys.
+    pub string_bytes: usize,
+    /// Number of `null`s.
+    pub nulls: usize,
+    /// Number of booleans.
+    pub bools: usize,
+    /// Number of numbers.
+    pub numbers: usize,
+    /// Number of strings.
+    pub strings: usize,
+    /// Number of arrays.
+    pub arrays: usize,
+    /// Number of objects.
+    pub objects: usize,
+    /// Length of the [`to_toon_string`](crate::to_toon_string) text, or
+    /// `None` for values that TOON cannot represent (arrays of arrays that
+    /// hold arrays or objects).
+    pub toon_len: Option<usize>,
+    /// Length of the compact JSON text, as `serde_json::to_string` writes it.
+    pub json_len: usize,
+}
+
+impl ValueStats {
+    /// Compute the statistics of `value`, in two passes over it.
+    pub fn of(value: &Value) -> Self {
+        let mut pass = Pass {
+            stats: ValueStats::default(),
+        };
+        pass.subtree(value, 0);
+        let mut counter = Counter(0);
+        let encoded = Encoder::new(&mut counter).value(value);
+        ValueStats {
+            toon_len: encoded.is_ok().then_some(counter.0),
+            ..pass.stats
+        }
+    }
+
+    /// How much shorter the TOON text is than the JSON text, in percent of
+    /// the JSON length. Negative when TOON is longer, and `None` when TOON
+    /// cannot represent the value.
+    pub fn savings_percent(&self) -> Option<f64> {
+        let toon_len = self.toon_len? as f64;
+        let json_len = self.json_len as f64;
+        Some((json_len - toon_len) / json_len * 100.0)
+    }
+}
+
+impl fmt::Display for ValueStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes, depth {}, longest array {}, {} string bytes; ",
+            self.nodes, self.max_depth, self.longest_array, self.string_bytes
+        )?;
+        match (self.toon_len, self.savings_percent()) {
+            (Some(toon_len), Some(savings)) if savings >= 0.0 => write!(
+                f,
+                "TOON {} bytes vs JSON {} bytes ({:.1}% smaller)",
+                toon_len, self.json_len, savings
+            ),
+            (Some(toon_len), Some(savings)) => write!(
+                f,
+                "TOON {} bytes vs JSON {} bytes ({:.1}% larger)",
+                toon_len, self.json_len, -savings
+            ),
+            _ => write!(f, "not representable as TOON, JSON {} bytes", self.json_len),
+        }
+    }
+}
+
+/// The traversal, counting each node and its share of the JSON text;
+/// `level` is the nesting level of the node being visited.
+struct Pass {
+    stats: ValueStats,
+}
+
+impl Pass {
+    /// Count `value` itself, and its own share of the JSON text.
+    fn node(&mut self, value: &Value, level: usize) {
+        let stats = &mut self.stats;
+        stats.nodes += 1;
+        stats.json_len += match value {
+            Value::Null => {
+                stats.nulls += 1;
+                4
+            }
+            Value::Bool(b) => {
+                stats.bools += 1;
+                if *b { 4 } else { 5 }
+            }
+            Value::Number(n) => {
+                stats.numbers += 1;
+                json_number_len(n)
+            }
+            Value::String(s) => {
+                stats.strings += 1;
+                stats.string_bytes += s.len();
+                json_string_len(s)
+            }
+            Value::Array(items) => {
+                stats.arrays += 1;
+                stats.max_depth = stats.max_depth.max(level + 1);
+                stats.longest_array = stats.longest_array.max(items.len());
+                2 + items.len().saturating_sub(1)
+            }
+            Value::Object(map) => {
+                stats.objects += 1;
+                stats.max_depth = stats.max_depth.max(level + 1);
+                2 + map.len().saturating_sub(1)
+                    + map
+                        .keys()
+                        .map(|key| json_string_len(key) + 1)
+                        .sum::<usize>()
+            }
+        };
+    }
+
+    /// Count `value` and its descendants.
+    fn subtree(&mut self, value: &Value, level: usize) {
+        self.node(value, level);
+        match value {
+            Value::Array(items) => items.iter().for_each(|item| self.subtree(item, level + 1)),
+            Value::Object(map) => map.values().for_each(|item| self.subtree(item, level + 1)),
+            _ => {}
+        }
+    }
+}
+
+/// A [`fmt::Write`] that only counts bytes.
+struct Counter(usize);
+
+impl Write for Counter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+fn digits(n: usize) -> usize {
+    n.checked_ilog10().unwrap_or(0) as usize + 1
+}
+
+/// Length of a JSON string, escaped as `serde_json` does.
+fn json_string_len(s: &str) -> usize {
+    2 + s
+        .bytes()
+        .map(|b| match b {
+            b'"' | b'\\' | b'\n' | b'\r' | b'\t' | 0x08 | 0x0c => 2,
+            0x00..=0x1f => 6,
+            _ => 1,
+        })
+        .sum::<usize>()
+}
+
+/// Length of a JSON number, formatted as `serde_json` does: integers in
+/// full, floats in their shortest form with at least one fraction digit
+/// (`1.0`, `0.001`) below 10^16 and down to 10^-5, and with an exponent
+/// (`1e+16`, `1.5e-7`) beyond. Non-finite floats are `null`.
+fn json_number_len(n: &Number) -> usize {
+    let mut counter = Counter(0);
+    match *n {
+        Number::I64(n) => {
+            let _ = write!(counter, "{}", n);
+            counter.0
+        }
+        Number::U64(n) => {
+            let _ = write!(counter, "{}", n);
+            counter.0
+        }
+        Number::F64(f) if !f.is_finite() => 4,
+        Number::F64(f) => {
+            let sign = f.is_sign_negative() as usize;
+            if f == 0.0 {
+                return sign + 3;
+            }
+            // `{:e}` gives the shortest digits: `d.ddde-x`.
+            let text = format!("{:e}", f.abs());
+            let (mantissa, exponent) = text.split_once('e').expect("`{:e}` has an exponent");
+            let len = mantissa.len() - mantissa.contains('.') as usize;
+            let exponent: isize = exponent.parse().expect("`{:e}` has an integer exponent");
+            // The value is 0.ddd * 10^point.
+            let point = exponent + 1;
+            let len = len as isize;
+            let body = if (len..=16).contains(&point) {
+                point + 2
+            } else if (1..=16).contains(&point) {
+                len + 1
+            } else if (-4..=0).contains(&point) {
+                2 - point + len
+            } else {
+                // With a sign either way.
+                let exponent_len = 1 + digits(exponent.unsigned_abs()) as isize;
+                let mantissa_len = if len == 1 { 1 } else { len + 1 };
+                mantissa_len + 1 + exponent_len
+            };
+            sign + body as usize
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_toon_string;
+
+    fn assert_lengths(value: &Value) {
+        let stats = ValueStats::of(value);
+        assert_eq!(
+            stats.toon_len,
+            Some(to_toon_string(value).unwrap().len()),
+            "TOON length of {:?}",
+            value
+        );
+        assert_eq!(
+            stats.json_len,
+            serde_json::to_string(value).unwrap().len(),
+            "JSON length of {:?}",
+            value
+        );
+    }
+
+    #[test]
+    fn test_lengths_match_encoders() {
+        for value in [
+            toon!(null),
+            toon!("plain"),
+            toon!("needs \"quotes\"\n"),
+            toon!({}),
+            toon!([]),
+            toon!({ a: 1, "b c": [1, "x", null], d: { e: { f: [] } } }),
+            toon!([[1, 2], [], ["a"]]),
+            toon!([{ id: 1, "full name": "A" }, { id: 2, "full name": "B" }]),
+            toon!({ rows: [{ a: 1, b: true }, { b: false, a: 2 }], "odd key": [1] }),
+            toon!([1, [1, 2], { a: 1 }, [], {}]),
+            toon!({
+                list: [
+                    { first: [1, 2], second: { x: 1 }, third: [{ a: 1 }, { a: 2 }] },
+                    { first: [], rest: [[1], [2]] },
+                    { first: [[1, 2]], more: [1, { b: 2 }] },
+                    { first: [{ a: 1 }, { a: 2 }], after: "x" },
+                    { first: [{ a: [1] }, 3] },
+                    { first: { nested: { deep: 1 } }, after: { y: "z" } },
+                    { first: "é\tü", "2nd": -0.0 }
+                ]
+            }),
+            toon!({ "ключ": "значение", "emoji 🎉": ["🎉", "\u{1}\u{1f}\u{7f}"] }),
+        ] {
+            assert_lengths(&value);
+        }
+    }
+
+    #[test]
+    fn test_lengths_match_encoders_on_generated_values() {
+        for value in crate::arbitrary::values(2000) {
+            let stats = ValueStats::of(&value);
+            if Encoder::new(&mut String::new()).value(&value).is_ok() {
+                assert_lengths(&value);
+            } else {
+                assert_eq!(stats.toon_len, None, "TOON length of {:?}", value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_float_lengths() {
+        for f in [
+            0.0,
+            -0.0,
+            1.0,
+            -2.5,
+            0.1,
+            123.456,
+            1e15,
+            1e16,
+            1.5e16,
+            9007199254740993.0,
+            1e-4,
+            1.25e-5,
+            1e-5,
+            1e-7,
+            -1.5e-7,
+            1e300,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            5e-324,
+            f64::NAN,
+            f64::INFINITY,
+        ] {
+            let value = Value::Number(Number::F64(f));
+            assert_eq!(
+                json_number_len(&Number::F64(f)),
+                serde_json::to_string(&value).unwrap().len(),
+                "for {:e}",
+                f
+            );
+            if f.is_finite() && f.fract() != 0.0 {
+                assert_lengths(&value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_counts() {
+        let stats = ValueStats::of(&toon!({
+            a: [1, 2.5, "xyz", null, true, [[]]],
+            b: { c: "é" }
+        }));
+        assert_eq!(
+            (stats.nulls, stats.bools, stats.numbers, stats.strings),
+            (1, 1, 2, 2)
+        );
+        assert_eq!((stats.arrays, stats.objects), (3, 2));
+        assert_eq!(stats.nodes, 11);
+        assert_eq!(stats.max_depth, 4);
+        assert_eq!(stats.longest_array, 6);
+        assert_eq!(stats.string_bytes, 5);
+
+        let scalar = ValueStats::of(&toon!(7));
+        assert_eq!((scalar.nodes, scalar.max_depth, scalar.numbers), (1, 0, 1));
+    }
+
+    #[test]
+    fn test_unrepresentable_values_are_still_counted() {
+        let value = toon!({ a: [[{ b: [1, 2] }], [3]] });
+        let stats = ValueStats::of(&value);
+        assert_eq!(stats.toon_len, None);
+        assert_eq!(stats.savings_percent(), None);
+        assert_eq!(stats.nodes, 9);
+        assert_eq!(stats.max_depth, 5);
+        assert_eq!(stats.json_len, serde_json::to_string(&value).unwrap().len());
+        assert!(
+            stats
+                .to_string()
+                .ends_with("not representable as TOON, JSON 25 bytes")
+        );
+    }
+
+    #[test]
+    fn test_savings() {
+        let stats = ValueStats::of(&toon!("x"));
+        assert_eq!((stats.toon_len, stats.json_len), (Some(1), 3));
+        assert!(stats.to_string().ends_with("(66.7% smaller)"));
+
+        let stats = ValueStats::of(&toon!(["-"]));
+        assert_eq!((stats.toon_len, stats.json_len), (Some(8), 5));
+        assert_eq!(stats.savings_percent(), Some(-60.0));
+        assert!(stats.to_string().ends_with("(60.0% larger)"));
+    }
+}
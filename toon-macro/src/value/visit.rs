@@ -0,0 +1,509 @@
+//! Depth-first traversal of values, and transforms built on it.
+//!
+//! [`walk`] and [`walk_mut`] visit every node of a value, parents before
+//! children and elements in order, passing each node's JSON pointer to the
+//! visitor. The transforms below rewrite a value in place.
+
+use std::mem;
+
+use super::{Number, Value};
+
+/// How a walk continues after [`Visitor::enter`] or [`VisitorMut::enter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Walk {
+    /// Visit the children of the node.
+    #[default]
+    Continue,
+    /// Do not visit the children of the node, but carry on with its
+    /// siblings.
+    Skip,
+    /// End the walk. No further `enter` or `leave` calls are made, including
+    /// for the node and its ancestors.
+    Stop,
+}
+
+/// Callbacks for [`walk`].
+///
+/// `path` is the JSON pointer of the node, `""` for the root.
+///
+/// # Example
+///
+/// ```
+/// use toon_macro::{toon, Value};
+/// use toon_macro::value::{walk, Visitor, Walk};
+///
+/// struct Emails(Vec<String>);
+///
+/// impl Visitor for Emails {
+///     fn enter(&mut self, path: &str, value: &Value) -> Walk {
+///         if path.ends_with("/email") {
+///             self.0.push(format!("{path} = {}", value.as_str().unwrap_or("?")));
+///         }
+///         Walk::Continue
+///     }
+/// }
+///
+/// let value = toon!({ users: [{ email: "a@x.io" }, { email: "b@x.io" }] });
+/// let mut emails = Emails(Vec::new());
+/// walk(&value, &mut emails);
+/// assert_eq!(emails.0, ["/users/0/email = a@x.io", "/users/1/email = b@x.io"]);
+/// ```
+pub trait Visitor {
+    /// Called on a node before its children.
+    fn enter(&mut self, path: &str, value: &Value) -> Walk {
+        let _ = (path, value);
+        Walk::Continue
+    }
+
+    /// Called on a node after its children, or right after `enter` returned
+    /// [`Walk::Skip`].
+    fn leave(&mut self, path: &str, value: &Value) {
+        let _ = (path, value);
+    }
+}
+
+/// Callbacks for [`walk_mut`], which may modify the nodes they visit.
+///
+/// Changes made by `enter` are walked: if it replaces a string with an
+/// object, the members of that object are visited next. `leave` sees the
+/// node after its children were visited, which suits bottom-up rewrites such
+/// as removing members that became empty.
+///
+/// # Example
+///
+/// ```
+/// use toon_macro::{toon, Value};
+/// use toon_macro::value::{walk_mut, VisitorMut};
+///
+/// /// Renames `id` members to `_id`, except at the top level.
+/// struct RenameIds;
+///
+/// impl VisitorMut for RenameIds {
+///     fn leave(&mut self, path: &str, value: &mut Value) {
+///         if let (false, Value::Object(map)) = (path.is_empty(), value) {
+///             if let Some(index) = map.get_index_of("id") {
+///                 let (_, id) = map.shift_remove_index(index).unwrap();
+///                 map.shift_insert(index, "_id".to_string(), id);
+///             }
+///         }
+///     }
+/// }
+///
+/// let mut value = toon!({ id: 1, items: [{ id: 2, name: "a" }] });
+/// walk_mut(&mut value, &mut RenameIds);
+/// assert_eq!(value, toon!({ id: 1, items: [{ _id: 2, name: "a" }] }));
+/// ```
+pub trait VisitorMut {
+    /// Called on a node before its children.
+    fn enter(&mut self, path: &str, value: &mut Value) -> Walk {
+        let _ = (path, value);
+        Walk::Continue
+    }
+
+    /// Called on a node after its children, or right after `enter` returned
+    /// [`Walk::Skip`].
+    fn leave(&mut self, path: &str, value: &mut Value) {
+        let _ = (path, value);
+    }
+}
+
+/// Visit `value` and its descendants depth-first.
+pub fn walk<V: Visitor + ?Sized>(value: &Value, visitor: &mut V) {
+    walk_node(value, &mut String::new(), visitor);
+}
+
+/// Visit `value` and its descendants depth-first, allowing changes.
+pub fn walk_mut<V: VisitorMut + ?Sized>(value: &mut Value, visitor: &mut V) {
+    walk_node_mut(value, &mut String::new(), visitor);
+}
+
+/// Returns `false` once the walk is stopped.
+fn walk_node<V: Visitor + ?Sized>(value: &Value, path: &mut String, visitor: &mut V) -> bool {
+    match visitor.enter(path, value) {
+        Walk::Stop => return false,
+        Walk::Skip => {}
+        Walk::Continue => {
+            let len = path.len();
+            match value {
+                Value::Array(items) => {
+                    for (index, item) in items.iter().enumerate() {
+                        push_index(path, index);
+                        let go_on = walk_node(item, path, visitor);
+                        path.truncate(len);
+                        if !go_on {
+                            return false;
+                        }
+                    }
+                }
+                Value::Object(map) => {
+                    for (key, item) in map {
+                        push_key(path, key);
+                        let go_on = walk_node(item, path, visitor);
+                        path.truncate(len);
+                        if !go_on {
+                            return false;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    visitor.leave(path, value);
+    true
+}
+
+/// Returns `false` once the walk is stopped.
+fn walk_node_mut<V: VisitorMut + ?Sized>(
+    value: &mut Value,
+    path: &mut String,
+    visitor: &mut V,
+) -> bool {
+    match visitor.enter(path, value) {
+        Walk::Stop => return false,
+        Walk::Skip => {}
+        Walk::Continue => {
+            let len = path.len();
+            match value {
+                Value::Array(items) => {
+                    for (index, item) in items.iter_mut().enumerate() {
+                        push_index(path, index);
+                        let go_on = walk_node_mut(item, path, visitor);
+                        path.truncate(len);
+                        if !go_on {
+                            return false;
+                        }
+                    }
+                }
+                Value::Object(map) => {
+                    for (key, item) in map.iter_mut() {
+                        push_key(path, key);
+                        let go_on = walk_node_mut(item, path, visitor);
+                        path.truncate(len);
+                        if !go_on {
+                            return false;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    visitor.leave(path, value);
+    true
+}
+
+fn push_index(path: &mut String, index: usize) {
+    use std::fmt::Write;
+    let _ = write!(path, "/{index}");
+}
+
+fn push_key(path: &mut String, key: &str) {
+    path.push('/');
+    for c in key.chars() {
+        match c {
+            '~' => path.push_str("~0"),
+            '/' => path.push_str("~1"),
+            c => path.push(c),
+        }
+    }
+}
+
+/// Remove `null` members and elements, at every depth. A `null` root is
+/// kept.
+///
+/// ```
+/// use toon_macro::{toon, value::prune_nulls};
+///
+/// let mut value = toon!({ a: null, b: [1, null, { c: null }] });
+/// prune_nulls(&mut value);
+/// assert_eq!(value, toon!({ b: [1, {}] }));
+/// ```
+pub fn prune_nulls(value: &mut Value) {
+    walk_mut(
+        value,
+        &mut OnLeave(|value: &mut Value| match value {
+            Value::Array(items) => items.retain(|item| !item.is_null()),
+            Value::Object(map) => map.retain(|_, item| !item.is_null()),
+            _ => {}
+        }),
+    );
+}
+
+/// Remove `null`s and empty arrays and objects, at every depth, including
+/// containers that only held such values. Empty strings are kept, and so is
+/// the root.
+///
+/// ```
+/// use toon_macro::{toon, value::prune_empty};
+///
+/// let mut value = toon!({ a: { b: [null, {}] }, c: "", d: [[], 0] });
+/// prune_empty(&mut value);
+/// assert_eq!(value, toon!({ c: "", d: [0] }));
+/// ```
+pub fn prune_empty(value: &mut Value) {
+    fn is_empty(value: &Value) -> bool {
+        match value {
+            Value::Null => true,
+            Value::Array(items) => items.is_empty(),
+            Value::Object(map) => map.is_empty(),
+            _ => false,
+        }
+    }
+
+    walk_mut(
+        value,
+        &mut OnLeave(|value: &mut Value| match value {
+            Value::Array(items) => items.retain(|item| !is_empty(item)),
+            Value::Object(map) => map.retain(|_, item| !is_empty(item)),
+            _ => {}
+        }),
+    );
+}
+
+/// Sort the members of every object by key.
+///
+/// ```
+/// use toon_macro::{toon, value::sort_keys};
+///
+/// let mut value = toon!({ b: 1, a: [{ d: 2, c: 3 }] });
+/// sort_keys(&mut value);
+/// assert_eq!(toon_macro::to_toon_string(&value).unwrap(), "a[1]{c,d}:\n  3,2\nb: 1");
+/// ```
+pub fn sort_keys(value: &mut Value) {
+    walk_mut(
+        value,
+        &mut OnLeave(|value: &mut Value| {
+            if let Value::Object(map) = value {
+                map.sort_keys();
+            }
+        }),
+    );
+}
+
+/// Replace every string value with `f` of it. Object keys are left alone.
+///
+/// ```
+/// use toon_macro::{toon, value::map_strings};
+///
+/// let mut value = toon!({ name: "  Alice ", tags: [" a", "b "] });
+/// map_strings(&mut value, |s| s.trim().to_string());
+/// assert_eq!(value, toon!({ name: "Alice", tags: ["a", "b"] }));
+/// ```
+pub fn map_strings<F: FnMut(&str) -> String>(value: &mut Value, mut f: F) {
+    walk_mut(
+        value,
+        &mut OnLeave(|value: &mut Value| {
+            if let Value::String(s) = value {
+                *s = f(s);
+            }
+        }),
+    );
+}
+
+/// Replace every number with `f` of it.
+///
+/// ```
+/// use toon_macro::{toon, Number, value::map_numbers};
+///
+/// let mut value = toon!({ price: 9.987, qty: 3, ratio: [0.333333, 1.5] });
+/// map_numbers(&mut value, |n| match n {
+///     Number::F64(f) => Number::F64((f * 100.0).round() / 100.0),
+///     n => n,
+/// });
+/// assert_eq!(value, toon!({ price: 9.99, qty: 3, ratio: [0.33, 1.5] }));
+/// ```
+pub fn map_numbers<F: FnMut(Number) -> Number>(value: &mut Value, mut f: F) {
+    walk_mut(
+        value,
+        &mut OnLeave(|value: &mut Value| {
+            if let Value::Number(n) = value {
+                *n = f(mem::replace(n, Number::I64(0)));
+            }
+        }),
+    );
+}
+
+/// Keep only the object members whose key satisfies `keep`, at every depth.
+/// The members of removed values are not visited.
+///
+/// ```
+/// use toon_macro::{toon, value::retain_keys};
+///
+/// let mut value = toon!({ id: 1, _rev: "3-a", items: [{ id: 2, _tmp: true }] });
+/// retain_keys(&mut value, |key| !key.starts_with('_'));
+/// assert_eq!(value, toon!({ id: 1, items: [{ id: 2 }] }));
+/// ```
+pub fn retain_keys<F: FnMut(&str) -> bool>(value: &mut Value, mut keep: F) {
+    walk_mut(
+        value,
+        &mut OnEnter(|value: &mut Value| {
+            if let Value::Object(map) = value {
+                map.retain(|key, _| keep(key));
+            }
+        }),
+    );
+}
+
+/// A [`VisitorMut`] running a closure on each node before its children.
+struct OnEnter<F>(F);
+
+impl<F: FnMut(&mut Value)> VisitorMut for OnEnter<F> {
+    fn enter(&mut self, _: &str, value: &mut Value) -> Walk {
+        (self.0)(value);
+        Walk::Continue
+    }
+}
+
+/// A [`VisitorMut`] running a closure on each node after its children.
+struct OnLeave<F>(F);
+
+impl<F: FnMut(&mut Value)> VisitorMut for OnLeave<F> {
+    fn leave(&mut self, _: &str, value: &mut Value) {
+        (self.0)(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ValueExt;
+
+    /// Records every call as `enter path` / `leave path`.
+    #[derive(Default)]
+    struct Trace {
+        calls: Vec<String>,
+        skip: &'static str,
+        stop: &'static str,
+    }
+
+    impl Visitor for Trace {
+        fn enter(&mut self, path: &str, _: &Value) -> Walk {
+            self.calls.push(format!("enter {path}"));
+            if path == self.stop {
+                Walk::Stop
+            } else if path == self.skip {
+                Walk::Skip
+            } else {
+                Walk::Continue
+            }
+        }
+
+        fn leave(&mut self, path: &str, _: &Value) {
+            self.calls.push(format!("leave {path}"));
+        }
+    }
+
+    #[test]
+    fn test_walk_order_and_paths() {
+        let value = toon!({ a: [1, { "b/c": 2 }], "~": null });
+        let mut trace = Trace {
+            stop: "-",
+            skip: "-",
+            ..Trace::default()
+        };
+        walk(&value, &mut trace);
+        assert_eq!(
+            trace.calls,
+            [
+                "enter ",
+                "enter /a",
+                "enter /a/0",
+                "leave /a/0",
+                "enter /a/1",
+                "enter /a/1/b~1c",
+                "leave /a/1/b~1c",
+                "leave /a/1",
+                "leave /a",
+                "enter /~0",
+                "leave /~0",
+                "leave ",
+            ]
+        );
+        for call in &trace.calls {
+            let path = call.split_once(' ').unwrap().1;
+            assert!(value.pointer(path).is_some(), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_walk_skip_and_stop() {
+        let value = toon!({ a: [1, 2], b: { c: 3 }, d: 4 });
+
+        let mut trace = Trace {
+            skip: "/a",
+            stop: "/b/c",
+            ..Trace::default()
+        };
+        walk(&value, &mut trace);
+        assert_eq!(
+            trace.calls,
+            ["enter ", "enter /a", "leave /a", "enter /b", "enter /b/c"]
+        );
+    }
+
+    #[test]
+    fn test_walk_mut_visits_replacements() {
+        struct Expand;
+
+        impl VisitorMut for Expand {
+            fn enter(&mut self, _: &str, value: &mut Value) -> Walk {
+                if value.as_str() == Some("pair") {
+                    *value = toon!(["pair?", "pair?"]);
+                }
+                if value.as_str() == Some("pair?") {
+                    *value = toon!(0);
+                }
+                Walk::Continue
+            }
+        }
+
+        let mut value = toon!({ x: "pair", y: ["pair"] });
+        walk_mut(&mut value, &mut Expand);
+        assert_eq!(value, toon!({ x: [0, 0], y: [[0, 0]] }));
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut value = toon!([null, { a: null, b: { c: [null] } }, [], ""]);
+        let mut nulls = value.clone();
+        prune_nulls(&mut nulls);
+        assert_eq!(nulls, toon!([{ b: { c: [] } }, [], ""]));
+        prune_empty(&mut value);
+        assert_eq!(value, toon!([""]));
+
+        let mut root = toon!(null);
+        prune_empty(&mut root);
+        assert_eq!(root, toon!(null));
+        let mut root = toon!({ a: {} });
+        prune_empty(&mut root);
+        assert_eq!(root, toon!({}));
+    }
+
+    #[test]
+    fn test_sort_keys_is_recursive() {
+        let mut value = toon!({ b: { z: 1, y: [{ q: 1, p: 2 }] }, a: 0 });
+        sort_keys(&mut value);
+        let keys =
+            |value: &Value| -> Vec<String> { value.as_object().unwrap().keys().cloned().collect() };
+        assert_eq!(keys(&value), ["a", "b"]);
+        assert_eq!(keys(value.pointer("/b").unwrap()), ["y", "z"]);
+        assert_eq!(keys(value.pointer("/b/y/0").unwrap()), ["p", "q"]);
+    }
+
+    #[test]
+    fn test_map_and_retain_skip_keys_and_other_types() {
+        let mut value = toon!({ "k": "v", n: 1, list: ["x", 2.5, true] });
+        map_strings(&mut value, |s| s.to_uppercase());
+        map_numbers(&mut value, |n| Number::F64(n.as_f64() * 2.0));
+        assert_eq!(value, toon!({ "k": "V", n: 2.0, list: ["X", 5.0, true] }));
+
+        let mut seen = Vec::new();
+        retain_keys(&mut value, |key| {
+            seen.push(key.to_string());
+            key != "list"
+        });
+        assert_eq!(value, toon!({ "k": "V", n: 2.0 }));
+        assert_eq!(seen, ["k", "n", "list"]);
+    }
+}
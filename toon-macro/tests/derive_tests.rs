@@ -2,7 +2,7 @@
 
 #![cfg(feature = "derive")]
 
-use toon_macro::{ToonTable, Value, toon};
+use toon_macro::{FromToon, ToToon, ToonTable, Value, toon};
 
 #[derive(Debug, Clone, PartialEq, ToonTable)]
 struct User {
@@ -117,11 +117,11 @@ fn test_toon_table_rename() {
 
     let table = RenamedFields::to_toon_table(&items);
 
-    if let Value::Object(map) = &table {
-        if let Some(Value::Array(cols)) = map.get("columns") {
-            assert_eq!(cols[0], Value::String("userId".into()));
-            assert_eq!(cols[1], Value::String("userName".into()));
-        }
+    if let Value::Object(map) = &table
+        && let Some(Value::Array(cols)) = map.get("columns")
+    {
+        assert_eq!(cols[0], Value::String("userId".into()));
+        assert_eq!(cols[1], Value::String("userName".into()));
     }
 }
 
@@ -172,3 +172,127 @@ fn test_toon_table_mixed_types() {
 
     assert_eq!(items, decoded);
 }
+
+#[derive(Debug, Clone, PartialEq)]
+struct Level(u8);
+
+impl ToToon for Level {
+    fn to_toon(&self) -> Value {
+        toon!(format!("L{}", self.0))
+    }
+}
+
+impl FromToon for Level {
+    fn from_toon(value: &Value) -> toon_macro::Result<Self> {
+        let s = String::from_toon(value)?;
+        s.strip_prefix('L')
+            .and_then(|n| n.parse().ok())
+            .map(Level)
+            .ok_or_else(|| toon_macro::Error::conversion("invalid level"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, ToonTable)]
+struct Player {
+    name: String,
+    level: Level,
+    tags: Vec<String>,
+    rank: Option<u16>,
+}
+
+#[test]
+fn test_toon_table_custom_field_types() {
+    let players = vec![Player {
+        name: "Ada".into(),
+        level: Level(3),
+        tags: vec!["pro".into()],
+        rank: None,
+    }];
+
+    let table = Player::to_toon_table(&players);
+    assert_eq!(
+        table,
+        toon!({
+            columns: ["name", "level", "tags", "rank"],
+            rows: [["Ada", "L3", ["pro"], null]]
+        })
+    );
+
+    // The same impls are used by `toon!` value expressions.
+    assert_eq!(toon!({ level: Level(3) }), toon!({ level: "L3" }));
+    assert_eq!(Player::from_toon_table(&table).unwrap(), players);
+}
+
+/// Implements only the deprecated cell traits, as the 0.1 docs suggested.
+#[derive(Debug, Clone, PartialEq)]
+struct Grade(char);
+
+#[allow(deprecated)]
+impl toon_macro::table::IntoToonValue for Grade {
+    fn to_toon_value(&self) -> Value {
+        Value::String(self.0.to_string())
+    }
+}
+
+#[allow(deprecated)]
+impl toon_macro::table::FromToonValue for Grade {
+    fn from_toon_value(value: &Value) -> toon_macro::Result<Self> {
+        let s = String::from_toon(value)?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(Grade(c)),
+            _ => Err(toon_macro::Error::conversion("invalid grade")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, ToonTable)]
+struct Report {
+    student: String,
+    grade: Grade,
+}
+
+#[test]
+fn test_toon_table_legacy_cell_traits() {
+    let reports = vec![Report {
+        student: "Ada".into(),
+        grade: Grade('A'),
+    }];
+
+    let table = Report::to_toon_table(&reports);
+    assert_eq!(
+        table,
+        toon!({
+            columns: ["student", "grade"],
+            rows: [["Ada", "A"]]
+        })
+    );
+    assert_eq!(Report::from_toon_table(&table).unwrap(), reports);
+    assert_eq!(toon!({ grade: Grade('B') }), toon!({ grade: "B" }));
+}
+
+#[test]
+fn test_legacy_types_in_patterns() {
+    let report = toon!({ student: "Ada", grade: "A" });
+    toon_macro::toon_let!({ student: name @ String, grade: grade @ Grade } = report else {
+        panic!("pattern did not match");
+    });
+    assert_eq!((name.as_str(), grade), ("Ada", Grade('A')));
+
+    let err =
+        toon_macro::toon_match!(toon!({ grade: "AB" }), { grade: g @ Grade } => g).unwrap_err();
+    assert!(err.to_string().contains("invalid grade"), "{}", err);
+}
+
+#[test]
+fn test_legacy_types_in_require() {
+    use toon_macro::ValueExt;
+
+    let report = toon!({ student: "Ada", grade: "A", retake: { grade: "AB" } });
+    assert_eq!(report.require::<Grade>("grade").unwrap(), Grade('A'));
+    let err = report.require::<Grade>("/retake/grade").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Conversion error: invalid grade at `/retake/grade`"
+    );
+}